@@ -0,0 +1,88 @@
+//! Daemon admin menu: commands aimed at the daemon process itself rather than its config,
+//! sent as `LOG_LEVEL` and `STOP` notifications.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+use crate::form::Field;
+use crate::node::NodeId;
+use crate::settings::{self, LOG_LEVELS};
+
+/// What has to be typed to confirm stopping a daemon.
+pub const STOP_CHALLENGE: &str = "stop";
+
+/// Entries of the admin menu, in the order shown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Item {
+    LogLevel,
+    Stop,
+}
+
+/// Admin menu for one node.
+#[derive(Clone, Debug)]
+pub struct AdminMenu {
+    /// Node the commands are for.
+    pub node: NodeId,
+    /// Selected entry.
+    pub item: Item,
+    /// Log level to set, starting at the daemon's current one.
+    pub log_level: Field,
+}
+
+impl AdminMenu {
+    /// Opens the menu on a node, given its last reported log level.
+    #[must_use]
+    pub fn new(node: &NodeId, log_level: Option<u32>) -> Self {
+        let current = log_level.map(|level| settings::log_level_name(level.into()));
+        Self {
+            node: node.clone(),
+            item: Item::LogLevel,
+            log_level: Field::choice("Log level", &LOG_LEVELS, current.as_deref()),
+        }
+    }
+
+    /// Handle a key press: up/down to move between entries, left/right to pick a log
+    /// level. Returns whether the key was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        match (self.item, key_event.code) {
+            (_, KeyCode::Up) => self.item = Item::LogLevel,
+            (_, KeyCode::Down) => self.item = Item::Stop,
+            (Item::LogLevel, _) => return self.log_level.handle_key(key_event),
+            (Item::Stop, _) => return false,
+        }
+        true
+    }
+
+    /// Picked log level as the daemon numbers them, if one has been picked.
+    #[must_use]
+    pub fn level(&self) -> Option<u32> {
+        LOG_LEVELS
+            .iter()
+            .position(|name| *name == self.log_level.value)
+            .and_then(|idx| u32::try_from(idx).ok())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test that the menu starts at the daemon's log level and steps through the others.
+    #[test]
+    fn test_handle_key() {
+        let node = String::from("10.0.0.1");
+        let mut menu = AdminMenu::new(&node, Some(1));
+        assert_eq!(menu.log_level.value, "info");
+        assert!(menu.handle_key(KeyEvent::from(KeyCode::Left)));
+        assert_eq!(menu.level(), Some(0));
+
+        assert!(menu.handle_key(KeyEvent::from(KeyCode::Down)));
+        assert_eq!(menu.item, Item::Stop);
+        assert!(!menu.handle_key(KeyEvent::from(KeyCode::Right)));
+        assert_eq!(menu.level(), Some(0));
+
+        let menu = AdminMenu::new(&node, Some(9));
+        assert_eq!(menu.log_level.value, "9");
+        assert_eq!(menu.level(), None);
+        assert_eq!(AdminMenu::new(&node, None).level(), None);
+    }
+}
@@ -1,5 +1,6 @@
 use std::time;
 
+use crate::node::NodeId;
 use crate::opensnitch_proto;
 
 #[derive(Clone, Debug)]
@@ -18,6 +19,16 @@ impl Priority {
             _ => Priority::High,
         }
     }
+
+    /// Inverse of [`Priority::new`].
+    #[must_use]
+    pub fn code(&self) -> i32 {
+        match self {
+            Priority::Low => 0,
+            Priority::Medium => 1,
+            Priority::High => 2,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -36,6 +47,16 @@ impl Type {
             _ => Type::Info,
         }
     }
+
+    /// Inverse of [`Type::new`].
+    #[must_use]
+    pub fn code(&self) -> i32 {
+        match self {
+            Type::Error => 0,
+            Type::Warning => 1,
+            Type::Info => 2,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -63,10 +84,26 @@ impl What {
             _ => What::Generic,
         }
     }
+
+    /// Inverse of [`What::new`].
+    #[must_use]
+    pub fn code(&self) -> i32 {
+        match self {
+            What::Generic => 0,
+            What::ProcMonitor => 1,
+            What::Firewall => 2,
+            What::Connection => 3,
+            What::Rule => 4,
+            What::Netlink => 5,
+            What::KernelEvent => 6,
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct Alert {
+    /// Node that raised the alert, or none if it came from the TUI itself.
+    pub node: Option<NodeId>,
     pub timestamp: time::SystemTime,
     pub priority: Priority,
     pub r#type: Type,
@@ -76,7 +113,11 @@ pub struct Alert {
 
 impl Alert {
     #[must_use]
-    pub fn new(ts: time::SystemTime, proto: &opensnitch_proto::pb::Alert) -> Alert {
+    pub fn new(
+        node: Option<NodeId>,
+        ts: time::SystemTime,
+        proto: &opensnitch_proto::pb::Alert,
+    ) -> Alert {
         let msg = match &proto.data {
             Some(data) => match data {
                 opensnitch_proto::pb::alert::Data::Text(v) => v.clone(),
@@ -86,6 +127,7 @@ impl Alert {
         };
 
         Alert {
+            node,
             timestamp: ts,
             priority: Priority::new(proto.priority),
             r#type: Type::new(proto.r#type),
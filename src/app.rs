@@ -1,57 +1,253 @@
+use crate::admin::{self, AdminMenu};
 use crate::alert;
-use crate::event::{AppEvent, ConnectionEvent, Event, EventHandler, PingEvent};
+use crate::audit::{self, AuditLog};
+use crate::breakdown::{Breakdown, Names, StatsView};
+use crate::event::{
+    AppEvent, ConnectionEvent, Event, EventHandler, Peer, PingEvent, SubscribeEvent,
+};
+use crate::node::{Health, Node, NodeId, StreamState};
 use crate::opensnitch_proto::pb;
-use crate::server::OpenSnitchUIServer;
+use crate::server::{
+    BindAddress, NotificationSenders, OpenSnitchUIServer, PendingRules, ServerState, TlsOptions,
+};
 use ratatui::{
     DefaultTerminal,
     crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
 };
 
 use crate::constants;
+use crate::firewall::{self, FirewallView};
+use crate::firewall_editor::FwRuleEditor;
+use crate::history::{EventHistory, HistoryView};
+use crate::nft;
+use crate::notification::{self, NotificationTracker, Outcome};
+use crate::opensnitch_json::OpenSnitchDaemonConfig;
 use crate::operator_util;
+use crate::rule_editor::RuleEditor;
+use crate::rules::RulesView;
+use crate::settings::{self, SettingsEditor};
+use crate::sockets::{self, SocketsMonitor, SocketsView};
+use crate::store::{Retention, Store, Writer};
+use crate::task::PidMonitor;
 
-use std::collections::VecDeque;
-use std::net::SocketAddr;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
-use tokio::sync::{Mutex, mpsc};
-use tonic::Status;
+use tokio::sync::Mutex;
+
+/// Top-level screens, cycled through with Tab.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum View {
+    /// Stats, connection prompts and alerts.
+    Main,
+    /// Connections the selected node has seen recently, and the rules they matched.
+    History,
+    /// The selected node's busiest hosts, ports, executables, users and the like.
+    Stats,
+    /// The selected node's rules.
+    Rules,
+    /// The selected node's system firewall chains and rules.
+    Firewall,
+    /// Sockets open on the selected node's host.
+    Sockets,
+    /// Notifications sent to the selected node and the daemon's replies.
+    Notifications,
+    /// The selected node's daemon config, editable.
+    Settings,
+}
+
+impl View {
+    /// Every view, in tab order.
+    pub const ALL: [View; 8] = [
+        View::Main,
+        View::History,
+        View::Stats,
+        View::Rules,
+        View::Firewall,
+        View::Sockets,
+        View::Notifications,
+        View::Settings,
+    ];
+
+    /// Tab label.
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            View::Main => "Connections",
+            View::History => "History",
+            View::Stats => "Stats",
+            View::Rules => "Rules",
+            View::Firewall => "Firewall",
+            View::Sockets => "Sockets",
+            View::Notifications => "Notifications",
+            View::Settings => "Settings",
+        }
+    }
+
+    /// The next view forwards or backwards in tab order, wrapping around.
+    #[must_use]
+    pub fn cycle(self, forward: bool) -> View {
+        let idx = View::ALL.iter().position(|view| *view == self).unwrap_or(0);
+        let len = View::ALL.len();
+        if forward {
+            View::ALL[(idx + 1) % len]
+        } else {
+            View::ALL[(idx + len - 1) % len]
+        }
+    }
+}
+
+/// Notifications that change the selected node's rules.
+pub const RULE_ACTIONS: [pb::Action; 4] = [
+    pb::Action::ChangeRule,
+    pb::Action::EnableRule,
+    pb::Action::DisableRule,
+    pb::Action::DeleteRule,
+];
+
+/// Notifications that switch interception or the system firewall.
+pub const CONTROL_ACTIONS: [pb::Action; 5] = [
+    pb::Action::EnableInterception,
+    pb::Action::DisableInterception,
+    pb::Action::EnableFirewall,
+    pb::Action::DisableFirewall,
+    pb::Action::ReloadFwRules,
+];
+
+/// Notifications that act on the daemon process itself. These, and the
+/// [`CONTROL_ACTIONS`], are kept in the audit trail.
+pub const ADMIN_ACTIONS: [pb::Action; 2] = [pb::Action::LogLevel, pb::Action::Stop];
+
+/// How long an alert stays in the alerts list. It's kept in the store, if there's one.
+pub const ALERT_LIFETIME: std::time::Duration = std::time::Duration::from_mins(1);
+
+/// A yes/no question put to the user, such as "Delete rule allow-dns?", and the
+/// notification it stands for. Pressing `y` sends `action`, with `data`, `rules` and
+/// `sys_firewall`, to `node`; any other key drops it. With a challenge, typed keys go
+/// towards it instead, and `enter` sends it once it's typed out.
+#[derive(Clone, Debug)]
+pub struct Confirmation {
+    /// Question to put to the user, naming what `y` will do.
+    pub prompt: String,
+    /// Node it was asked about, which it goes to even if another is selected by then.
+    pub node: NodeId,
+    /// Notification to send on `y`. Admin commands also go in the audit trail.
+    pub action: pb::Action,
+    pub data: String,
+    pub rules: Vec<pb::Rule>,
+    pub sys_firewall: Option<pb::SysFirewall>,
+    /// Text to type to confirm, for commands a stray `y` shouldn't set off.
+    pub challenge: Option<String>,
+    /// What's been typed towards the challenge so far.
+    pub typed: String,
+}
 
 /// Application.
 #[derive(Debug)]
 pub struct App {
     /// Is the application running?
     pub running: bool,
+    /// Why the application stopped, if it didn't stop cleanly.
+    pub failure: Option<String>,
     /// Event handler.
     pub events: EventHandler,
     /// Server
     pub server: OpenSnitchUIServer,
-    /// Rx Pings.
-    pub rx_pings: u64,
-    /// Peer (`OpenSnitch` daemon) address.
-    pub peer: Option<std::net::SocketAddr>,
-    /// Latest stats to present to UI.
-    pub current_stats: Option<pb::Statistics>,
+    /// Last reported state of the server.
+    pub server_state: ServerState,
+    /// Daemons seen by the server, keyed by node.
+    pub nodes: BTreeMap<NodeId, Node>,
+    /// Node that views and actions are scoped to.
+    pub selected_node: Option<NodeId>,
     /// Vector of alerts
     pub current_alerts: VecDeque<alert::Alert>,
     /// Alert list head in UI.
     pub alert_list_render_offset: usize,
-    /// Channel sender to generate notifications for a daemon towards.
-    /// The sender handle gets replaced to the latest client connection.
+    /// Screen being shown.
+    pub view: View,
+    /// Notifications sent to daemons, and their replies.
+    pub notifications: NotificationTracker,
+    /// Notification log head in UI.
+    pub notification_log_offset: usize,
+    /// Events from every node's pings, each kept once.
+    pub history: EventHistory,
+    /// Writer to the on-disk store of events, alerts, decisions and stats, if keeping one.
+    pub store: Option<Writer>,
+    /// State of the history view.
+    pub history_view: HistoryView,
+    /// This host's names for the uids and ports in the stats view, used for local nodes.
+    pub names: Names,
+    /// State of the stats view.
+    pub stats_view: StatsView,
+    /// State of the rules view.
+    pub rules_view: RulesView,
+    /// State of the firewall view.
+    pub firewall_view: FirewallView,
+    /// State of the sockets view.
+    pub sockets_view: SocketsView,
+    /// Process being watched by a daemon's pid monitor task, shown in the main view.
+    pub pid_monitor: Option<PidMonitor>,
+    /// Daemon admin menu, shown over the current view.
+    pub admin_menu: Option<AdminMenu>,
+    /// Notification awaiting the user's go-ahead, shown over the current view.
+    pub confirmation: Option<Confirmation>,
+    /// Daemon commands sent, and what became of them.
+    pub audit: AuditLog,
+    /// Settings form for the selected node, once it has sent its config.
+    pub settings: Option<SettingsEditor>,
+    /// Channel senders to generate notifications for each daemon towards.
+    /// A node's sender handle gets replaced to its latest client connection.
     /// Race protection enabled by the mutex.
-    pub notification_sender: Arc<Mutex<mpsc::Sender<Result<pb::Notification, Status>>>>,
-    /// Info on the current connection awaiting a rule determination.
-    pub current_connection: Option<ConnectionEvent>,
-    /// Rule sender.
-    pub rule_sender: mpsc::Sender<pb::Rule>,
-    /// gRPC server IP and port to bind to.
-    bind_address: SocketAddr,
+    pub notification_senders: NotificationSenders,
+    /// Reply slots for daemons waiting on a rule.
+    pub pending_rules: PendingRules,
     /// Default action to be sent to connected daemons.
     default_action: constants::DefaultAction,
+    /// Action for connections still awaiting disposition when the app exits.
+    exit_action: constants::Action,
     /// Temporary rule lifetime.
     pub temp_rule_lifetime: constants::Duration,
     /// The duration up to which app waits for user to make a disposition
     /// (allow/deny) on a trapped connection.
     connection_disposition_timeout: std::time::Duration,
+    /// How long a node may go without pinging before it's flagged as stale.
+    pub stale_threshold: std::time::Duration,
+}
+
+/// Startup settings for [`App`], as given on the command line.
+#[derive(Clone, Debug)]
+pub struct AppOptions {
+    /// Bind string for the gRPC server.
+    pub bind: String,
+    /// TLS settings for the gRPC server, if terminating TLS.
+    pub tls: Option<TlsOptions>,
+    /// Whether to allow a plaintext listener on a non-loopback address.
+    pub allow_insecure_bind: bool,
+    /// Default action to be sent to connected daemons.
+    pub default_action: String,
+    /// Action for connections still awaiting disposition when the app exits.
+    /// Falls back to the default action.
+    pub exit_action: Option<String>,
+    /// Temporary rule lifetime.
+    pub temp_rule_lifetime: String,
+    /// Seconds to wait on a disposition for a trapped connection.
+    pub dispo_seconds: u64,
+    /// Seconds without a ping before a node is flagged as stale.
+    pub stale_seconds: u64,
+    /// Where to keep the on-disk store.
+    pub store: StoreLocation,
+}
+
+/// Where the on-disk store goes, if anywhere.
+#[derive(Clone, Debug)]
+pub enum StoreLocation {
+    /// Not keeping one, as asked.
+    Off,
+    /// Keeping one was asked for, but there's no data directory to keep it in.
+    NoDataDir,
+    /// Database file to keep it in.
+    File(PathBuf),
 }
 
 impl App {
@@ -59,29 +255,28 @@ impl App {
     /// # Errors
     /// Returns an error for invalid input arg.
     #[allow(clippy::missing_panics_doc)]
-    pub fn new(
-        bind_string: &String,
-        default_action_in: &String,
-        temp_rule_lifetime: &String,
-        connection_disposition_timeout_in: &u64,
-    ) -> Result<Self, String> {
-        if bind_string.starts_with("unix") {
-            return Err(String::from("Unix domain sockets not supported"));
-        }
-        let maybe_bind_addr = bind_string.parse::<SocketAddr>();
-        if maybe_bind_addr.is_err() {
+    pub fn new(options: &AppOptions) -> Result<Self, String> {
+        let bind_address = BindAddress::parse(&options.bind)?;
+        let tls_config = options.tls.as_ref().map(TlsOptions::load).transpose()?;
+        // Anyone who can reach a plaintext listener can feed us prompts and read our rules.
+        if tls_config.is_none() && !bind_address.is_local() && !options.allow_insecure_bind {
             return Err(format!(
-                "Error parsing bind address '{}' : {}",
-                bind_string,
-                maybe_bind_addr.unwrap_err()
+                "Refusing to bind to non-loopback address {bind_address} without TLS"
             ));
         }
 
+        let default_action_in = &options.default_action;
         let maybe_default_action = constants::DefaultAction::new(default_action_in);
         if maybe_default_action.is_err() {
             return Err(format!("Invalid default action: {default_action_in}"));
         }
 
+        let exit_action_in = options.exit_action.as_ref().unwrap_or(default_action_in);
+        let Ok(exit_action) = constants::DefaultAction::new(exit_action_in) else {
+            return Err(format!("Invalid exit action: {exit_action_in}"));
+        };
+
+        let temp_rule_lifetime = &options.temp_rule_lifetime;
         let maybe_temp_rule_lifetime = constants::Duration::new(temp_rule_lifetime);
         if maybe_temp_rule_lifetime.is_err() {
             return Err(format!(
@@ -91,58 +286,82 @@ impl App {
 
         // The client RPC context timeout in opensnitch/daemon/ui/client.go is set to 120s
         // Subtract a few seconds just to be nice.
-        if *connection_disposition_timeout_in > 115 {
+        let connection_disposition_timeout_in = options.dispo_seconds;
+        if connection_disposition_timeout_in > 115 {
             return Err(format!(
                 "Connection disposition timeout {connection_disposition_timeout_in} cannot be over 115"
             ));
         }
         let connection_disposition_timeout =
-            std::time::Duration::from_secs(*connection_disposition_timeout_in);
+            std::time::Duration::from_secs(connection_disposition_timeout_in);
 
-        let events_handler = EventHandler::new();
-        let server = OpenSnitchUIServer::default();
+        if options.stale_seconds == 0 {
+            return Err(String::from(
+                "Staleness threshold must be at least 1 second",
+            ));
+        }
+        let stale_threshold = std::time::Duration::from_secs(options.stale_seconds);
 
-        // Hold a dummy sender channel until a client actually connects to server and swaps in a usable
-        // sender handle.
-        let (dummy_notification_sender, _) = mpsc::channel(1);
-        let (dummy_rule_sender, _) = mpsc::channel(1);
+        let events_handler = EventHandler::new();
+        let (store, history, current_alerts) = match &options.store {
+            StoreLocation::File(path) => open_store(path, &events_handler)?,
+            StoreLocation::Off => (None, EventHistory::default(), VecDeque::new()),
+            // Not worth refusing to start over, but nothing is kept once we exit.
+            StoreLocation::NoDataDir => {
+                let alert = app_alert(
+                    alert::Type::Warning,
+                    String::from(
+                        "No data directory for the store, nothing will be kept: pass --data-dir, or --no-store",
+                    ),
+                );
+                (None, EventHistory::default(), VecDeque::from([alert]))
+            }
+        };
+        let server = OpenSnitchUIServer::new(bind_address, tls_config);
 
         Ok(Self {
             running: true,
-            rx_pings: 0,
-            peer: None,
+            failure: None,
             events: events_handler,
             server,
-            current_stats: None,
-            current_alerts: VecDeque::new(),
+            server_state: ServerState::Stopped,
+            nodes: BTreeMap::new(),
+            selected_node: None,
+            current_alerts,
             alert_list_render_offset: 0,
-            notification_sender: Arc::new(Mutex::new(dummy_notification_sender)),
-            current_connection: None,
-            rule_sender: dummy_rule_sender,
-            bind_address: maybe_bind_addr.unwrap(),
+            view: View::Main,
+            notifications: NotificationTracker::new(),
+            notification_log_offset: 0,
+            history,
+            store,
+            history_view: HistoryView::default(),
+            names: Names::load(),
+            stats_view: StatsView::default(),
+            rules_view: RulesView::default(),
+            firewall_view: FirewallView::default(),
+            sockets_view: SocketsView::default(),
+            pid_monitor: None,
+            admin_menu: None,
+            confirmation: None,
+            audit: AuditLog::default(),
+            settings: None,
+            notification_senders: Arc::new(Mutex::new(HashMap::new())),
+            pending_rules: PendingRules::default(),
             default_action: maybe_default_action.unwrap(),
+            exit_action: exit_action.as_action(),
             temp_rule_lifetime: maybe_temp_rule_lifetime.unwrap(),
             connection_disposition_timeout,
+            stale_threshold,
         })
     }
 
     /// Run the application's main loop.
     /// # Errors
-    /// Doesn't nominally return any errors at runtime.
+    /// Returns an error if some task panicked.
     /// # Panics
     /// Largely upon runtime invariant violation, could be fixed in future versions.
     pub async fn run(mut self, mut terminal: DefaultTerminal) -> color_eyre::Result<()> {
-        // Rule receiver gets borrowed by the server
-        let (rule_sender, rule_receiver) = mpsc::channel(1);
-        self.rule_sender = rule_sender;
-        self.server.spawn_and_run(
-            self.bind_address,
-            self.events.sender.clone(),
-            &self.notification_sender,
-            rule_receiver,
-            self.default_action,
-            self.connection_disposition_timeout,
-        );
+        self.start(true);
         // Only need a draw if:
         // * This is the first cycle (see default value below)
         // * Tick resulted in a meaningful state update
@@ -150,34 +369,151 @@ impl App {
         // * We received an event from the gRPC server
         let mut draw_needed = true;
         while self.running {
-            match self.events.next().await? {
-                Event::Tick => draw_needed |= self.tick(), /* Doing an OR here lets first tick through */
-                Event::Crossterm(event) => match event {
-                    crossterm::event::Event::Key(key_event)
-                        if key_event.kind == crossterm::event::KeyEventKind::Press =>
-                    {
-                        draw_needed = true;
-                        self.handle_key_events(key_event)?;
-                    }
-                    _ => {}
-                },
-                Event::App(app_event) => {
-                    draw_needed = true;
-                    match *app_event {
-                        AppEvent::Update(stats) => self.update_stats(stats),
-                        AppEvent::Alert(alert) => self.current_alerts.push_back(alert.clone()),
-                        AppEvent::AskRule(evt) => self.update_connection(evt),
-                        AppEvent::TestNotify => self.test_notify().await,
-                        AppEvent::Quit => self.quit(),
+            let event = self.events.next().await?;
+            draw_needed |= self.handle_event(event)?; /* Doing an OR here lets first tick through */
+            // The terminal may already be restored if we're stopping due to a panic.
+            if draw_needed && self.running {
+                terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
+                draw_needed = false;
+            }
+        }
+        self.finish().await
+    }
+
+    /// Start serving and generating events.
+    /// * `terminal_input`: Whether to read key presses from the terminal.
+    pub fn start(&mut self, terminal_input: bool) {
+        self.forward_panics();
+        self.events.start(terminal_input);
+        self.server.spawn_and_run(
+            self.events.sender.clone(),
+            &self.notification_senders,
+            &self.pending_rules,
+            self.default_action,
+            self.connection_disposition_timeout,
+        );
+    }
+
+    /// Shut down once the main loop is done.
+    /// # Errors
+    /// Returns an error if the app stopped because some task panicked.
+    pub async fn finish(mut self) -> color_eyre::Result<()> {
+        self.shutdown().await;
+        match self.failure {
+            Some(msg) => Err(color_eyre::eyre::eyre!(msg)),
+            None => Ok(()),
+        }
+    }
+
+    /// Update state from one event.
+    /// Returns whether meaningful change occured, which should trigger a re-render of terminal.
+    /// # Errors
+    /// Not really...
+    pub fn handle_event(&mut self, event: Event) -> color_eyre::Result<bool> {
+        match event {
+            Event::Tick => return Ok(self.tick()),
+            Event::Crossterm(event) => match event {
+                crossterm::event::Event::Key(key_event)
+                    if key_event.kind == crossterm::event::KeyEventKind::Press =>
+                {
+                    self.handle_key_events(key_event)?;
+                }
+                _ => return Ok(false),
+            },
+            Event::App(app_event) => self.handle_app_event(*app_event),
+        }
+        Ok(true)
+    }
+
+    /// Update state from an event raised by the server or the app itself.
+    fn handle_app_event(&mut self, app_event: AppEvent) {
+        match app_event {
+            AppEvent::Update(stats) => self.update_stats(stats),
+            AppEvent::Alert(alert) => self.push_alert(alert),
+            AppEvent::AskRule(evt) => self.update_connection(evt),
+            AppEvent::AskRuleClosed { node, id } => {
+                if let Some(node) = self.nodes.get_mut(&node) {
+                    node.remove_connection(id);
+                }
+            }
+            AppEvent::Subscribe(evt) => self.update_subscription(*evt),
+            AppEvent::NotificationStream { node, state } => {
+                self.update_stream_state(node, state);
+            }
+            AppEvent::NotificationReply { node, reply } => {
+                self.handle_notification_reply(&node, &reply);
+            }
+            AppEvent::ServerState(state) => self.update_server_state(state),
+            AppEvent::Quit => self.quit(),
+            AppEvent::Panicked(msg) => {
+                self.failure = Some(msg);
+                self.quit();
+            }
+        }
+    }
+
+    /// Stop the app if any task panics, rather than carry on drawing to a terminal
+    /// the panic hook has already restored.
+    fn forward_panics(&self) {
+        let sender = self.events.sender.clone();
+        let prev_hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            prev_hook(info);
+            let _ = sender.send(Event::App(Box::new(AppEvent::Panicked(info.to_string()))));
+        }));
+    }
+
+    /// Wind down before exiting: answer daemons still waiting on a prompt with the exit
+    /// action, end their notification streams and stop the server.
+    /// Calls the app hasn't seen yet are dropped, so those daemons fall back to their
+    /// own default action right away instead of waiting out the timeout.
+    /// # Panics
+    /// If the pending rules lock is poisoned.
+    pub async fn shutdown(&mut self) {
+        let exit_action = self.exit_action;
+        self.answer_pending_prompts(|_| (exit_action, constants::Duration::Once));
+        self.pending_rules.lock().unwrap().clear();
+        // Dropping the senders ends each daemon's notifications stream cleanly.
+        self.notification_senders.lock().await.clear();
+        self.server.shutdown().await;
+    }
+
+    /// Answer every queued prompt, on every node, with a rule for just that connection.
+    /// * `decide`: Picks the action and duration for each connection.
+    ///
+    /// Returns the prompts that were answered, and how. Prompts whose call has since
+    /// ended are dropped without an answer.
+    /// # Panics
+    /// If the pending rules lock is poisoned.
+    pub fn answer_pending_prompts(
+        &mut self,
+        mut decide: impl FnMut(&ConnectionEvent) -> (constants::Action, constants::Duration),
+    ) -> Vec<(ConnectionEvent, pb::Rule)> {
+        let mut answered = Vec::new();
+        for node in self.nodes.values_mut() {
+            let mut kept = Vec::new();
+            for conn in node.pending_connections.drain(..) {
+                let (action, duration) = decide(&conn);
+                let rule = rule_for(&conn.connection, action, duration);
+                if let Some(rule_tx) = self.pending_rules.lock().unwrap().remove(&conn.id)
+                    && rule_tx.send(rule.clone()).is_ok()
+                {
+                    if is_kept(&rule) {
+                        kept.push(rule.clone());
                     }
+                    answered.push((conn, rule));
                 }
             }
-            if draw_needed {
-                terminal.draw(|frame| frame.render_widget(&self, frame.area()))?;
-                draw_needed = false;
+            for rule in kept {
+                node.upsert_rule(rule);
             }
+            node.prompt_cursor = 0;
         }
-        Ok(())
+        let now = std::time::SystemTime::now();
+        for (conn, rule) in &answered {
+            self.persist(|store| store.save_decision(&conn.node, &conn.connection, rule, now));
+        }
+        answered
     }
 
     /// Handles the key events and updates the state of [`App`].
@@ -188,7 +524,49 @@ impl App {
             KeyCode::Char('c' | 'C') if key_event.modifiers == KeyModifiers::CONTROL => {
                 self.events.send(AppEvent::Quit);
             }
-            KeyCode::Char('t' | 'T') => self.events.send(AppEvent::TestNotify),
+            // Whatever's being confirmed gets the next key, and nothing else does.
+            _ if self.confirmation.is_some() => self.handle_confirmation_keys(key_event),
+            _ if self.rules_view.editor.is_some() => self.handle_rule_editor_keys(key_event),
+            _ if self.firewall_view.editor.is_some() => self.handle_fw_rule_editor_keys(key_event),
+            _ if self.firewall_view.export.is_some() => self.handle_fw_export_keys(key_event),
+            _ if self.admin_menu.is_some() => self.handle_admin_menu_keys(key_event),
+            KeyCode::Tab => self.set_view(self.view.cycle(true)),
+            KeyCode::BackTab => self.set_view(self.view.cycle(false)),
+            KeyCode::Char('n') => self.select_next_node(true),
+            KeyCode::Char('N') => self.select_next_node(false),
+            _ => match self.view {
+                View::Main => self.handle_main_keys(key_event),
+                View::History => self.handle_history_keys(key_event),
+                View::Stats => self.handle_stats_keys(key_event),
+                View::Rules => self.handle_rules_keys(key_event),
+                View::Firewall => self.handle_firewall_keys(key_event),
+                View::Sockets => self.handle_sockets_keys(key_event),
+                View::Notifications => self.handle_notification_log_keys(key_event),
+                View::Settings => self.handle_settings_keys(key_event),
+            },
+        }
+        Ok(())
+    }
+
+    /// Switch views. The sockets monitor only runs while the sockets view is shown.
+    pub fn set_view(&mut self, view: View) {
+        if view == self.view {
+            return;
+        }
+        if self.view == View::Sockets {
+            self.stop_sockets_monitor();
+        }
+        self.view = view;
+        if view == View::Sockets {
+            self.start_sockets_monitor();
+        }
+    }
+
+    /// Keys for the main view: connection prompts and alerts.
+    fn handle_main_keys(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char(']') => self.move_prompt_cursor(true),
+            KeyCode::Char('[') => self.move_prompt_cursor(false),
             KeyCode::Char('a' | 'A') => {
                 self.make_and_send_rule(constants::Action::Allow, self.temp_rule_lifetime);
             }
@@ -201,265 +579,1782 @@ impl App {
             KeyCode::Char('l' | 'L') => {
                 self.make_and_send_rule(constants::Action::Deny, constants::Duration::Always);
             }
+            KeyCode::Char('m') => {
+                if let Some(pid) = self
+                    .current_connection()
+                    .map(|evt| evt.connection.process_id)
+                {
+                    self.start_pid_monitor(pid);
+                }
+            }
+            KeyCode::Char('M') => self.stop_pid_monitor(),
             KeyCode::Up => {
                 self.alert_list_render_offset = self.alert_list_render_offset.saturating_sub(1);
             }
             KeyCode::Down => {
-                if !self.current_alerts.is_empty() {
+                let visible_alerts = self.visible_alerts().count();
+                if visible_alerts > 0 {
                     self.alert_list_render_offset = std::cmp::min(
                         self.alert_list_render_offset.saturating_add(1),
-                        self.current_alerts.len() - 1,
+                        visible_alerts - 1,
                     );
                 }
             }
             _ => {}
         }
-        Ok(())
     }
 
-    /// Handles the tick event of the terminal.
-    /// Returns whether meaningful change occured, which should trigger a re-render of terminal.
-    pub fn tick(&mut self) -> bool {
-        let mut did_work = false;
-        let now = std::time::SystemTime::now();
-        if let Some(conn) = &self.current_connection
-            && now >= conn.expiry_ts
+    /// Keys for the history view.
+    fn handle_history_keys(&mut self, key_event: KeyEvent) {
+        let events = self
+            .selected_node
+            .as_ref()
+            .map_or(0, |node| self.history.for_node(node).count());
+        if self.history_view.handle_key(key_event, events) {
+            return;
+        }
+        // Watch the process in the connections view, where its panel is.
+        if key_event.code == KeyCode::Char('m')
+            && let Some(pid) = self
+                .selected_event()
+                .and_then(|event| event.connection.as_ref())
+                .map(|connection| connection.process_id)
         {
-            // The daemon's gRPC call should time out and take some default action
-            // in the absence of a Rule created by us.
-            self.clear_connection();
-            did_work = true;
+            self.start_pid_monitor(pid);
+            self.set_view(View::Main);
         }
+    }
 
-        // Routinely expire alerts.
-        match self.current_alerts.front() {
-            None => {}
-            Some(alert) => {
-                if let Ok(age) = now.duration_since(alert.timestamp) {
-                    // Max alert duration is 60s, could be adjustable if needed
-                    if age.as_secs() >= 60 {
-                        // Pop this off but also correct the render offset in case it's set to back of list.
-                        if self.alert_list_render_offset == (self.current_alerts.len() - 1) {
-                            self.alert_list_render_offset =
-                                self.alert_list_render_offset.saturating_sub(1);
-                        }
-                        self.current_alerts.pop_front();
-                        did_work = true;
-                    }
-                }
-            }
-        }
+    /// The selected event in the history table.
+    #[must_use]
+    pub fn selected_event(&self) -> Option<&pb::Event> {
+        let node = self.selected_node.as_ref()?;
+        let events = self.history.for_node(node).count();
+        let idx = std::cmp::min(self.history_view.cursor, events.checked_sub(1)?);
+        self.history.for_node(node).nth(idx)
+    }
 
-        did_work
+    /// Keys for the stats view.
+    fn handle_stats_keys(&mut self, key_event: KeyEvent) {
+        let entries = self.breakdown().entries.len();
+        self.stats_view.handle_key(key_event, entries);
     }
 
-    /// Set running to false to quit the application.
-    pub fn quit(&mut self) {
-        self.running = false;
+    /// The selected node's hits, broken down by the stats view's dimension. Uids and
+    /// ports are only named if the node runs on this host, where the names come from.
+    #[must_use]
+    pub fn breakdown(&self) -> Breakdown {
+        let Some(node) = self.selected() else {
+            return Breakdown::default();
+        };
+        let names = node
+            .peer
+            .as_ref()
+            .is_some_and(Peer::is_local)
+            .then_some(&self.names);
+        node.stats
+            .as_ref()
+            .map(|stats| Breakdown::new(stats, self.stats_view.dimension, names))
+            .unwrap_or_default()
     }
 
-    /// Update peer stats from incoming Ping payload.
-    /// TODO: Set a staleness threshold so we can clearly flag a disconnected peer.
-    /// For now, this can be simulated locally via:
-    /// iptables -A INPUT -p tcp --dport 50051 -j DROP
-    /// iptables -D INPUT -p tcp --dport 50051 -j DROP
-    pub fn update_stats(&mut self, ping_event: PingEvent) {
-        self.rx_pings = self.rx_pings.saturating_add(1);
-        self.peer = ping_event.peer;
-        self.current_stats = Some(ping_event.stats);
-    }
-
-    /// Server to daemon notifications under development.
-    pub async fn test_notify(&mut self) {
-        let sender = self.notification_sender.lock().await;
-        let _ = sender
-            .send(Ok(pb::Notification {
-                id: 123,
-                client_name: String::default(),
-                server_name: String::default(),
-                r#type: 14, // This is a task stop notification.
-                data: String::from("Test notification triggers an error"),
-                rules: Vec::default(),
-                sys_firewall: None,
-            }))
-            .await;
-    }
-
-    /// Update connection holder with latest inbound event.
-    pub fn update_connection(&mut self, evt: ConnectionEvent) {
-        self.current_connection = Some(evt);
+    /// Keys for the sockets view: `r` restarts the monitor, the view takes the rest.
+    fn handle_sockets_keys(&mut self, key_event: KeyEvent) {
+        let sockets = self.visible_sockets().len();
+        if !self.sockets_view.handle_key(key_event, sockets) && key_event.code == KeyCode::Char('r')
+        {
+            self.stop_sockets_monitor();
+            self.start_sockets_monitor();
+        }
     }
 
-    /// Clear connection holder.
-    pub fn clear_connection(&mut self) {
-        self.current_connection = None;
+    /// Start the selected node's sockets monitor task.
+    pub fn start_sockets_monitor(&mut self) {
+        let Some(node) = self.selected_node.clone() else {
+            return;
+        };
+        let data = SocketsMonitor::task_data();
+        let id = self.send_notification_to(&node, pb::Action::TaskStart, data, Vec::new(), None);
+        self.sockets_view.monitor = Some(SocketsMonitor::new(&node, id));
+        self.sockets_view.cursor = 0;
     }
 
-    /// Generate a rule for the current connection being handled by this server.
-    /// Matches on user ID && process path && IP dst && l4 port && l4 protocol.
-    /// TODO: Consider including process hash for extra strictness.
-    /// Returns `none` if there is no current connection.
-    /// * `is_allow`: Whether the rule for this connection should allow or deny the flow.
-    fn make_rule(
-        &self,
-        action: constants::Action,
-        duration: constants::Duration,
-    ) -> Option<pb::Rule> {
-        // Noop if there's no connection trapped.
-        self.current_connection.as_ref()?;
-
-        let conn = &self.current_connection.as_ref().unwrap().connection;
-
-        // Build up an array of "safe"ish default operators to match this process's
-        // specific connection, though this can obviously be better validated/configured
-        // in the future.
-        // This could have also been implemented with enum+trait magic, but using a simple
-        // Operator factory lets us pass this vector into the larger Rule we are creating.
-        let operators = vec![
-            operator_util::match_user_id(conn.user_id),
-            operator_util::match_proc_path(&conn.process_path),
-            operator_util::match_dst_ip(&conn.dst_ip),
-            operator_util::match_dst_port(conn.dst_port),
-            operator_util::match_protocol(&conn.protocol),
-        ];
-
-        let action_str = action.get_str();
-        let duration = String::from(duration.get_str());
-        let pretty_proc_path = conn.process_path.clone().replace('/', "-");
-        let maybe_operator_json = serde_json::to_string(&operators);
-        // Shouldn't really happen due to serde_impl.rs, ideally something caught at build time.
-        assert!(
-            maybe_operator_json.is_ok(),
-            "Operator list JSON serialization failed: {}",
-            maybe_operator_json.unwrap_err()
-        );
+    /// Stop the sockets monitor task, if there's one. Any reports still on their way
+    /// are dropped.
+    pub fn stop_sockets_monitor(&mut self) {
+        if let Some(monitor) = self.sockets_view.monitor.take() {
+            self.send_notification_to(
+                &monitor.node,
+                pb::Action::TaskStop,
+                SocketsMonitor::task_data(),
+                Vec::new(),
+                None,
+            );
+        }
+    }
 
-        Some(pb::Rule {
-            created: 0,
-            // TODO: Leading slash gets turned into double-dash, may be annoying
-            name: format!("{action_str}-{duration}-simple-via-tui-{pretty_proc_path}"),
-            description: String::default(),
-            enabled: true,
-            precedence: false,
-            nolog: false,
-            action: String::from(action_str),
-            duration,
-            operator: Some(pb::Operator {
-                r#type: String::from(constants::RuleType::List.get_str()),
-                operand: String::from(constants::Operand::List.get_str()),
-                data: maybe_operator_json.unwrap(),
-                sensitive: false,
-                list: operators,
-            }),
-        })
+    /// Sockets of the selected node in table order, limited to the picked state.
+    #[must_use]
+    pub fn visible_sockets(&self) -> Vec<&sockets::Socket> {
+        self.sockets_view.visible(self.selected_node.as_ref())
     }
 
-    fn send_rule(&self, rule: pb::Rule) {
-        let send_res = self.rule_sender.try_send(rule);
-        if let Err(err) = send_res {
-            // Shouldn't really happen so bail here.
-            panic!("Unable to send rule: {err}");
+    /// Keys for the rules view: changes to rules are made here, the view takes the rest.
+    fn handle_rules_keys(&mut self, key_event: KeyEvent) {
+        let rules = self.selected().map_or(0, |node| node.rules.len());
+        if self.rules_view.handle_key(key_event, rules) {
+            return;
+        }
+        match key_event.code {
+            KeyCode::Char('e') => self.toggle_selected_rule(),
+            KeyCode::Char('c') => self.rules_view.editor = Some(RuleEditor::new_rule()),
+            KeyCode::Enter => self.rules_view.editor = self.selected_rule().map(RuleEditor::edit),
+            KeyCode::Char('x') | KeyCode::Delete => {
+                if let Some(rule) = self.selected_rule()
+                    && let Some(node) = &self.selected_node
+                {
+                    self.confirmation = Some(Confirmation {
+                        prompt: format!("Delete rule {}?", rule.name),
+                        node: node.clone(),
+                        action: pb::Action::DeleteRule,
+                        data: String::new(),
+                        rules: vec![rule.clone()],
+                        sys_firewall: None,
+                        challenge: None,
+                        typed: String::new(),
+                    });
+                }
+            }
+            _ => {}
         }
     }
 
-    fn make_and_send_rule(&mut self, action: constants::Action, duration: constants::Duration) {
-        if let Some(rule) = self.make_rule(action, duration) {
-            self.send_rule(rule);
-            self.clear_connection();
+    /// Keys for the firewall view: changes are made here, and sent as the daemon's whole
+    /// firewall, with problems left in the view's status. The view takes the rest.
+    fn handle_firewall_keys(&mut self, key_event: KeyEvent) {
+        self.firewall_view.status = None;
+        let firewall = self
+            .selected_node
+            .as_ref()
+            .and_then(|node| self.nodes.get(node))
+            .and_then(|node| node.system_firewall.as_ref());
+        if self.firewall_view.handle_key(key_event, firewall) {
+            return;
+        }
+        let result = match key_event.code {
+            KeyCode::Char('w') => self.open_fw_export(),
+            // The ruleset is read only.
+            _ if self.firewall_view.nft => Ok(()),
+            KeyCode::Enter => self.open_fw_rule_editor(false),
+            KeyCode::Char('c') => self.open_fw_rule_editor(true),
+            KeyCode::Char('e') => self.toggle_fw_rule(),
+            KeyCode::Char('[') => self.move_fw_rule(false),
+            KeyCode::Char(']') => self.move_fw_rule(true),
+            KeyCode::Char('x') | KeyCode::Delete => self.confirm_delete_fw_rule(),
+            KeyCode::Char('p') => self.confirm_chain_policy(),
+            _ => Ok(()),
+        };
+        if let Err(err) = result {
+            self.firewall_view.status = Some(Err(err));
         }
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::opensnitch_proto::pb::{Connection, Rule};
-    use std::time::SystemTime;
+    /// Ask where to export the selected node's firewall.
+    fn open_fw_export(&mut self) -> Result<(), String> {
+        let (Some(node), Some(_)) = (self.selected(), self.selected_firewall()) else {
+            return Err(String::from(
+                "No system firewall received from this daemon yet",
+            ));
+        };
+        self.firewall_view.export = Some(firewall::Export::new(&node.id));
+        Ok(())
+    }
 
-    use super::*;
+    /// Keys for the firewall export prompt: `enter` writes the file, asking first if it
+    /// would replace one, `esc` closes the prompt, and anything else edits the path.
+    /// While asking about replacing a file, `y` does and anything else goes back.
+    fn handle_fw_export_keys(&mut self, key_event: KeyEvent) {
+        let Some(export) = &mut self.firewall_view.export else {
+            return;
+        };
+        if export.overwrite {
+            export.overwrite = false;
+            if matches!(key_event.code, KeyCode::Char('y' | 'Y')) {
+                self.write_fw_export(true);
+            }
+            return;
+        }
+        match key_event.code {
+            KeyCode::Esc => self.firewall_view.export = None,
+            KeyCode::Enter => self.write_fw_export(false),
+            _ => {
+                export.path.handle_key(key_event);
+            }
+        }
+    }
 
-    /// Convenience Alias for String-to-String Hashmap.
-    type S2SMap = std::collections::HashMap<String, String>;
+    /// Write the firewall being exported to the path typed in, reporting where it went.
+    /// A file already there is only replaced if `overwrite`, and otherwise asked about.
+    fn write_fw_export(&mut self, overwrite: bool) {
+        let Some(export) = &self.firewall_view.export else {
+            return;
+        };
+        let result = export.target().and_then(|path| {
+            self.export_firewall(&export.node, &path, overwrite)
+                .map(|written| written.then_some(path))
+        });
+        match result {
+            Ok(Some(path)) => {
+                self.firewall_view.export = None;
+                self.firewall_view.status = Some(Ok(format!("Wrote {}", path.display())));
+            }
+            Ok(None) => {
+                if let Some(export) = &mut self.firewall_view.export {
+                    export.overwrite = true;
+                }
+            }
+            Err(err) => self.firewall_view.status = Some(Err(err)),
+        }
+    }
 
-    /// Simple construction test.
-    #[tokio::test]
-    async fn test_new() {
-        let _ = App::new(
-            &"127.0.0.1:65534".to_string(),
-            &"deny".to_string(),
-            &"12h".to_string(),
-            &60,
-        )
-        .expect("new failed");
+    /// Write a node's firewall as an `nft` ruleset to `path`. Returns false, having written
+    /// nothing, if there's a file there already and not to `overwrite`.
+    fn export_firewall(&self, node: &NodeId, path: &Path, overwrite: bool) -> Result<bool, String> {
+        let firewall = self
+            .nodes
+            .get(node)
+            .and_then(|node| node.system_firewall.as_ref())
+            .ok_or_else(|| String::from("No system firewall received from this daemon yet"))?;
+        let mut options = std::fs::OpenOptions::new();
+        if overwrite {
+            options.write(true).create(true).truncate(true);
+        } else {
+            options.write(true).create_new(true);
+        }
+        let write = options
+            .open(path)
+            .and_then(|mut file| file.write_all(nft::render(firewall).as_bytes()));
+        match write {
+            Ok(()) => Ok(true),
+            Err(err) if err.kind() == std::io::ErrorKind::AlreadyExists => Ok(false),
+            Err(err) => Err(format!("Unable to write '{}': {err}", path.display())),
+        }
     }
 
-    /// Test that making a rule with no "current connection" generates a noop.
-    #[tokio::test]
-    async fn test_make_rule_no_conn() {
-        let app = App::new(
-            &"127.0.0.1:65534".to_string(),
-            &"deny".to_string(),
-            &"12h".to_string(),
-            &60,
-        )
-        .expect("new failed");
+    /// The selected node's firewall, if it has sent one.
+    fn selected_firewall(&self) -> Option<&pb::SysFirewall> {
+        self.selected()
+            .and_then(|node| node.system_firewall.as_ref())
+    }
 
-        assert!(app.current_connection.is_none());
+    /// The selected firewall rule, with where it is.
+    #[must_use]
+    pub fn selected_fw_rule(&self) -> Option<(firewall::ChainIdx, usize, &pb::FwRule)> {
+        let firewall::Row::Rule(chain, idx) = self.selected_firewall_row()? else {
+            return None;
+        };
+        let rule = chain.get(self.selected_firewall()?)?.rules.get(idx)?;
+        Some((chain, idx, rule))
+    }
 
-        let maybe_rule = app.make_rule(constants::Action::Allow, constants::Duration::Once);
-        assert!(maybe_rule.is_none());
+    /// Open the firewall rule editor on the selected rule, or on a new rule for the
+    /// selected chain.
+    fn open_fw_rule_editor(&mut self, new: bool) -> Result<(), String> {
+        let chain = self
+            .selected_firewall_row()
+            .map(firewall::Row::chain)
+            .and_then(|idx| idx.get(self.selected_firewall()?))
+            .ok_or_else(|| String::from("Select a chain first"))?;
+        self.firewall_view.editor = if new {
+            Some(FwRuleEditor::new_rule(chain))
+        } else {
+            self.selected_fw_rule()
+                .map(|(_, _, rule)| FwRuleEditor::edit(chain, rule))
+        };
+        Ok(())
     }
 
-    /// Helper to make a fake connection object.
-    fn make_fake_connection() -> Connection {
-        Connection {
-            protocol: String::from("tcp"),
-            src_ip: String::from("192.168.0.3"),
-            src_port: 1337,
-            dst_ip: String::from("192.128.0.4"),
-            dst_host: String::from("suspicious.local"),
-            dst_port: 1338,
-            user_id: 1000,
-            process_id: 1339,
-            process_path: String::from("/usr/bin/hello"),
-            process_cwd: String::from("/home/spongebob"),
-            process_args: vec![],
-            process_env: S2SMap::default(),
-            process_checksums: S2SMap::default(),
-            process_tree: vec![],
+    /// The selected node's firewall with `change` made to it, checked and ready to send.
+    /// Changes wait on the daemon loading the last one sent, since they're made to the
+    /// firewall it last confirmed.
+    fn changed_firewall(
+        &self,
+        change: impl FnOnce(&mut pb::SysFirewall) -> Result<(), String>,
+    ) -> Result<pb::SysFirewall, String> {
+        self.check_no_firewall_in_flight()?;
+        let mut firewall = self
+            .selected_firewall()
+            .cloned()
+            .ok_or_else(|| String::from("No system firewall received from this daemon yet"))?;
+        change(&mut firewall)?;
+        firewall::validate(&firewall)?;
+        Ok(firewall)
+    }
+
+    /// Fails if the selected node has yet to answer a firewall sent to it.
+    fn check_no_firewall_in_flight(&self) -> Result<(), String> {
+        match &self.selected_node {
+            Some(node) if self.notifications.pending(node, pb::Action::ReloadFwRules) => Err(
+                String::from("Waiting on the daemon to load the last firewall sent to it"),
+            ),
+            _ => Ok(()),
         }
     }
 
-    /// Test that making a rule with a valid "current connection" generates something meaningful.
-    #[tokio::test]
-    async fn test_make_rule_has_conn() {
-        let mut app = App::new(
-            &"127.0.0.1:65534".to_string(),
-            &"deny".to_string(),
-            &"12h".to_string(),
-            &60,
+    /// Send the selected node a whole new firewall, which it saves and loads.
+    /// The firewall view only changes once the daemon confirms.
+    fn send_firewall(&mut self, firewall: pb::SysFirewall) -> Option<u64> {
+        self.send_notification(
+            pb::Action::ReloadFwRules,
+            String::new(),
+            Vec::new(),
+            Some(firewall),
         )
-        .expect("new failed");
-
-        let fake_conn = make_fake_connection();
-        app.current_connection = Some(ConnectionEvent {
-            connection: fake_conn.clone(),
-            expiry_ts: SystemTime::now() + app.connection_disposition_timeout,
-        });
+    }
 
-        let maybe_rule = app
-            .make_rule(constants::Action::Allow, constants::Duration::Once)
-            .expect("missing rule");
+    /// Ask before enabling or disabling the selected firewall rule.
+    fn toggle_fw_rule(&mut self) -> Result<(), String> {
+        let Some((chain, idx, rule)) = self.selected_fw_rule() else {
+            return Ok(());
+        };
+        let prompt = format!(
+            "{} firewall rule {}?",
+            if rule.enabled { "Disable" } else { "Enable" },
+            firewall::rule_summary(rule)
+        );
+        let firewall = self.changed_firewall(|firewall| {
+            let rule = chain
+                .get_mut(firewall)
+                .and_then(|chain| chain.rules.get_mut(idx))
+                .ok_or_else(|| String::from("The rule is gone"))?;
+            rule.enabled = !rule.enabled;
+            Ok(())
+        })?;
+        self.confirm_firewall(prompt, firewall);
+        Ok(())
+    }
 
-        // JSON blob representing the vector of operators we use for a rule.
-        // Checking this also acts as a high-level test for serde_json not producing
-        // unexpected output in the future.
-        let expected_str = "[{\"type\":\"simple\",\"operand\":\"user.id\",\"data\":\"1000\",\
-        \"sensitive\":false,\"list\":[]},{\"type\":\"simple\",\"operand\":\"process.path\",\
-        \"data\":\"/usr/bin/hello\",\"sensitive\":false,\"list\":[]},{\"type\":\"simple\",\
-        \"operand\":\"dest.ip\",\"data\":\"192.128.0.4\",\"sensitive\":false,\"list\":[]},\
-        {\"type\":\"simple\",\"operand\":\"dest.port\",\"data\":\"1338\",\"sensitive\":false,\
-        \"list\":[]},{\"type\":\"simple\",\"operand\":\"protocol\",\"data\":\"tcp\",\"sensitive\"\
-        :false,\"list\":[]}]";
+    /// Ask before moving the selected firewall rule one place down its chain, or up.
+    /// The rules swap positions too.
+    fn move_fw_rule(&mut self, down: bool) -> Result<(), String> {
+        let Some((chain, idx, rule)) = self.selected_fw_rule() else {
+            return Ok(());
+        };
+        let prompt = format!(
+            "Move firewall rule {} {}?",
+            firewall::rule_summary(rule),
+            if down { "down" } else { "up" }
+        );
+        let rules = self
+            .selected_firewall()
+            .and_then(|firewall| chain.get(firewall))
+            .map_or(0, |chain| chain.rules.len());
+        let other = if down { idx + 1 } else { idx.wrapping_sub(1) };
+        // Already at that end of the chain.
+        if other >= rules {
+            return Ok(());
+        }
+        let firewall = self.changed_firewall(|firewall| {
+            let rules = &mut chain
+                .get_mut(firewall)
+                .ok_or_else(|| String::from("The chain is gone"))?
+                .rules;
+            let (a, b) = (rules[idx].position, rules[other].position);
+            rules.swap(idx, other);
+            rules[idx].position = a;
+            rules[other].position = b;
+            Ok(())
+        })?;
+        self.confirm_firewall(prompt, firewall);
+        Ok(())
+    }
+
+    /// Ask before deleting the selected firewall rule.
+    fn confirm_delete_fw_rule(&mut self) -> Result<(), String> {
+        let Some((chain, idx, rule)) = self.selected_fw_rule() else {
+            return Ok(());
+        };
+        let prompt = format!("Delete firewall rule {}?", firewall::rule_summary(rule));
+        let firewall = self.changed_firewall(|firewall| {
+            if let Some(chain) = chain.get_mut(firewall) {
+                chain.rules.remove(idx);
+            }
+            Ok(())
+        })?;
+        self.confirm_firewall(prompt, firewall);
+        Ok(())
+    }
+
+    /// Ask before switching the selected chain to the next policy.
+    fn confirm_chain_policy(&mut self) -> Result<(), String> {
+        let Some(idx) = self.selected_firewall_row().map(firewall::Row::chain) else {
+            return Ok(());
+        };
+        let mut prompt = String::new();
+        let firewall = self.changed_firewall(|firewall| {
+            let chain = idx
+                .get_mut(firewall)
+                .ok_or_else(|| String::from("The chain is gone"))?;
+            if chain.hook.is_empty() {
+                return Err(String::from("Only base chains have a policy"));
+            }
+            let next = firewall::POLICIES
+                .iter()
+                .position(|policy| *policy == chain.policy)
+                .map_or(0, |idx| (idx + 1) % firewall::POLICIES.len());
+            chain.policy = String::from(firewall::POLICIES[next]);
+            prompt = format!(
+                "Set the policy of {} to {}?",
+                firewall::chain_key(chain),
+                chain.policy
+            );
+            Ok(())
+        })?;
+        self.confirm_firewall(prompt, firewall);
+        Ok(())
+    }
+
+    /// Hold a firewall for the selected node until the user confirms it.
+    fn confirm_firewall(&mut self, prompt: String, firewall: pb::SysFirewall) {
+        let Some(node) = self.selected_node.clone() else {
+            return;
+        };
+        self.confirmation = Some(Confirmation {
+            prompt,
+            node,
+            action: pb::Action::ReloadFwRules,
+            data: String::new(),
+            rules: Vec::new(),
+            sys_firewall: Some(firewall),
+            challenge: None,
+            typed: String::new(),
+        });
+    }
+
+    /// Keys for the firewall rule editor: `enter` sends the rule, `esc` closes the
+    /// editor, and anything else edits it.
+    fn handle_fw_rule_editor_keys(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Enter => self.submit_fw_rule_editor(),
+            KeyCode::Esc => self.firewall_view.editor = None,
+            _ => {
+                // Editing moves on from whatever was last sent.
+                if let Some(editor) = &mut self.firewall_view.editor
+                    && editor.handle_key(key_event)
+                {
+                    editor.sent = None;
+                }
+            }
+        }
+    }
+
+    /// Send the selected node its firewall with the edited rule in place, or added to
+    /// the end of its chain. The editor stays open until the daemon confirms.
+    pub fn submit_fw_rule_editor(&mut self) {
+        let Some(editor) = &self.firewall_view.editor else {
+            return;
+        };
+        let result = editor.build().and_then(|rule| {
+            self.changed_firewall(|firewall| {
+                let chain = firewall::chains(firewall)
+                    .into_iter()
+                    .find(|(_, chain)| firewall::chain_key(chain) == editor.chain)
+                    .map(|(idx, _)| idx)
+                    .and_then(|idx| idx.get_mut(firewall))
+                    .ok_or_else(|| format!("Chain {} is gone", editor.chain))?;
+                match chain.rules.iter_mut().find(|known| known.uuid == rule.uuid) {
+                    Some(known) => *known = rule,
+                    None if editor.editing => {
+                        return Err(String::from("The rule is gone from its chain"));
+                    }
+                    None => chain.rules.push(rule),
+                }
+                Ok(())
+            })
+        });
+        let result = result.map(|firewall| self.send_firewall(firewall));
+        if let Some(editor) = &mut self.firewall_view.editor {
+            match result {
+                Ok(id) => {
+                    editor.sent = id;
+                    editor.error = None;
+                }
+                Err(err) => editor.error = Some(err),
+            }
+        }
+    }
+
+    /// Keys for the rule editor: `enter` sends the rule, `esc` closes the editor, and
+    /// anything else edits it.
+    fn handle_rule_editor_keys(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Enter => self.submit_rule_editor(),
+            KeyCode::Esc => self.rules_view.editor = None,
+            _ => {
+                // Editing moves on from whatever was last sent.
+                if let Some(editor) = &mut self.rules_view.editor
+                    && editor.handle_key(key_event)
+                {
+                    editor.sent = None;
+                }
+            }
+        }
+    }
+
+    /// Send the rule being edited to the selected node as a `CHANGE_RULE` notification.
+    /// The editor stays open until the daemon confirms the change.
+    pub fn submit_rule_editor(&mut self) {
+        let Some(editor) = &self.rules_view.editor else {
+            return;
+        };
+        let taken: Vec<&str> = self.selected().map_or_else(Vec::new, |node| {
+            node.rules.iter().map(|rule| rule.name.as_str()).collect()
+        });
+        match editor.build(&taken) {
+            Ok(rule) => {
+                let id =
+                    self.send_notification(pb::Action::ChangeRule, String::new(), vec![rule], None);
+                if let Some(editor) = &mut self.rules_view.editor {
+                    editor.sent = id;
+                    editor.error = None;
+                }
+            }
+            Err(err) => {
+                if let Some(editor) = &mut self.rules_view.editor {
+                    editor.error = Some(err);
+                }
+            }
+        }
+    }
+
+    /// Ask to turn connection interception on the selected node on or off. Daemons
+    /// don't report interception, so each way is asked for rather than toggled.
+    pub fn confirm_interception(&mut self, on: bool) {
+        let Some(node) = self.selected_node.clone() else {
+            return;
+        };
+        let (prompt, action) = if on {
+            (
+                "Enable connection interception?",
+                pb::Action::EnableInterception,
+            )
+        } else {
+            (
+                "Disable connection interception? Connections will go through without prompts or rules.",
+                pb::Action::DisableInterception,
+            )
+        };
+        self.confirm_daemon_command(&node, prompt, action);
+    }
+
+    /// Ask to stop the selected node's system firewall, or start it.
+    pub fn confirm_toggle_firewall(&mut self) {
+        let Some(node) = self.selected() else {
+            return;
+        };
+        let Some(running) = node.firewall_running else {
+            let alert = node_alert(
+                &node.id,
+                alert::Type::Warning,
+                String::from("Can't toggle the system firewall before the daemon has reported it"),
+            );
+            self.push_alert(alert);
+            return;
+        };
+        let (prompt, action) = if running {
+            (
+                "Disable the system firewall? Its rules will be removed until it's enabled again.",
+                pb::Action::DisableFirewall,
+            )
+        } else {
+            ("Enable the system firewall?", pb::Action::EnableFirewall)
+        };
+        let node = node.id.clone();
+        self.confirm_daemon_command(&node, prompt, action);
+    }
+
+    /// Ask to have the selected node reload its system firewall.
+    /// The daemon saves the firewall it's sent before loading it, so it's sent the one
+    /// it last reported, and can't be asked until it's reported one.
+    pub fn confirm_reload_firewall(&mut self) {
+        let Some(node) = self.selected() else {
+            return;
+        };
+        let firewall = node
+            .system_firewall
+            .clone()
+            .ok_or_else(|| {
+                String::from("Can't reload the system firewall before the daemon has sent it")
+            })
+            .and_then(|firewall| self.check_no_firewall_in_flight().map(|()| firewall));
+        let firewall = match firewall {
+            Ok(firewall) => firewall,
+            Err(err) => {
+                let alert = node_alert(&node.id, alert::Type::Error, err);
+                self.push_alert(alert);
+                return;
+            }
+        };
+        let prompt = format!("{}: Reload the system firewall rules?", node.label());
+        self.confirm_firewall(prompt, firewall);
+    }
+
+    /// Hold a payload-less command for a node until the user confirms it.
+    fn confirm_daemon_command(&mut self, node: &NodeId, prompt: &str, action: pb::Action) {
+        let Some(node) = self.nodes.get(node) else {
+            return;
+        };
+        self.confirmation = Some(Confirmation {
+            prompt: format!("{}: {prompt}", node.label()),
+            node: node.id.clone(),
+            action,
+            data: String::new(),
+            rules: Vec::new(),
+            sys_firewall: None,
+            challenge: None,
+            typed: String::new(),
+        });
+    }
+
+    /// `y` sends the notification being confirmed, anything else drops it.
+    /// A notification with a challenge is instead sent by typing the challenge and
+    /// pressing `enter`, and dropped with `esc`.
+    fn handle_confirmation_keys(&mut self, key_event: KeyEvent) {
+        let Some(confirmation) = &mut self.confirmation else {
+            return;
+        };
+        let confirmed = match (&confirmation.challenge, key_event.code) {
+            (None, code) => matches!(code, KeyCode::Char('y' | 'Y')),
+            (Some(_), KeyCode::Char(c)) => {
+                confirmation.typed.push(c);
+                return;
+            }
+            (Some(_), KeyCode::Backspace) => {
+                confirmation.typed.pop();
+                return;
+            }
+            (Some(challenge), KeyCode::Enter) if confirmation.typed != *challenge => return,
+            (Some(_), code) => code == KeyCode::Enter,
+        };
+        let Some(confirmation) = self.confirmation.take() else {
+            return;
+        };
+        if !confirmed {
+            return;
+        }
+        if ADMIN_ACTIONS.contains(&confirmation.action) {
+            self.send_admin_command(&confirmation.node, confirmation.action, confirmation.data);
+        } else {
+            self.send_notification_to(
+                &confirmation.node,
+                confirmation.action,
+                confirmation.data,
+                confirmation.rules,
+                confirmation.sys_firewall,
+            );
+        }
+    }
+
+    /// Keys for the admin menu: `enter` sends the picked log level, or asks to stop the
+    /// daemon, and `esc` closes the menu. Commands go to the node the menu was opened on.
+    fn handle_admin_menu_keys(&mut self, key_event: KeyEvent) {
+        let Some(menu) = &mut self.admin_menu else {
+            return;
+        };
+        match key_event.code {
+            KeyCode::Esc => self.admin_menu = None,
+            KeyCode::Enter => match menu.item {
+                admin::Item::LogLevel => {
+                    if let Some(level) = menu.level() {
+                        let node = menu.node.clone();
+                        self.admin_menu = None;
+                        self.send_admin_command(&node, pb::Action::LogLevel, level.to_string());
+                    }
+                }
+                admin::Item::Stop => {
+                    let node = menu.node.clone();
+                    self.admin_menu = None;
+                    self.confirm_stop_daemon(&node);
+                }
+            },
+            _ => {
+                menu.handle_key(key_event);
+            }
+        }
+    }
+
+    /// Ask to have a node's daemon stop, which takes typing [`admin::STOP_CHALLENGE`]
+    /// rather than a single key.
+    pub fn confirm_stop_daemon(&mut self, node: &NodeId) {
+        let Some(node) = self.nodes.get(node) else {
+            return;
+        };
+        self.confirmation = Some(Confirmation {
+            prompt: format!(
+                "{}: Stop the daemon? It can only be started again on its host.",
+                node.label()
+            ),
+            node: node.id.clone(),
+            action: pb::Action::Stop,
+            data: String::new(),
+            rules: Vec::new(),
+            sys_firewall: None,
+            challenge: Some(String::from(admin::STOP_CHALLENGE)),
+            typed: String::new(),
+        });
+    }
+
+    /// Have the selected node's daemon report on a process until told to stop.
+    /// Whatever process was being watched before stops being watched.
+    pub fn start_pid_monitor(&mut self, pid: u32) {
+        let Some(node) = self.selected_node.clone() else {
+            return;
+        };
+        if pid == 0 {
+            let alert = node_alert(
+                &node,
+                alert::Type::Warning,
+                String::from("The daemon didn't say which process this is"),
+            );
+            self.push_alert(alert);
+            return;
+        }
+        self.stop_pid_monitor();
+        let data = PidMonitor::task_data(pid);
+        if let Some(id) = self.send_notification(pb::Action::TaskStart, data, Vec::new(), None) {
+            self.pid_monitor = Some(PidMonitor::new(&node, pid, id));
+        }
+    }
+
+    /// Stop the pid monitor task, if there's one. The panel goes right away, and any
+    /// reports still on their way are dropped.
+    pub fn stop_pid_monitor(&mut self) {
+        let Some(monitor) = self.pid_monitor.take() else {
+            return;
+        };
+        // The task runs on its own node, whichever is selected now.
+        self.send_notification_to(
+            &monitor.node,
+            pb::Action::TaskStop,
+            PidMonitor::task_data(monitor.pid),
+            Vec::new(),
+            None,
+        );
+    }
+
+    /// Send an admin command to a node, noting it in the alerts as it goes out. The
+    /// daemon's reply is noted too, once it comes, and both go in the audit trail.
+    fn send_admin_command(&mut self, node: &NodeId, action: pb::Action, data: String) {
+        let msg = match action {
+            pb::Action::LogLevel => format!(
+                "Asked the daemon to set its log level to {}",
+                data.parse()
+                    .map_or_else(|_| data.clone(), settings::log_level_name)
+            ),
+            _ => format!("Sent {} to the daemon", action.as_str_name()),
+        };
+        let id = self.send_notification_to(node, action, data, Vec::new(), None);
+        let alert = node_alert(node, alert::Type::Info, format!("{msg} (#{id})"));
+        self.push_alert(alert);
+    }
+
+    /// Ask the selected node to enable or disable the selected rule.
+    /// The rules table only changes once the daemon confirms.
+    pub fn toggle_selected_rule(&mut self) {
+        let Some(rule) = self.selected_rule() else {
+            return;
+        };
+        let rule = pb::Rule {
+            enabled: !rule.enabled,
+            ..rule.clone()
+        };
+        let action = if rule.enabled {
+            pb::Action::EnableRule
+        } else {
+            pb::Action::DisableRule
+        };
+        self.send_notification(action, String::new(), vec![rule], None);
+    }
+
+    /// Keys for the notification log view.
+    fn handle_notification_log_keys(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Up => {
+                self.notification_log_offset = self.notification_log_offset.saturating_sub(1);
+            }
+            KeyCode::Down => {
+                let entries = self.notification_log().count();
+                self.notification_log_offset = std::cmp::min(
+                    self.notification_log_offset.saturating_add(1),
+                    entries.saturating_sub(1),
+                );
+            }
+            _ => {}
+        }
+    }
+
+    /// Keys for the settings view. Anything not for the view itself edits the form.
+    fn handle_settings_keys(&mut self, key_event: KeyEvent) {
+        match key_event.code {
+            KeyCode::Char('i') => self.confirm_interception(true),
+            KeyCode::Char('I') => self.confirm_interception(false),
+            KeyCode::Char('f') => self.confirm_toggle_firewall(),
+            KeyCode::Char('r') => self.confirm_reload_firewall(),
+            KeyCode::Char('a') => {
+                self.admin_menu = self
+                    .selected()
+                    .map(|node| AdminMenu::new(&node.id, node.log_level));
+            }
+            KeyCode::Enter => self.submit_settings(),
+            KeyCode::Esc => self.load_settings(true),
+            _ => {
+                // Editing moves on from whatever was last sent.
+                if let Some(editor) = &mut self.settings
+                    && editor.form.handle_key(key_event)
+                {
+                    editor.sent = None;
+                    editor.error = None;
+                }
+            }
+        }
+    }
+
+    /// Handles the tick event of the terminal.
+    /// Returns whether meaningful change occured, which should trigger a re-render of terminal.
+    pub fn tick(&mut self) -> bool {
+        let mut did_work = false;
+        let now = std::time::SystemTime::now();
+        let mut health_alerts = Vec::new();
+        for node in self.nodes.values_mut() {
+            // The daemon's gRPC call should time out and take some default action
+            // in the absence of a Rule created by us.
+            did_work |= node.expire_connections(now);
+
+            let health = node.health(now, self.stale_threshold);
+            if health != node.last_health {
+                did_work = true;
+                if health != Health::Connected {
+                    health_alerts.push(node_alert(
+                        &node.id,
+                        alert::Type::Warning,
+                        format!("Daemon is {health}"),
+                    ));
+                }
+                node.last_health = health;
+            }
+        }
+        for alert in health_alerts {
+            self.push_alert(alert);
+        }
+
+        for record in self.notifications.expire(now) {
+            did_work = true;
+            self.audit(&record);
+            self.push_alert(node_alert(
+                &record.node,
+                alert::Type::Warning,
+                format!(
+                    "No reply to notification #{} ({})",
+                    record.notification.id,
+                    record.action().as_str_name()
+                ),
+            ));
+        }
+        // Keep the "no ping for Ns" counter ticking while the selected node is unhealthy.
+        did_work |= self
+            .selected()
+            .is_some_and(|node| node.last_health != Health::Connected);
+
+        // Routinely expire alerts.
+        match self.current_alerts.front() {
+            None => {}
+            Some(alert) => {
+                if let Ok(age) = now.duration_since(alert.timestamp)
+                    && age >= ALERT_LIFETIME
+                {
+                    // Pop this off but also correct the render offset in case it's set to back of list.
+                    self.current_alerts.pop_front();
+                    self.alert_list_render_offset = std::cmp::min(
+                        self.alert_list_render_offset,
+                        self.visible_alerts().count().saturating_sub(1),
+                    );
+                    did_work = true;
+                }
+            }
+        }
+
+        did_work
+    }
+
+    /// Track the server's state, alerting when it stops serving while the app still runs.
+    pub fn update_server_state(&mut self, state: ServerState) {
+        let msg = match &state {
+            ServerState::Failed(err) => Some(err.clone()),
+            ServerState::Stopped if self.running => Some(String::from("gRPC server stopped")),
+            _ => None,
+        };
+        if let Some(msg) = msg {
+            self.push_alert(app_alert(alert::Type::Error, msg));
+        }
+        self.server_state = state;
+    }
+
+    /// Set running to false to quit the application.
+    pub fn quit(&mut self) {
+        // Don't leave tasks running on daemons with no one to report to.
+        self.stop_sockets_monitor();
+        self.stop_pid_monitor();
+        self.running = false;
+    }
+
+    /// Update peer stats from incoming Ping payload, flagging reconnects and daemon restarts.
+    /// Staleness is checked on tick, and can be simulated locally via:
+    /// iptables -A INPUT -p tcp --dport 50051 -j DROP
+    /// iptables -D INPUT -p tcp --dport 50051 -j DROP
+    pub fn update_stats(&mut self, ping_event: PingEvent) {
+        let now = std::time::SystemTime::now();
+        let stale_threshold = self.stale_threshold;
+        let added = self
+            .history
+            .extend(&ping_event.node, &ping_event.stats.events);
+        let new_events = self.history.newest(added).cloned().collect();
+        self.persist(|store| store.save_ping(&ping_event.node, new_events, &ping_event.stats, now));
+        // Keep a selected event selected as newer ones come in above it.
+        if self.history_view.cursor > 0 && self.selected_node.as_ref() == Some(&ping_event.node) {
+            self.history_view.cursor += added;
+        }
+        let node = self.node_entry(ping_event.node);
+        node.rx_pings = node.rx_pings.saturating_add(1);
+        node.peer = ping_event.peer;
+
+        let mut alerts = Vec::new();
+        // A ping from a node we'd given up on means it's back.
+        if node.last_ping.is_some() && node.health(now, stale_threshold) != Health::Connected {
+            alerts.push(node_alert(
+                &node.id,
+                alert::Type::Info,
+                String::from("Daemon reconnected"),
+            ));
+        }
+        // Counters only ever go up while the daemon is running, so a smaller uptime means a restart.
+        if let Some(prev) = &node.stats
+            && ping_event.stats.uptime < prev.uptime
+        {
+            alerts.push(node_alert(
+                &node.id,
+                alert::Type::Warning,
+                format!(
+                    "Daemon restarted (uptime reset from {}s to {}s)",
+                    prev.uptime, ping_event.stats.uptime
+                ),
+            ));
+        }
+        node.last_ping = Some(now);
+        node.rates.record(now, &ping_event.stats);
+        node.stats = Some(ping_event.stats);
+        node.last_health = node.health(now, stale_threshold);
+        for alert in alerts {
+            self.push_alert(alert);
+        }
+    }
+
+    /// Record a node's notifications stream opening or closing.
+    pub fn update_stream_state(&mut self, node: NodeId, state: StreamState) {
+        let now = std::time::SystemTime::now();
+        let stale_threshold = self.stale_threshold;
+        let node = self.node_entry(node);
+        node.stream_state = state;
+        node.last_health = node.health(now, stale_threshold);
+    }
+
+    /// Record identity details a daemon shares when subscribing.
+    pub fn update_subscription(&mut self, evt: SubscribeEvent) {
+        let early = self.take_unsubscribed(&evt);
+        if let Some(early) = &early {
+            self.history.rekey(&early.id, &evt.node);
+            for alert in &mut self.current_alerts {
+                if alert.node.as_ref() == Some(&early.id) {
+                    alert.node = Some(evt.node.clone());
+                }
+            }
+        }
+        let node = self.node_entry(evt.node);
+        if let Some(early) = early {
+            node.absorb(early);
+        }
+        node.peer = evt.peer;
+        node.name = Some(evt.client_config.name).filter(|name| !name.is_empty());
+        node.version = Some(evt.client_config.version);
+        node.config = evt.config;
+        node.config_json = Some(evt.client_config.config);
+        node.rules = evt.client_config.rules;
+        node.firewall_running = Some(evt.client_config.is_firewall_running);
+        node.system_firewall = evt.client_config.system_firewall;
+        node.log_level = Some(evt.client_config.log_level);
+        self.load_settings(false);
+    }
+
+    /// Pings, prompts and alerts that beat a daemon's subscription here were filed under
+    /// its address. Take that node out, if it's this same connection's, to fold it into
+    /// the subscribed one.
+    fn take_unsubscribed(&mut self, evt: &SubscribeEvent) -> Option<Node> {
+        let peer = evt.peer.as_ref()?;
+        let early = peer.node_id();
+        if early == evt.node || self.nodes.get(&early)?.peer.as_ref() != Some(peer) {
+            return None;
+        }
+        if self.selected_node.as_ref() == Some(&early) {
+            self.selected_node = Some(evt.node.clone());
+        }
+        self.nodes.remove(&early)
+    }
+
+    /// Load the settings form from the selected node's config.
+    /// Edits in progress on the same node are kept, unless `discard`.
+    pub fn load_settings(&mut self, discard: bool) {
+        let previous = self.settings.take();
+        let Some(node) = self
+            .selected_node
+            .as_ref()
+            .and_then(|id| self.nodes.get(id))
+        else {
+            return;
+        };
+        let previous = previous.filter(|editor| editor.node == node.id);
+        if let Some(editor) = previous
+            .as_ref()
+            .filter(|editor| editor.form.is_dirty() && !discard)
+        {
+            self.settings = Some(editor.clone());
+            return;
+        }
+        self.settings = node.config.as_ref().map(|config| {
+            let mut editor = SettingsEditor::new(&node.id, config);
+            if let Some(previous) = previous {
+                editor.form.cursor = previous.form.cursor;
+                editor.sent = previous.sent;
+            }
+            editor
+        });
+    }
+
+    /// Send the edited settings to the selected node as a `CHANGE_CONFIG` notification.
+    /// The form keeps its edits until the daemon confirms them.
+    pub fn submit_settings(&mut self) {
+        let Some(editor) = &self.settings else {
+            return;
+        };
+        let result = if editor.form.is_dirty() {
+            self.nodes
+                .get(&editor.node)
+                .and_then(|node| node.config.as_ref().zip(node.config_json.as_ref()))
+                .ok_or_else(|| String::from("No config from this node"))
+                .and_then(|(config, json)| editor.apply(config)?.merge_into(json))
+        } else {
+            Err(String::from("Nothing changed"))
+        };
+        match result {
+            Ok(json) => {
+                let id = self.send_notification(pb::Action::ChangeConfig, json, vec![], None);
+                if let Some(editor) = &mut self.settings {
+                    editor.sent = id;
+                    editor.error = None;
+                }
+            }
+            Err(err) => {
+                if let Some(editor) = &mut self.settings {
+                    editor.error = Some(err);
+                }
+            }
+        }
+    }
+
+    /// Get a node's state, starting to track it if it's new.
+    /// The first node seen gets selected so there's something to look at.
+    fn node_entry(&mut self, id: NodeId) -> &mut Node {
+        if self.selected_node.is_none() {
+            self.selected_node = Some(id.clone());
+        }
+        self.nodes
+            .entry(id.clone())
+            .or_insert_with(|| Node::new(id))
+    }
+
+    /// The node that views and actions are scoped to, if any.
+    #[must_use]
+    pub fn selected(&self) -> Option<&Node> {
+        self.selected_node
+            .as_ref()
+            .and_then(|id| self.nodes.get(id))
+    }
+
+    /// Mutable access to the node that views and actions are scoped to, if any.
+    pub fn selected_mut(&mut self) -> Option<&mut Node> {
+        self.selected_node
+            .as_ref()
+            .and_then(|id| self.nodes.get_mut(id))
+    }
+
+    /// Cycle the selected node forwards or backwards, in node key order.
+    pub fn select_next_node(&mut self, forward: bool) {
+        let ids: Vec<&NodeId> = self.nodes.keys().collect();
+        if ids.is_empty() {
+            return;
+        }
+        let current = self
+            .selected_node
+            .as_ref()
+            .and_then(|id| ids.iter().position(|other| *other == id));
+        let next = match (current, forward) {
+            (None, _) => 0,
+            (Some(idx), true) => (idx + 1) % ids.len(),
+            (Some(idx), false) => (idx + ids.len() - 1) % ids.len(),
+        };
+        self.selected_node = Some(ids[next].clone());
+        self.alert_list_render_offset = 0;
+        self.notification_log_offset = 0;
+        self.history_view = HistoryView::default();
+        self.stats_view.cursor = 0;
+        self.rules_view.cursor = 0;
+        self.firewall_view.cursor = 0;
+        self.firewall_view.expanded.clear();
+        self.firewall_view.status = None;
+        self.firewall_view.nft_scroll = 0;
+        // Don't carry a question about one node's state over to another.
+        self.confirmation = None;
+        self.admin_menu = None;
+        self.load_settings(false);
+        // The sockets view follows the selected node.
+        if self.view == View::Sockets
+            && self
+                .sockets_view
+                .monitor
+                .as_ref()
+                .is_none_or(|monitor| self.selected_node.as_ref() != Some(&monitor.node))
+        {
+            self.stop_sockets_monitor();
+            self.start_sockets_monitor();
+        }
+    }
+
+    /// Alerts relevant to the selected node, including the TUI's own.
+    pub fn visible_alerts(&self) -> impl Iterator<Item = &alert::Alert> {
+        self.current_alerts.iter().filter(|alert| {
+            alert.node.is_none() || alert.node.as_ref() == self.selected_node.as_ref()
+        })
+    }
+
+    /// Send a notification to the selected node, tracking it until the daemon replies.
+    /// Returns the notification ID, or none if no node is selected.
+    pub fn send_notification(
+        &mut self,
+        action: pb::Action,
+        data: String,
+        rules: Vec<pb::Rule>,
+        sys_firewall: Option<pb::SysFirewall>,
+    ) -> Option<u64> {
+        let node = self.selected_node.clone()?;
+        Some(self.send_notification_to(&node, action, data, rules, sys_firewall))
+    }
+
+    /// Send a notification to a node, tracking it until the daemon replies.
+    /// Returns the notification ID.
+    pub fn send_notification_to(
+        &mut self,
+        node: &NodeId,
+        action: pb::Action,
+        data: String,
+        rules: Vec<pb::Rule>,
+        sys_firewall: Option<pb::SysFirewall>,
+    ) -> u64 {
+        let notification = self
+            .notifications
+            .track(node, action, data, rules, sys_firewall);
+        let id = notification.id;
+
+        // Never block the UI on the server. The lock is only ever held briefly by the server,
+        // and a full stream buffer means the daemon isn't keeping up anyway.
+        let send_result = match self.notification_senders.try_lock() {
+            Ok(senders) => match senders.get(node) {
+                Some(sender) => sender
+                    .try_send(Ok(notification))
+                    .map_err(|err| err.to_string()),
+                None => Err(String::from("no notifications stream from this node")),
+            },
+            Err(err) => Err(err.to_string()),
+        };
+        if let Err(err) = send_result {
+            self.push_alert(node_alert(
+                node,
+                alert::Type::Error,
+                format!(
+                    "Unable to send notification #{id} ({}): {err}",
+                    action.as_str_name()
+                ),
+            ));
+            self.notifications.send_failed(id, err);
+        }
+        if let Some(record) = self.notifications.get(id).cloned() {
+            self.audit(&record);
+        }
+        id
+    }
+
+    /// Note where a daemon command stands in the audit trail, and the store.
+    /// Notifications that aren't [`ADMIN_ACTIONS`] or [`CONTROL_ACTIONS`] are left out.
+    fn audit(&mut self, record: &notification::Record) {
+        let action = record.action();
+        if !ADMIN_ACTIONS.contains(&action) && !CONTROL_ACTIONS.contains(&action) {
+            return;
+        }
+        let entry = audit::Entry::new(record);
+        self.persist(|store| store.save_audit(&entry));
+        self.audit.push(entry);
+    }
+
+    /// Match a daemon's notification reply to what was sent, surfacing failures
+    /// and applying what the daemon confirmed.
+    pub fn handle_notification_reply(&mut self, node: &NodeId, reply: &pb::NotificationReply) {
+        if let Some(monitor) = self
+            .pid_monitor
+            .as_mut()
+            .filter(|monitor| monitor.node == *node && monitor.task_id == reply.id)
+        {
+            monitor.update(reply, std::time::SystemTime::now());
+            // Reports keep coming under the same ID, the first one settles the notification.
+            // Problems show in the monitor's panel rather than as alerts.
+            self.notifications.reply(node, reply);
+            return;
+        }
+        if let Some(monitor) = self
+            .sockets_view
+            .monitor
+            .as_mut()
+            .filter(|monitor| monitor.node == *node && monitor.task_id == reply.id)
+        {
+            monitor.update(reply, std::time::SystemTime::now());
+            self.notifications.reply(node, reply);
+            return;
+        }
+        let settled = self.notifications.reply(node, reply).cloned();
+        if let Some(record) = &settled {
+            self.audit(record);
+        }
+        let (msg, confirmed) = match &settled {
+            Some(record) => match &record.outcome {
+                Outcome::Error(data) => (
+                    Some(format!(
+                        "Notification #{} ({}) failed: {data}",
+                        reply.id,
+                        record.action().as_str_name()
+                    )),
+                    None,
+                ),
+                Outcome::Ok(_) => (None, Some(record.notification.clone())),
+                _ => (None, None),
+            },
+            // Not something we're waiting on, but still worth knowing about if it's an error.
+            None if reply.code() == pb::NotificationReplyCode::Error => {
+                (Some(reply.data.clone()), None)
+            }
+            None => (None, None),
+        };
+        if let Some(msg) = msg {
+            self.push_alert(node_alert(node, alert::Type::Error, msg));
+        }
+        if let Some(notification) = confirmed {
+            if ADMIN_ACTIONS.contains(&notification.r#type()) {
+                let msg = format!(
+                    "Daemon accepted {} (#{})",
+                    notification.r#type().as_str_name(),
+                    notification.id
+                );
+                self.push_alert(node_alert(node, alert::Type::Info, msg));
+            }
+            self.apply_notification(node, &notification);
+        }
+    }
+
+    /// Bring local state in line with a notification the daemon has confirmed.
+    fn apply_notification(&mut self, node: &NodeId, notification: &pb::Notification) {
+        if let Some(node) = self.nodes.get_mut(node) {
+            match notification.r#type() {
+                pb::Action::EnableInterception => node.interception = Some(true),
+                pb::Action::DisableInterception => node.interception = Some(false),
+                pb::Action::EnableFirewall => node.firewall_running = Some(true),
+                pb::Action::DisableFirewall => node.firewall_running = Some(false),
+                pb::Action::ReloadFwRules if notification.sys_firewall.is_some() => {
+                    node.system_firewall.clone_from(&notification.sys_firewall);
+                }
+                pb::Action::LogLevel => node.log_level = notification.data.parse().ok(),
+                _ => {}
+            }
+            for rule in &notification.rules {
+                match notification.r#type() {
+                    pb::Action::ChangeRule => node.upsert_rule(rule.clone()),
+                    pb::Action::DeleteRule => {
+                        node.remove_rule(&rule.name);
+                    }
+                    action @ (pb::Action::EnableRule | pb::Action::DisableRule) => {
+                        if let Some(known) =
+                            node.rules.iter_mut().find(|known| known.name == rule.name)
+                        {
+                            known.enabled = action == pb::Action::EnableRule;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+        if self
+            .rules_view
+            .editor
+            .as_ref()
+            .is_some_and(|editor| editor.sent == Some(notification.id))
+        {
+            self.rules_view.editor = None;
+        }
+        if self
+            .firewall_view
+            .editor
+            .as_ref()
+            .is_some_and(|editor| editor.sent == Some(notification.id))
+        {
+            self.firewall_view.editor = None;
+        }
+        if notification.r#type() == pb::Action::ChangeConfig {
+            // Sent by us, so it parses, but don't trust that blindly.
+            if let Ok(config) = OpenSnitchDaemonConfig::parse(&notification.data)
+                && let Some(node) = self.nodes.get_mut(node)
+            {
+                node.config = Some(config);
+                node.config_json = Some(notification.data.clone());
+            }
+            let ours = self
+                .settings
+                .as_ref()
+                .is_some_and(|editor| editor.sent == Some(notification.id));
+            self.load_settings(ours);
+        }
+    }
+
+    /// The selected node's rules, in table order.
+    #[must_use]
+    pub fn visible_rules(&self) -> Vec<&pb::Rule> {
+        self.selected()
+            .map_or_else(Vec::new, |node| self.rules_view.sorted(&node.rules))
+    }
+
+    /// The rule under the cursor in the rules table.
+    /// The cursor may have run past the end if rules went away, so it's clamped here.
+    #[must_use]
+    pub fn selected_rule(&self) -> Option<&pb::Rule> {
+        let rules = self.visible_rules();
+        let idx = std::cmp::min(self.rules_view.cursor, rules.len().checked_sub(1)?);
+        Some(rules[idx])
+    }
+
+    /// Rows of the firewall view for the selected node.
+    #[must_use]
+    pub fn firewall_rows(&self) -> Vec<firewall::Row> {
+        self.selected_firewall()
+            .map(|firewall| self.firewall_view.rows(firewall))
+            .unwrap_or_default()
+    }
+
+    /// The row under the cursor in the firewall view.
+    #[must_use]
+    pub fn selected_firewall_row(&self) -> Option<firewall::Row> {
+        self.selected_firewall()
+            .and_then(|firewall| self.firewall_view.selected_row(firewall))
+    }
+
+    /// The latest of the given kinds of notification sent to the selected node,
+    /// to show how it went.
+    #[must_use]
+    pub fn last_notification(&self, actions: &[pb::Action]) -> Option<&notification::Record> {
+        self.notification_log()
+            .find(|record| actions.contains(&record.action()))
+    }
+
+    /// Notifications sent to the selected node, newest first.
+    pub fn notification_log(&self) -> impl Iterator<Item = &notification::Record> {
+        self.selected_node
+            .iter()
+            .flat_map(|node| self.notifications.log_for(node))
+            .rev()
+    }
+
+    /// Queue the latest inbound event on the trapping node.
+    pub fn update_connection(&mut self, evt: ConnectionEvent) {
+        self.node_entry(evt.node.clone()).push_connection(evt);
+    }
+
+    /// Clear the selected node's current prompt, returning it.
+    pub fn clear_connection(&mut self) -> Option<ConnectionEvent> {
+        let id = self.current_connection()?.id;
+        self.selected_mut()?.remove_connection(id)
+    }
+
+    /// Info on the selected node's connection awaiting a rule determination.
+    #[must_use]
+    pub fn current_connection(&self) -> Option<&ConnectionEvent> {
+        self.selected().and_then(Node::current_connection)
+    }
+
+    /// Walk through the selected node's prompt queue.
+    pub fn move_prompt_cursor(&mut self, forward: bool) {
+        if let Some(node) = self.selected_mut() {
+            node.move_prompt_cursor(forward);
+        }
+    }
+
+    /// Generate a rule for the selected node's current connection.
+    /// Returns `none` if there is no current connection.
+    fn make_rule(
+        &self,
+        action: constants::Action,
+        duration: constants::Duration,
+    ) -> Option<pb::Rule> {
+        // Noop if there's no connection trapped.
+        let conn = &self.current_connection()?.connection;
+        Some(rule_for(conn, action, duration))
+    }
+
+    /// Hand a rule to the matching in-flight `AskRule` call.
+    /// A noop if the call has already given up waiting.
+    /// Returns whether the rule was handed over.
+    fn send_rule(&self, id: u64, rule: pb::Rule) -> bool {
+        self.pending_rules
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .is_some_and(|rule_tx| rule_tx.send(rule).is_ok())
+    }
+
+    fn make_and_send_rule(&mut self, action: constants::Action, duration: constants::Duration) {
+        if let Some(rule) = self.make_rule(action, duration)
+            && let Some(conn) = self.clear_connection()
+            && self.send_rule(conn.id, rule.clone())
+        {
+            let now = std::time::SystemTime::now();
+            self.persist(|store| store.save_decision(&conn.node, &conn.connection, &rule, now));
+            if is_kept(&rule)
+                && let Some(node) = self.nodes.get_mut(&conn.node)
+            {
+                node.upsert_rule(rule);
+            }
+        }
+    }
+
+    /// Add an alert to the alerts list, and to the store.
+    fn push_alert(&mut self, alert: alert::Alert) {
+        self.persist(|store| store.save_alert(&alert));
+        self.current_alerts.push_back(alert);
+    }
+
+    /// Hand a write to the store, if there's one. The store stops at the first write
+    /// to fail, with an alert of its own, and is then let go here.
+    fn persist(&mut self, write: impl FnOnce(&mut Writer) -> Result<(), String>) {
+        let Some(store) = &mut self.store else {
+            return;
+        };
+        if write(store).is_err() {
+            self.store = None;
+        }
+    }
+}
+
+/// Generate a rule for a trapped connection.
+/// Matches on user ID && process path && IP dst && l4 port && l4 protocol.
+/// TODO: Consider including process hash for extra strictness.
+/// * `action`: Whether the rule for this connection should allow or deny the flow.
+fn rule_for(
+    conn: &pb::Connection,
+    action: constants::Action,
+    duration: constants::Duration,
+) -> pb::Rule {
+    // Build up an array of "safe"ish default operators to match this process's
+    // specific connection, though this can obviously be better validated/configured
+    // in the future.
+    // This could have also been implemented with enum+trait magic, but using a simple
+    // Operator factory lets us pass this vector into the larger Rule we are creating.
+    let operators = vec![
+        operator_util::match_user_id(conn.user_id),
+        operator_util::match_proc_path(&conn.process_path),
+        operator_util::match_dst_ip(&conn.dst_ip),
+        operator_util::match_dst_port(conn.dst_port),
+        operator_util::match_protocol(&conn.protocol),
+    ];
+
+    let action_str = action.get_str();
+    let duration = String::from(duration.get_str());
+    let pretty_proc_path = conn.process_path.clone().replace('/', "-");
+    let maybe_operator_json = serde_json::to_string(&operators);
+    // Shouldn't really happen due to serde_impl.rs, ideally something caught at build time.
+    assert!(
+        maybe_operator_json.is_ok(),
+        "Operator list JSON serialization failed: {}",
+        maybe_operator_json.unwrap_err()
+    );
+
+    pb::Rule {
+        created: 0,
+        // TODO: Leading slash gets turned into double-dash, may be annoying
+        name: format!("{action_str}-{duration}-simple-via-tui-{pretty_proc_path}"),
+        description: String::default(),
+        enabled: true,
+        precedence: false,
+        nolog: false,
+        action: String::from(action_str),
+        duration,
+        operator: Some(pb::Operator {
+            r#type: String::from(constants::RuleType::List.get_str()),
+            operand: String::from(constants::Operand::List.get_str()),
+            data: maybe_operator_json.unwrap(),
+            sensitive: false,
+            list: operators,
+        }),
+    }
+}
+
+/// Whether the daemon keeps a rule it was answered with. One-off answers aren't kept.
+fn is_kept(rule: &pb::Rule) -> bool {
+    rule.duration != constants::Duration::Once.get_str()
+}
+
+/// Opens the store at `path`, reads back what earlier runs kept in it, and starts
+/// writing to it. A failed write is reported as an alert through `events`.
+fn open_store(
+    path: &Path,
+    events: &EventHandler,
+) -> Result<(Option<Writer>, EventHistory, VecDeque<alert::Alert>), String> {
+    let store = Store::open(path, Retention::default())
+        .map_err(|err| format!("Unable to open the store: {err}"))?;
+    let (history, alerts) = restore(&store);
+    let sender = events.sender.clone();
+    let writer = Writer::spawn(store, move |err| {
+        let alert = app_alert(
+            alert::Type::Error,
+            format!("Stopped saving to the store: {err}"),
+        );
+        let _ = sender.send(Event::App(Box::new(AppEvent::Alert(alert))));
+    });
+    Ok((Some(writer), history, alerts))
+}
+
+/// Events and alerts kept in the store from earlier runs: the history, and whatever
+/// alerts would still be listed. What can't be read back is left out, with an alert.
+fn restore(store: &Store) -> (EventHistory, VecDeque<alert::Alert>) {
+    let mut history = EventHistory::default();
+    let mut problems = Vec::new();
+    match store.events(crate::history::HISTORY_CAPACITY) {
+        Ok((events, skipped)) => {
+            for entry in events {
+                history.extend(&entry.node, std::slice::from_ref(&entry.event));
+            }
+            if skipped > 0 {
+                problems.push(format!("Skipped {skipped} unreadable events in the store"));
+            }
+        }
+        Err(err) => problems.push(format!("Unable to read events from the store: {err}")),
+    }
+    let since = std::time::SystemTime::now() - ALERT_LIFETIME;
+    let mut alerts: VecDeque<alert::Alert> = match store.alerts_since(since) {
+        Ok(alerts) => alerts.into(),
+        Err(err) => {
+            problems.push(format!("Unable to read alerts from the store: {err}"));
+            VecDeque::new()
+        }
+    };
+    alerts.extend(
+        problems
+            .into_iter()
+            .map(|msg| app_alert(alert::Type::Warning, msg)),
+    );
+    (history, alerts)
+}
+
+/// Alert generated by the TUI itself, about no node in particular.
+fn app_alert(r#type: alert::Type, msg: String) -> alert::Alert {
+    alert::Alert {
+        node: None,
+        timestamp: std::time::SystemTime::now(),
+        priority: alert::Priority::High,
+        r#type,
+        what: alert::What::Generic,
+        msg,
+    }
+}
+
+/// Alert generated by the TUI itself about a node.
+fn node_alert(node: &NodeId, r#type: alert::Type, msg: String) -> alert::Alert {
+    alert::Alert {
+        node: Some(node.clone()),
+        timestamp: std::time::SystemTime::now(),
+        priority: alert::Priority::High,
+        r#type,
+        what: alert::What::Generic,
+        msg,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::breakdown::Dimension;
+    use crate::opensnitch_proto::pb::{Connection, Rule};
+    use crate::rates::Window;
+    use std::time::SystemTime;
+
+    use super::*;
+
+    /// Convenience Alias for String-to-String Hashmap.
+    type S2SMap = std::collections::HashMap<String, String>;
+
+    /// Helper for the command line defaults, listening on the given address.
+    fn options(bind: &str) -> AppOptions {
+        AppOptions {
+            bind: String::from(bind),
+            tls: None,
+            allow_insecure_bind: false,
+            default_action: String::from("deny"),
+            exit_action: None,
+            temp_rule_lifetime: String::from("12h"),
+            dispo_seconds: 60,
+            stale_seconds: 10,
+            store: StoreLocation::Off,
+        }
+    }
+
+    /// Receiving end of a node's notifications stream.
+    type Notifications = tokio::sync::mpsc::Receiver<Result<pb::Notification, tonic::Status>>;
+
+    /// Node the app from [`test_app_with_node`] is subscribed to.
+    const NODE: &str = "10.0.0.1";
+
+    /// Helper for an app with [`NODE`] subscribed, and selected, with `client_config`.
+    /// Its notifications stream is open, and the receiving end is returned with the app.
+    fn test_app_with_node(client_config: pb::ClientConfig) -> (App, Notifications) {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        let config = OpenSnitchDaemonConfig::parse(&client_config.config).ok();
+        app.update_subscription(SubscribeEvent {
+            node: String::from(NODE),
+            peer: None,
+            client_config,
+            config,
+        });
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        app.notification_senders
+            .try_lock()
+            .expect("senders locked")
+            .insert(String::from(NODE), tx);
+        (app, rx)
+    }
+
+    /// Helper to press a key.
+    fn press(app: &mut App, code: KeyCode) {
+        app.handle_key_events(KeyEvent::from(code)).unwrap();
+    }
+
+    /// Helper to reply to a notification sent to [`NODE`], without data.
+    fn reply(app: &mut App, id: u64, code: pb::NotificationReplyCode) {
+        app.handle_notification_reply(
+            &String::from(NODE),
+            &pb::NotificationReply {
+                id,
+                code: code.into(),
+                data: String::new(),
+            },
+        );
+    }
+
+    /// Simple construction test.
+    #[tokio::test]
+    async fn test_new() {
+        let _ = App::new(&options("127.0.0.1:65534")).expect("new failed");
+    }
+
+    /// Construction test with a Unix domain socket bind string.
+    #[tokio::test]
+    async fn test_new_unix() {
+        let app = App::new(&options("unix:///tmp/osui.sock")).expect("new failed");
+        assert_eq!(
+            app.server.address(),
+            &BindAddress::Unix(std::path::PathBuf::from("/tmp/osui.sock"))
+        );
+    }
+
+    /// Test that plaintext non-loopback binds need an explicit opt-in.
+    #[tokio::test]
+    async fn test_new_insecure_bind() {
+        let make = |allow_insecure_bind| {
+            App::new(&AppOptions {
+                allow_insecure_bind,
+                ..options("0.0.0.0:65534")
+            })
+        };
+        assert!(make(false).is_err());
+        assert!(make(true).is_ok());
+    }
+
+    /// Test that making a rule with no "current connection" generates a noop.
+    #[tokio::test]
+    async fn test_make_rule_no_conn() {
+        let app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+
+        assert!(app.current_connection().is_none());
+
+        let maybe_rule = app.make_rule(constants::Action::Allow, constants::Duration::Once);
+        assert!(maybe_rule.is_none());
+    }
+
+    /// Helper to make a fake connection object.
+    fn make_fake_connection() -> Connection {
+        Connection {
+            protocol: String::from("tcp"),
+            src_ip: String::from("192.168.0.3"),
+            src_port: 1337,
+            dst_ip: String::from("192.128.0.4"),
+            dst_host: String::from("suspicious.local"),
+            dst_port: 1338,
+            user_id: 1000,
+            process_id: 1339,
+            process_path: String::from("/usr/bin/hello"),
+            process_cwd: String::from("/home/spongebob"),
+            process_args: vec![],
+            process_env: S2SMap::default(),
+            process_checksums: S2SMap::default(),
+            process_tree: vec![],
+        }
+    }
+
+    /// Test that making a rule with a valid "current connection" generates something meaningful.
+    #[tokio::test]
+    async fn test_make_rule_has_conn() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+
+        let fake_conn = make_fake_connection();
+        app.update_connection(ConnectionEvent {
+            id: 0,
+            node: String::from("127.0.0.1"),
+            connection: fake_conn.clone(),
+            expiry_ts: SystemTime::now() + app.connection_disposition_timeout,
+        });
+
+        let maybe_rule = app
+            .make_rule(constants::Action::Allow, constants::Duration::Once)
+            .expect("missing rule");
+
+        // JSON blob representing the vector of operators we use for a rule.
+        // Checking this also acts as a high-level test for serde_json not producing
+        // unexpected output in the future.
+        let expected_str = "[{\"type\":\"simple\",\"operand\":\"user.id\",\"data\":\"1000\",\
+        \"sensitive\":false,\"list\":[]},{\"type\":\"simple\",\"operand\":\"process.path\",\
+        \"data\":\"/usr/bin/hello\",\"sensitive\":false,\"list\":[]},{\"type\":\"simple\",\
+        \"operand\":\"dest.ip\",\"data\":\"192.128.0.4\",\"sensitive\":false,\"list\":[]},\
+        {\"type\":\"simple\",\"operand\":\"dest.port\",\"data\":\"1338\",\"sensitive\":false,\
+        \"list\":[]},{\"type\":\"simple\",\"operand\":\"protocol\",\"data\":\"tcp\",\"sensitive\"\
+        :false,\"list\":[]}]";
 
         let expected_rule = Rule {
             created: 0,
@@ -485,6 +2380,1062 @@ mod tests {
             }),
         };
 
-        assert_eq!(maybe_rule, expected_rule)
+        assert_eq!(maybe_rule, expected_rule);
+    }
+
+    /// Helper to make a ping from a given TCP peer.
+    fn make_ping(peer: &str) -> PingEvent {
+        let peer = crate::event::Peer::Tcp(peer.parse().unwrap());
+        PingEvent {
+            node: peer.node_id(),
+            peer: Some(peer),
+            stats: pb::Statistics::default(),
+        }
+    }
+
+    /// Test that state is kept per node and rules are routed to the selected node.
+    #[tokio::test]
+    async fn test_multi_node() {
+        let (mut app, _notifications) = test_app_with_node(pb::ClientConfig::default());
+
+        app.update_stats(make_ping("10.0.0.2:4000"));
+        app.update_stats(make_ping("10.0.0.1:5000"));
+        // Reconnects from a new source port belong to the same node.
+        app.update_stats(make_ping("10.0.0.2:4001"));
+        assert_eq!(app.nodes.len(), 2);
+        assert_eq!(app.nodes["10.0.0.2"].rx_pings, 2);
+        assert_eq!(app.nodes["10.0.0.1"].rx_pings, 1);
+        // First node seen stays selected, cycling goes in key order and wraps around.
+        assert_eq!(app.selected_node.as_deref(), Some(NODE));
+        press(&mut app, KeyCode::Char('n'));
+        assert_eq!(app.selected_node.as_deref(), Some("10.0.0.2"));
+        press(&mut app, KeyCode::Char('N'));
+        assert_eq!(app.selected_node.as_deref(), Some(NODE));
+        press(&mut app, KeyCode::Char('N'));
+        assert_eq!(app.selected_node.as_deref(), Some("10.0.0.2"));
+
+        // Both nodes ask for a rule, only the selected one gets an answer.
+        let mut receivers = ask_rules(&mut app, &[(1, "10.0.0.1"), (2, "10.0.0.2")]);
+        app.make_and_send_rule(constants::Action::Deny, constants::Duration::Once);
+        assert!(app.nodes["10.0.0.2"].pending_connections.is_empty());
+        assert_eq!(app.nodes["10.0.0.1"].pending_connections.len(), 1);
+        let rule = receivers.remove(&2).unwrap().await.expect("no rule routed");
+        assert_eq!(rule.action, "deny");
+        assert!(receivers.remove(&1).unwrap().try_recv().is_err());
+    }
+
+    /// Test that a ping filed under a daemon's address before it subscribed is folded
+    /// into the node it subscribes as: stats, history, alerts, prompts and all.
+    #[tokio::test]
+    async fn test_subscribe_after_ping() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        let mut ping = make_ping("10.0.0.1:4000");
+        ping.stats.uptime = 7;
+        ping.stats.events = vec![pb::Event {
+            unixnano: 1,
+            ..Default::default()
+        }];
+        let peer = ping.peer.clone().unwrap();
+        app.update_stats(ping);
+        let early = String::from("10.0.0.1");
+        app.push_alert(node_alert(&early, alert::Type::Info, String::from("early")));
+        let _receivers = ask_rules(&mut app, &[(1, "10.0.0.1")]);
+        let subscribe = |peer: Peer, name: &str| SubscribeEvent {
+            node: peer.named_node_id(name),
+            peer: Some(peer),
+            client_config: pb::ClientConfig {
+                name: String::from(name),
+                ..Default::default()
+            },
+            config: None,
+        };
+
+        app.update_subscription(subscribe(peer, "laptop"));
+        let ids: Vec<&str> = app.nodes.keys().map(String::as_str).collect();
+        assert_eq!(ids, ["laptop@10.0.0.1"]);
+        assert_eq!(app.selected_node.as_deref(), Some("laptop@10.0.0.1"));
+        let node = app.selected().unwrap();
+        assert_eq!(node.label(), "laptop (10.0.0.1)");
+        assert_eq!(node.pending_connections[0].node, "laptop@10.0.0.1");
+        assert_eq!(node.rx_pings, 1);
+        assert_eq!(node.stats.as_ref().map(|stats| stats.uptime), Some(7));
+        assert!(node.last_ping.is_some());
+        assert_eq!(app.history.for_node(&node.id).count(), 1);
+        assert!(app.history.for_node(&early).next().is_none());
+        let alert = app.current_alerts.iter().find(|alert| alert.msg == "early");
+        assert_eq!(
+            alert.and_then(|alert| alert.node.as_deref()),
+            Some("laptop@10.0.0.1")
+        );
+
+        // Another daemon behind the same address that hasn't subscribed yet stays put.
+        app.update_stats(make_ping("10.0.0.1:5000"));
+        let other = make_ping("10.0.0.1:6000").peer.unwrap();
+        app.update_subscription(subscribe(other, "desktop"));
+        let ids: Vec<&str> = app.nodes.keys().map(String::as_str).collect();
+        assert_eq!(ids, ["10.0.0.1", "desktop@10.0.0.1", "laptop@10.0.0.1"]);
+    }
+
+    /// Helper to queue `AskRule` prompts the way the server would, returning their reply channels.
+    fn ask_rules(
+        app: &mut App,
+        requests: &[(u64, &str)],
+    ) -> HashMap<u64, tokio::sync::oneshot::Receiver<Rule>> {
+        let mut receivers = HashMap::new();
+        for (id, node) in requests {
+            let (rule_tx, rule_rx) = tokio::sync::oneshot::channel();
+            app.pending_rules.lock().unwrap().insert(*id, rule_tx);
+            receivers.insert(*id, rule_rx);
+            app.update_connection(ConnectionEvent {
+                id: *id,
+                node: String::from(*node),
+                connection: make_fake_connection(),
+                expiry_ts: SystemTime::now() + app.connection_disposition_timeout,
+            });
+        }
+        receivers
+    }
+
+    /// Test that concurrent prompts from one node queue up and can be answered in any order.
+    #[tokio::test]
+    async fn test_prompt_queue() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+
+        let mut receivers = ask_rules(
+            &mut app,
+            &[(7, "10.0.0.1"), (8, "10.0.0.1"), (9, "10.0.0.1")],
+        );
+        assert_eq!(app.current_connection().unwrap().id, 7);
+
+        // Skip ahead to the second prompt and answer it.
+        app.move_prompt_cursor(true);
+        assert_eq!(app.current_connection().unwrap().id, 8);
+        app.make_and_send_rule(constants::Action::Allow, constants::Duration::Once);
+        assert_eq!(receivers.remove(&8).unwrap().await.unwrap().action, "allow");
+        assert_eq!(app.current_connection().unwrap().id, 9);
+
+        // The daemon giving up on a prompt drops it from the queue.
+        app.nodes.get_mut("10.0.0.1").unwrap().remove_connection(9);
+        assert_eq!(app.current_connection().unwrap().id, 7);
+        app.make_and_send_rule(constants::Action::Deny, constants::Duration::Once);
+        assert_eq!(receivers.remove(&7).unwrap().await.unwrap().action, "deny");
+        assert!(app.current_connection().is_none());
+    }
+
+    /// Test that a pid monitor is started for the prompted process, keeps taking reports
+    /// under its notification's ID, and is stopped on its own node.
+    #[tokio::test]
+    async fn test_pid_monitor() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig::default());
+        let node = String::from(NODE);
+        let _receivers = ask_rules(&mut app, &[(7, NODE)]);
+        let pid = app.current_connection().unwrap().connection.process_id;
+
+        press(&mut app, KeyCode::Char('m'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::TaskStart);
+        assert_eq!(sent.data, crate::task::PidMonitor::task_data(pid));
+        for resident in [1, 2] {
+            app.handle_notification_reply(
+                &node,
+                &pb::NotificationReply {
+                    id: sent.id,
+                    code: pb::NotificationReplyCode::Ok.into(),
+                    data: format!(r#"{{"Statm": {{"Resident": {resident}}}}}"#),
+                },
+            );
+        }
+        let monitor = app.pid_monitor.as_ref().unwrap();
+        assert_eq!(monitor.latest.as_ref().unwrap().1.resident, Some(2 * 4096));
+        assert!(app.current_alerts.is_empty());
+        assert!(matches!(
+            app.notifications.get(sent.id).unwrap().outcome,
+            Outcome::Ok(_)
+        ));
+
+        app.selected_node = None;
+        press(&mut app, KeyCode::Char('M'));
+        assert!(app.pid_monitor.is_none());
+        let stop = rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            (stop.r#type(), stop.data),
+            (pb::Action::TaskStop, sent.data)
+        );
+    }
+
+    /// Test that the sockets monitor runs only while the sockets view is shown, and that
+    /// its reports fill the table.
+    #[tokio::test]
+    async fn test_sockets_view() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig::default());
+        let node = String::from(NODE);
+
+        app.view = View::Firewall;
+        press(&mut app, KeyCode::Tab);
+        assert_eq!(app.view, View::Sockets);
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::TaskStart);
+        let socket = |state, port| {
+            format!(
+                r#"{{"Socket": {{"State": {state}, "ID": {{"SourcePort": {port}, "Source": "127.0.0.1"}}}}, "PID": -1, "Proto": 6}}"#
+            )
+        };
+        app.handle_notification_reply(
+            &node,
+            &pb::NotificationReply {
+                id: sent.id,
+                code: pb::NotificationReplyCode::Ok.into(),
+                data: format!(r#"{{"Table": [{}, {}]}}"#, socket(10, 22), socket(1, 40000)),
+            },
+        );
+        assert_eq!(app.visible_sockets().len(), 2);
+        press(&mut app, KeyCode::Char('f'));
+        assert_eq!(app.sockets_view.state, Some(1));
+        let ports: Vec<u16> = app.visible_sockets().iter().map(|s| s.local_port).collect();
+        assert_eq!(ports, [40000]);
+
+        press(&mut app, KeyCode::BackTab);
+        assert!(app.sockets_view.monitor.is_none());
+        let stop = rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            (stop.r#type(), stop.data),
+            (pb::Action::TaskStop, sent.data)
+        );
+        assert!(app.current_alerts.is_empty());
+    }
+
+    /// Test that ping events build up a history, with the selected event staying
+    /// selected as newer ones arrive.
+    #[test]
+    fn test_history() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        let event = |unixnano, pid| pb::Event {
+            time: String::new(),
+            unixnano,
+            connection: Some(pb::Connection {
+                process_id: pid,
+                ..Default::default()
+            }),
+            rule: None,
+        };
+        let mut ping = make_ping("10.0.0.1:4000");
+        ping.stats.events = vec![event(1, 100), event(2, 200)];
+        app.update_stats(ping.clone());
+        app.view = View::History;
+        let selected_pid = |app: &App| {
+            app.selected_event()
+                .unwrap()
+                .connection
+                .as_ref()
+                .unwrap()
+                .process_id
+        };
+        assert_eq!(selected_pid(&app), 200);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(selected_pid(&app), 100);
+
+        ping.stats.events.push(event(3, 300));
+        app.update_stats(ping);
+        assert_eq!(app.history.len(), 3);
+        assert_eq!(selected_pid(&app), 100);
+
+        press(&mut app, KeyCode::Enter);
+        assert!(app.history_view.detail);
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.history_view.detail);
+        press(&mut app, KeyCode::Char('m'));
+        assert_eq!(app.pid_monitor.as_ref().unwrap().pid, 100);
+        assert_eq!(app.view, View::Main);
+    }
+
+    /// Test that the stats view steps through dimensions, keeping its cursor in range,
+    /// and through chart windows.
+    #[tokio::test]
+    async fn test_stats_view() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        let mut ping = make_ping("10.0.0.1:4000");
+        ping.stats.by_host =
+            HashMap::from([(String::from("a.com"), 1), (String::from("b.com"), 5)]);
+        ping.stats.by_uid = HashMap::from([(String::from("0"), 2)]);
+        app.update_stats(ping);
+        app.view = View::Stats;
+        assert_eq!(app.breakdown().entries[0].key, "b.com");
+        for _ in 0..3 {
+            press(&mut app, KeyCode::Down);
+        }
+        assert_eq!(app.stats_view.cursor, 1);
+
+        press(&mut app, KeyCode::Left);
+        assert_eq!(app.stats_view.dimension, Dimension::Uid);
+        assert_eq!(app.stats_view.cursor, 0);
+        assert_eq!(app.breakdown().total, 2);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(app.stats_view.cursor, 0);
+        // Uids are only named for daemons on this host.
+        app.names = Names::parse("root:x:0:0:root:/root:/bin/sh", "");
+        assert_eq!(app.breakdown().entries[0].name, None);
+        let mut ping = make_ping("127.0.0.1:4000");
+        ping.stats.by_uid = HashMap::from([(String::from("0"), 2)]);
+        app.update_stats(ping);
+        app.selected_node = Some(String::from("127.0.0.1"));
+        assert_eq!(app.breakdown().entries[0].name.as_deref(), Some("root"));
+
+        press(&mut app, KeyCode::Char('w'));
+        assert_eq!(app.stats_view.window, Window::FifteenMinutes);
+    }
+
+    /// Test that the history and recent alerts come back after a restart, leaving out
+    /// what can't be read back.
+    #[tokio::test]
+    async fn test_store_restart() {
+        let path = std::env::temp_dir().join(format!("osui-test-{}-store.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let options = AppOptions {
+            store: StoreLocation::File(path.clone()),
+            ..options("127.0.0.1:65534")
+        };
+        let mut app = App::new(&options).expect("new failed");
+        let mut ping = make_ping("10.0.0.1:4000");
+        ping.stats.events = vec![pb::Event {
+            time: String::new(),
+            unixnano: 1,
+            connection: Some(make_fake_connection()),
+            rule: None,
+        }];
+        app.update_stats(ping);
+        app.update_server_state(ServerState::Failed(String::from("bind failed")));
+        drop(app);
+
+        let app = App::new(&options).expect("new failed");
+        assert_eq!(app.history.len(), 1);
+        let msgs: Vec<&str> = app.current_alerts.iter().map(|a| a.msg.as_str()).collect();
+        assert_eq!(msgs, ["bind failed"]);
+        drop(app);
+
+        // An event that can't be read back is left out, rather than stopping startup.
+        rusqlite::Connection::open(&path)
+            .and_then(|conn| conn.execute("UPDATE events SET event = x'ff'", []))
+            .expect("update failed");
+        let app = App::new(&options).expect("new failed");
+        assert!(app.history.is_empty());
+        assert_eq!(
+            app.current_alerts.back().unwrap().msg,
+            "Skipped 1 unreadable events in the store"
+        );
+        drop(app);
+        std::fs::remove_file(&path).expect("remove failed");
+    }
+
+    /// Test that with no data directory for the store, the app starts anyway, and says so.
+    #[tokio::test]
+    async fn test_store_no_data_dir() {
+        let options = AppOptions {
+            store: StoreLocation::NoDataDir,
+            ..options("127.0.0.1:65534")
+        };
+        let app = App::new(&options).expect("new failed");
+        assert!(app.store.is_none());
+        let alert = app.current_alerts.back().unwrap();
+        assert!(matches!(alert.r#type, alert::Type::Warning));
+        assert!(alert.msg.starts_with("No data directory for the store"));
+    }
+
+    /// Test that a daemon restart and a reconnect after going quiet are both flagged.
+    #[tokio::test]
+    async fn test_liveness_alerts() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+
+        let mut ping = make_ping("10.0.0.1:4000");
+        ping.stats.uptime = 100;
+        app.update_stats(ping.clone());
+        assert!(app.current_alerts.is_empty());
+
+        // Pretend the last ping was long ago, the next tick flags the node.
+        let node = app.nodes.get_mut("10.0.0.1").unwrap();
+        node.last_ping = Some(SystemTime::now() - std::time::Duration::from_secs(15));
+        app.tick();
+        assert_eq!(app.nodes["10.0.0.1"].last_health, Health::Stale);
+        assert_eq!(app.current_alerts.len(), 1);
+
+        // Daemon comes back, freshly restarted.
+        ping.stats.uptime = 3;
+        app.update_stats(ping);
+        assert_eq!(app.nodes["10.0.0.1"].last_health, Health::Connected);
+        let msgs: Vec<&str> = app.current_alerts.iter().map(|a| a.msg.as_str()).collect();
+        assert_eq!(
+            msgs,
+            vec![
+                "Daemon is stale",
+                "Daemon reconnected",
+                "Daemon restarted (uptime reset from 100s to 3s)"
+            ]
+        );
+    }
+
+    /// Test that notifications reach the selected node's stream and replies are correlated.
+    #[tokio::test]
+    async fn test_notification_reply() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+
+        // Nothing to send to without a node.
+        assert!(
+            app.send_notification(pb::Action::Stop, String::new(), vec![], None)
+                .is_none()
+        );
+
+        app.update_stats(make_ping("10.0.0.1:4000"));
+        let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+        app.notification_senders
+            .lock()
+            .await
+            .insert(String::from("10.0.0.1"), tx);
+
+        let id = app
+            .send_notification(pb::Action::ReloadFwRules, String::new(), vec![], None)
+            .expect("no node selected");
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.id, id);
+        assert_eq!(
+            app.notification_log().next().unwrap().outcome,
+            Outcome::Pending
+        );
+
+        let node = String::from("10.0.0.1");
+        app.handle_notification_reply(
+            &node,
+            &pb::NotificationReply {
+                id,
+                code: pb::NotificationReplyCode::Error.into(),
+                data: String::from("no such table"),
+            },
+        );
+        assert_eq!(
+            app.notification_log().next().unwrap().outcome,
+            Outcome::Error(String::from("no such table"))
+        );
+        assert_eq!(app.current_alerts.len(), 1);
+
+        // A node without a stream fails right away.
+        app.update_stats(make_ping("10.0.0.2:4000"));
+        app.select_next_node(true);
+        let id = app
+            .send_notification(pb::Action::Stop, String::new(), vec![], None)
+            .unwrap();
+        let record = app.notification_log().next().unwrap();
+        assert_eq!(record.notification.id, id);
+        assert!(matches!(record.outcome, Outcome::SendFailed(_)));
+        assert_eq!(app.current_alerts.len(), 2);
+    }
+
+    /// Test that settings edits go out as `CHANGE_CONFIG`, and are kept once confirmed.
+    #[tokio::test]
+    async fn test_change_config() {
+        let json = r#"{"DefaultAction": "allow", "LogLevel": 1, "Audit": null}"#;
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig {
+            config: String::from(json),
+            ..Default::default()
+        });
+
+        app.view = View::Settings;
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(
+            app.settings.as_ref().unwrap().error.as_deref(),
+            Some("Nothing changed")
+        );
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Enter);
+
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::ChangeConfig);
+        assert_eq!(
+            sent.data,
+            r#"{"Audit":null,"DefaultAction":"deny","LogLevel":1}"#
+        );
+        assert!(app.settings.as_ref().unwrap().form.is_dirty());
+
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        let config = app.selected().unwrap().config.as_ref().unwrap();
+        assert_eq!(config.DefaultAction.as_deref(), Some("deny"));
+        let editor = app.settings.as_ref().unwrap();
+        assert!(!editor.form.is_dirty());
+        assert_eq!(editor.sent, Some(sent.id));
+    }
+
+    /// Test that a node's rules come from its subscription and follow changes made here.
+    #[tokio::test]
+    async fn test_rules_tracking() {
+        let existing = Rule {
+            name: String::from("zz-existing"),
+            enabled: true,
+            ..Default::default()
+        };
+        let (mut app, _rx) = test_app_with_node(pb::ClientConfig {
+            rules: vec![existing],
+            ..Default::default()
+        });
+        assert_eq!(app.visible_rules().len(), 1);
+
+        // Once rules aren't kept by the daemon, so they aren't listed.
+        let _receivers = ask_rules(&mut app, &[(1, "10.0.0.1"), (2, "10.0.0.1")]);
+        app.make_and_send_rule(constants::Action::Allow, constants::Duration::Once);
+        assert_eq!(app.visible_rules().len(), 1);
+        app.make_and_send_rule(constants::Action::Deny, constants::Duration::Always);
+        let names: Vec<&str> = app
+            .visible_rules()
+            .iter()
+            .map(|rule| rule.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec!["deny-always-simple-via-tui--usr-bin-hello", "zz-existing"]
+        );
+
+        app.rules_view.descending = true;
+        app.rules_view.cursor = 5;
+        assert_eq!(
+            app.selected_rule().unwrap().name,
+            "deny-always-simple-via-tui--usr-bin-hello"
+        );
+
+        let confirm = |app: &mut App, action: pb::Action| {
+            let rule = Rule {
+                name: String::from("zz-existing"),
+                ..Default::default()
+            };
+            let id = app
+                .send_notification(action, String::new(), vec![rule], None)
+                .unwrap();
+            reply(app, id, pb::NotificationReplyCode::Ok);
+        };
+        confirm(&mut app, pb::Action::DisableRule);
+        assert!(!app.selected().unwrap().rules[0].enabled);
+        confirm(&mut app, pb::Action::DeleteRule);
+        assert_eq!(app.visible_rules().len(), 1);
+    }
+
+    /// Test that rule toggles and deletes go out as notifications, deletes only once
+    /// confirmed, and the rules table waits for the daemon's OK.
+    #[tokio::test]
+    async fn test_rule_notifications() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig {
+            rules: vec![Rule {
+                name: String::from("curl"),
+                enabled: true,
+                ..Default::default()
+            }],
+            ..Default::default()
+        });
+        app.view = View::Rules;
+
+        press(&mut app, KeyCode::Char('e'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::DisableRule);
+        assert_eq!(sent.rules.len(), 1);
+        assert!(!sent.rules[0].enabled);
+        assert!(app.selected_rule().unwrap().enabled);
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        assert!(!app.selected_rule().unwrap().enabled);
+
+        // Anything but `y` backs out of a delete.
+        press(&mut app, KeyCode::Char('x'));
+        assert!(app.confirmation.is_some());
+        press(&mut app, KeyCode::Char('n'));
+        assert!(app.confirmation.is_none());
+        assert!(rx.try_recv().is_err());
+
+        press(&mut app, KeyCode::Char('x'));
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::DeleteRule);
+        assert_eq!(sent.rules[0].name, "curl");
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Error);
+        assert!(app.selected_rule().is_some());
+        assert_eq!(
+            app.last_notification(&[pb::Action::DeleteRule])
+                .unwrap()
+                .notification
+                .id,
+            sent.id
+        );
+    }
+
+    /// Test that interception and firewall switches are confirmed first, and only
+    /// change the node's state once the daemon accepts them.
+    #[tokio::test]
+    async fn test_daemon_control() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig {
+            is_firewall_running: true,
+            system_firewall: Some(pb::SysFirewall::default()),
+            ..Default::default()
+        });
+        app.view = View::Settings;
+        assert_eq!(app.selected().unwrap().firewall_running, Some(true));
+        assert_eq!(app.selected().unwrap().interception, None);
+
+        press(&mut app, KeyCode::Char('f'));
+        assert_eq!(
+            app.confirmation.as_ref().unwrap().action,
+            pb::Action::DisableFirewall
+        );
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::DisableFirewall);
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Error);
+        assert_eq!(app.selected().unwrap().firewall_running, Some(true));
+
+        // Interception isn't reported, so it's switched either way, whatever it's thought to be.
+        press(&mut app, KeyCode::Char('I'));
+        let prompt = &app.confirmation.as_ref().unwrap().prompt;
+        assert!(prompt.starts_with("10.0.0.1: Disable connection interception?"));
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::DisableInterception);
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        assert_eq!(app.selected().unwrap().interception, Some(false));
+
+        press(&mut app, KeyCode::Char('I'));
+        assert_eq!(
+            app.confirmation.as_ref().unwrap().action,
+            pb::Action::DisableInterception
+        );
+        press(&mut app, KeyCode::Char('n'));
+        press(&mut app, KeyCode::Char('i'));
+        assert_eq!(
+            app.confirmation.as_ref().unwrap().prompt,
+            "10.0.0.1: Enable connection interception?"
+        );
+        press(&mut app, KeyCode::Char('n'));
+        assert!(rx.try_recv().is_err());
+
+        press(&mut app, KeyCode::Char('r'));
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::ReloadFwRules);
+        assert_eq!(sent.sys_firewall, Some(pb::SysFirewall::default()));
+        assert_eq!(
+            app.last_notification(&CONTROL_ACTIONS)
+                .unwrap()
+                .notification
+                .id,
+            sent.id
+        );
+
+        app.nodes.get_mut(NODE).unwrap().firewall_running = None;
+        press(&mut app, KeyCode::Char('f'));
+        assert!(app.confirmation.is_none());
+    }
+
+    /// Test that the admin menu sends log levels, and only stops the daemon once the
+    /// challenge is typed out, with each command noted in the alerts and the audit trail,
+    /// and sent to the node the menu was opened on.
+    #[tokio::test]
+    async fn test_admin_menu() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig {
+            log_level: 1,
+            ..Default::default()
+        });
+        let node = String::from(NODE);
+        app.view = View::Settings;
+
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.admin_menu.as_ref().unwrap().log_level.value, "info");
+        press(&mut app, KeyCode::Right);
+        press(&mut app, KeyCode::Enter);
+        assert!(app.admin_menu.is_none());
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            (sent.r#type(), sent.data.as_str()),
+            (pb::Action::LogLevel, "2")
+        );
+        assert_eq!(app.selected().unwrap().log_level, Some(1));
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        assert_eq!(app.selected().unwrap().log_level, Some(2));
+        let audit: Vec<&str> = app.current_alerts.iter().map(|a| a.msg.as_str()).collect();
+        assert_eq!(
+            audit,
+            [
+                "Asked the daemon to set its log level to important (#1)",
+                "Daemon accepted LOG_LEVEL (#1)"
+            ]
+        );
+
+        press(&mut app, KeyCode::Char('a'));
+        // Selecting another node with the menu open doesn't change whom it's for.
+        app.selected_node = Some(String::from("10.0.0.2"));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Enter);
+        let prompt = app.confirmation.as_ref().map(|c| c.prompt.as_str());
+        assert!(prompt.is_some_and(|prompt| prompt.starts_with("10.0.0.1: ")));
+        for c in "stoq".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        // A wrong answer leaves the question open.
+        press(&mut app, KeyCode::Enter);
+        assert!(app.confirmation.is_some());
+        press(&mut app, KeyCode::Backspace);
+        press(&mut app, KeyCode::Char('p'));
+        press(&mut app, KeyCode::Enter);
+        assert!(app.confirmation.is_none());
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::Stop);
+        assert_eq!(app.current_alerts.len(), 3);
+        let audit: Vec<(pb::Action, &str, u64, String)> = app
+            .audit
+            .entries()
+            .map(|e| {
+                (
+                    e.action,
+                    e.data.as_str(),
+                    e.notification,
+                    e.outcome.to_string(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            audit,
+            [
+                (pb::Action::LogLevel, "2", 1, String::from("pending")),
+                (pb::Action::LogLevel, "2", 1, String::from("ok")),
+                (pb::Action::Stop, "", 2, String::from("pending")),
+            ]
+        );
+        assert!(app.audit.entries().all(|e| e.node == node));
+
+        app.confirm_stop_daemon(&node);
+        press(&mut app, KeyCode::Esc);
+        assert!(app.confirmation.is_none());
+        assert!(rx.try_recv().is_err());
+    }
+
+    /// Test that the firewall view lists the subscribed chains, and folds their rules.
+    #[test]
+    fn test_firewall_view() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        let chain = |name: &str, rules: usize| pb::FwChain {
+            name: String::from(name),
+            table: String::from("filter"),
+            family: String::from("inet"),
+            rules: vec![pb::FwRule::default(); rules],
+            ..Default::default()
+        };
+        app.update_subscription(SubscribeEvent {
+            node: String::from("10.0.0.1"),
+            peer: None,
+            client_config: pb::ClientConfig {
+                system_firewall: Some(pb::SysFirewall {
+                    enabled: true,
+                    version: 1,
+                    system_rules: vec![pb::FwChains {
+                        rule: None,
+                        chains: vec![chain("input", 2), chain("output", 1)],
+                    }],
+                }),
+                ..Default::default()
+            },
+            config: None,
+        });
+        app.view = View::Firewall;
+        let input = firewall::ChainIdx { group: 0, chain: 0 };
+        let output = firewall::ChainIdx { group: 0, chain: 1 };
+        assert_eq!(app.firewall_rows().len(), 2);
+
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(app.firewall_rows().len(), 4);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(
+            app.selected_firewall_row(),
+            Some(firewall::Row::Rule(input, 1))
+        );
+        // Folding from a rule leaves its chain selected.
+        press(&mut app, KeyCode::Char(' '));
+        assert_eq!(
+            app.selected_firewall_row(),
+            Some(firewall::Row::Chain(input))
+        );
+
+        press(&mut app, KeyCode::Char('a'));
+        assert_eq!(app.firewall_rows().len(), 5);
+        press(&mut app, KeyCode::Char('a'));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Down);
+        assert_eq!(
+            app.selected_firewall_row(),
+            Some(firewall::Row::Chain(output))
+        );
+    }
+
+    /// Test that the firewall can be shown and exported as an nft ruleset, without
+    /// editing keys doing anything meanwhile, or replacing a file unasked.
+    #[test]
+    fn test_firewall_nft() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        app.view = View::Firewall;
+        press(&mut app, KeyCode::Char('w'));
+        assert!(app.firewall_view.export.is_none());
+        assert!(matches!(app.firewall_view.status, Some(Err(_))));
+
+        app.update_subscription(SubscribeEvent {
+            node: String::from("unix:/local"),
+            peer: None,
+            client_config: pb::ClientConfig {
+                system_firewall: Some(pb::SysFirewall {
+                    enabled: true,
+                    version: 1,
+                    system_rules: vec![pb::FwChains {
+                        rule: None,
+                        chains: vec![pb::FwChain {
+                            name: String::from("input"),
+                            table: String::from("filter"),
+                            family: String::from("inet"),
+                            ..Default::default()
+                        }],
+                    }],
+                }),
+                ..Default::default()
+            },
+            config: None,
+        });
+        press(&mut app, KeyCode::Char('v'));
+        assert!(app.firewall_view.nft);
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('c'));
+        assert_eq!(app.firewall_view.nft_scroll, 1);
+        assert!(app.firewall_view.editor.is_none());
+        press(&mut app, KeyCode::Esc);
+        assert!(!app.firewall_view.nft);
+
+        // The path starts at a file named after the node, and can be changed.
+        press(&mut app, KeyCode::Char('w'));
+        let export = app.firewall_view.export.as_mut().unwrap();
+        assert_eq!(export.path.value, "unix__local-firewall.nft");
+        let path = std::env::temp_dir().join(format!("fw-export-{}.nft", std::process::id()));
+        export.path.value = path.display().to_string();
+        press(&mut app, KeyCode::Enter);
+        assert!(app.firewall_view.export.is_none());
+        assert_eq!(
+            app.firewall_view.status,
+            Some(Ok(format!("Wrote {}", path.display())))
+        );
+        let text = std::fs::read_to_string(&path).expect("read failed");
+        assert_eq!(
+            nft::parse(&text),
+            Ok(app.selected_firewall().cloned().unwrap())
+        );
+
+        // An existing file is only replaced once that's confirmed.
+        std::fs::write(&path, "keep").expect("write failed");
+        press(&mut app, KeyCode::Char('w'));
+        app.firewall_view.export.as_mut().unwrap().path.value = path.display().to_string();
+        press(&mut app, KeyCode::Enter);
+        assert!(app.firewall_view.export.as_ref().unwrap().overwrite);
+        press(&mut app, KeyCode::Char('n'));
+        assert!(!app.firewall_view.export.as_ref().unwrap().overwrite);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "keep");
+        press(&mut app, KeyCode::Enter);
+        press(&mut app, KeyCode::Char('y'));
+        assert!(app.firewall_view.export.is_none());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), text);
+        std::fs::remove_file(&path).expect("remove failed");
+    }
+
+    /// Test that firewall changes are confirmed and sent as the daemon's whole firewall,
+    /// one at a time, and only show once the daemon accepts them.
+    #[tokio::test]
+    async fn test_firewall_changes() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig {
+            system_firewall: Some(make_fake_firewall()),
+            ..Default::default()
+        });
+        app.view = View::Firewall;
+        let rules = |firewall: &pb::SysFirewall| {
+            firewall.system_rules[0].chains[0]
+                .rules
+                .iter()
+                .map(|rule| (rule.uuid.clone(), rule.position, rule.enabled))
+                .collect::<Vec<_>>()
+        };
+
+        press(&mut app, KeyCode::Char(' '));
+        press(&mut app, KeyCode::Down);
+        press(&mut app, KeyCode::Char('e'));
+        assert!(rx.try_recv().is_err());
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::ReloadFwRules);
+        let firewall = sent.sys_firewall.unwrap();
+        assert!(!firewall.system_rules[0].chains[0].rules[0].enabled);
+        assert!(app.selected_fw_rule().unwrap().2.enabled);
+
+        // Nothing else can change until the daemon has loaded that.
+        press(&mut app, KeyCode::Char(']'));
+        assert!(app.confirmation.is_none());
+        assert!(matches!(app.firewall_view.status, Some(Err(_))));
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        assert!(!app.selected_fw_rule().unwrap().2.enabled);
+
+        press(&mut app, KeyCode::Char('['));
+        assert!(app.confirmation.is_none());
+        press(&mut app, KeyCode::Char(']'));
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            rules(sent.sys_firewall.as_ref().unwrap()),
+            vec![(String::from("b"), 0, true), (String::from("a"), 1, false)]
+        );
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+
+        press(&mut app, KeyCode::Char('p'));
+        assert!(app.confirmation.is_some());
+        press(&mut app, KeyCode::Char('y'));
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(
+            sent.sys_firewall.unwrap().system_rules[0].chains[0].policy,
+            "drop"
+        );
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+
+        // A new rule needs something to match before it's sent.
+        press(&mut app, KeyCode::Char('c'));
+        assert!(app.firewall_view.editor.is_some());
+        press(&mut app, KeyCode::Enter);
+        assert!(app.firewall_view.editor.as_ref().unwrap().error.is_some());
+        assert!(rx.try_recv().is_err());
+        let editor = app.firewall_view.editor.as_mut().unwrap();
+        editor.cursor = editor.rows().len() - 1;
+        for c in "443".chars() {
+            press(&mut app, KeyCode::Char(c));
+        }
+        press(&mut app, KeyCode::Enter);
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(rules(sent.sys_firewall.as_ref().unwrap())[2].1, 2);
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        assert!(app.firewall_view.editor.is_none());
+        assert_eq!(rules(app.selected_firewall().unwrap()).len(), 3);
+    }
+
+    /// Helper for a firewall with one base chain holding enabled rules `a` and `b`.
+    fn make_fake_firewall() -> pb::SysFirewall {
+        let rule = |uuid: &str, position| pb::FwRule {
+            uuid: String::from(uuid),
+            enabled: true,
+            position,
+            target: String::from("accept"),
+            ..Default::default()
+        };
+        pb::SysFirewall {
+            enabled: true,
+            version: 1,
+            system_rules: vec![pb::FwChains {
+                rule: None,
+                chains: vec![pb::FwChain {
+                    name: String::from("input"),
+                    table: String::from("filter"),
+                    family: String::from("inet"),
+                    hook: String::from("input"),
+                    policy: String::from("accept"),
+                    rules: vec![rule("a", 0), rule("b", 1)],
+                    ..Default::default()
+                }],
+            }],
+        }
+    }
+
+    /// Test that the rule editor sends `CHANGE_RULE`, and closes once the daemon confirms.
+    #[tokio::test]
+    async fn test_rule_editor() {
+        let (mut app, mut rx) = test_app_with_node(pb::ClientConfig::default());
+        app.view = View::Rules;
+        let type_text = |app: &mut App, text: &str| {
+            for c in text.chars() {
+                press(app, KeyCode::Char(c));
+            }
+        };
+
+        press(&mut app, KeyCode::Char('c'));
+        assert!(app.rules_view.editor.is_some());
+        // Typing goes to the editor, not the view's or the app's own keys.
+        type_text(&mut app, "nc");
+        assert_eq!(app.view, View::Rules);
+        press(&mut app, KeyCode::Enter);
+        assert!(app.rules_view.editor.as_ref().unwrap().error.is_some());
+        assert!(rx.try_recv().is_err());
+
+        let data_row = app.rules_view.editor.as_ref().unwrap().rows().len() - 2;
+        app.rules_view.editor.as_mut().unwrap().cursor = data_row;
+        type_text(&mut app, "/usr/bin/nc");
+        press(&mut app, KeyCode::Enter);
+        let sent = rx.recv().await.unwrap().unwrap();
+        assert_eq!(sent.r#type(), pb::Action::ChangeRule);
+        assert_eq!(sent.rules[0].name, "nc");
+        assert_eq!(sent.rules[0].operator.as_ref().unwrap().data, "/usr/bin/nc");
+        assert!(app.rules_view.editor.is_some());
+
+        reply(&mut app, sent.id, pb::NotificationReplyCode::Ok);
+        assert!(app.rules_view.editor.is_none());
+        assert_eq!(app.selected_rule().unwrap().name, "nc");
+
+        // Editing an existing rule starts from it, and esc backs out.
+        press(&mut app, KeyCode::Enter);
+        assert_eq!(
+            app.rules_view.editor.as_ref().unwrap().editing.as_deref(),
+            Some("nc")
+        );
+        press(&mut app, KeyCode::Esc);
+        assert!(app.rules_view.editor.is_none());
+    }
+
+    /// Test that prompts still pending at exit get the exit action, once.
+    #[tokio::test]
+    async fn test_shutdown() {
+        let mut app = App::new(&AppOptions {
+            exit_action: Some(String::from("allow")),
+            ..options("127.0.0.1:65534")
+        })
+        .expect("new failed");
+        assert!(
+            App::new(&AppOptions {
+                exit_action: Some(String::from("bogus")),
+                ..options("127.0.0.1:65534")
+            })
+            .is_err()
+        );
+
+        let mut receivers = ask_rules(&mut app, &[(1, "10.0.0.1"), (2, "10.0.0.2")]);
+        // A call the app hasn't heard about yet.
+        let (unseen_tx, unseen_rx) = tokio::sync::oneshot::channel();
+        app.pending_rules.lock().unwrap().insert(3, unseen_tx);
+
+        app.shutdown().await;
+        for id in [1, 2] {
+            let rule = receivers.remove(&id).unwrap().await.expect("no rule sent");
+            assert_eq!(rule.action, "allow");
+            assert_eq!(rule.duration, "once");
+        }
+        assert!(unseen_rx.await.is_err());
+        assert!(app.pending_rules.lock().unwrap().is_empty());
+        assert!(app.current_connection().is_none());
+    }
+
+    /// Test that server failures raise alerts, but a requested stop doesn't.
+    #[tokio::test]
+    async fn test_server_state() {
+        let mut app = App::new(&options("127.0.0.1:65534")).expect("new failed");
+        app.update_server_state(ServerState::Listening);
+        assert!(app.current_alerts.is_empty());
+
+        app.update_server_state(ServerState::Failed(String::from(
+            "gRPC server failed: boom",
+        )));
+        assert_eq!(
+            app.server_state,
+            ServerState::Failed(String::from("gRPC server failed: boom"))
+        );
+        assert_eq!(app.current_alerts.len(), 1);
+
+        app.quit();
+        app.update_server_state(ServerState::Stopped);
+        assert_eq!(app.current_alerts.len(), 1);
     }
 }
@@ -0,0 +1,63 @@
+//! Audit trail of the commands sent to daemons to change how they run: each is noted
+//! when it goes out, and again when the daemon answers it or it's given up on.
+
+use std::collections::VecDeque;
+use std::time::SystemTime;
+
+use crate::node::NodeId;
+use crate::notification::{Outcome, Record};
+use crate::opensnitch_proto::pb;
+
+/// Max number of entries kept in memory. They're kept in the store, if there's one.
+pub const AUDIT_CAPACITY: usize = 256;
+
+/// A command sent to a daemon, as it stood at one point.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    pub node: NodeId,
+    /// When it was sent, or settled.
+    pub at: SystemTime,
+    pub action: pb::Action,
+    pub data: String,
+    /// ID of the notification that carried it.
+    pub notification: u64,
+    pub outcome: Outcome,
+}
+
+impl Entry {
+    /// An entry for where a sent notification stands, as of its settling, or its
+    /// sending if it's still pending.
+    #[must_use]
+    pub fn new(record: &Record) -> Self {
+        Self {
+            node: record.node.clone(),
+            at: record.settled_at.unwrap_or(record.sent_at),
+            action: record.action(),
+            data: record.notification.data.clone(),
+            notification: record.notification.id,
+            outcome: record.outcome.clone(),
+        }
+    }
+}
+
+/// Recent entries, oldest first.
+#[derive(Debug, Default)]
+pub struct AuditLog {
+    entries: VecDeque<Entry>,
+}
+
+impl AuditLog {
+    /// Adds an entry, dropping the oldest once full.
+    pub fn push(&mut self, entry: Entry) {
+        if self.entries.len() >= AUDIT_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    /// Every entry, oldest first.
+    #[must_use]
+    pub fn entries(&self) -> impl DoubleEndedIterator<Item = &Entry> {
+        self.entries.iter()
+    }
+}
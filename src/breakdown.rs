@@ -0,0 +1,313 @@
+//! Top-N breakdowns of the hit counts daemons send with their stats, one per dimension
+//! (`by_proto`, `by_address`, ...). Uids and ports are named from this host's
+//! `/etc/passwd` and `/etc/services`, where they're listed, but only for daemons on this
+//! host: elsewhere the same uid or port may well stand for something else.
+
+use std::collections::HashMap;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+use crate::opensnitch_proto::pb;
+use crate::rates::Window;
+
+/// Max number of entries shown for a dimension.
+pub const TOP_N: usize = 50;
+
+/// What a breakdown counts connections by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Dimension {
+    Host,
+    Address,
+    Port,
+    Proto,
+    Executable,
+    Uid,
+}
+
+impl Dimension {
+    /// Every dimension, in tab order.
+    pub const ALL: [Dimension; 6] = [
+        Dimension::Host,
+        Dimension::Address,
+        Dimension::Port,
+        Dimension::Proto,
+        Dimension::Executable,
+        Dimension::Uid,
+    ];
+
+    /// Tab label.
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            Dimension::Proto => "Protocol",
+            Dimension::Address => "Address",
+            Dimension::Host => "Host",
+            Dimension::Port => "Port",
+            Dimension::Uid => "User",
+            Dimension::Executable => "Executable",
+        }
+    }
+
+    /// The next dimension in tab order, or the previous one, wrapping around.
+    #[must_use]
+    pub fn step(self, forward: bool) -> Dimension {
+        let len = Dimension::ALL.len();
+        let idx = Dimension::ALL
+            .iter()
+            .position(|dimension| *dimension == self)
+            .unwrap_or(0);
+        let idx = if forward { idx + 1 } else { idx + len - 1 };
+        Dimension::ALL[idx % len]
+    }
+
+    fn counts(self, stats: &pb::Statistics) -> &HashMap<String, u64> {
+        match self {
+            Dimension::Proto => &stats.by_proto,
+            Dimension::Address => &stats.by_address,
+            Dimension::Host => &stats.by_host,
+            Dimension::Port => &stats.by_port,
+            Dimension::Uid => &stats.by_uid,
+            Dimension::Executable => &stats.by_executable,
+        }
+    }
+}
+
+/// Local names for uids and ports.
+#[derive(Clone, Debug, Default)]
+pub struct Names {
+    users: HashMap<u32, String>,
+    services: HashMap<u16, String>,
+}
+
+impl Names {
+    /// Reads `/etc/passwd` and `/etc/services`. A file that can't be read names nothing.
+    #[must_use]
+    pub fn load() -> Self {
+        let read = |path| std::fs::read_to_string(path).unwrap_or_default();
+        Self::parse(&read("/etc/passwd"), &read("/etc/services"))
+    }
+
+    /// Reads names from the contents of a passwd and a services file. Ports listed for
+    /// both TCP and UDP under different names go by their TCP name.
+    #[must_use]
+    pub fn parse(passwd: &str, services: &str) -> Self {
+        let users = passwd
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split(':');
+                let name = fields.next()?;
+                let uid = fields.nth(1)?.parse().ok()?;
+                Some((uid, String::from(name)))
+            })
+            .collect();
+
+        // e.g. "https  443/tcp  # http protocol over TLS/SSL"
+        let entries: Vec<(u16, &str, &str)> = services
+            .lines()
+            .filter_map(|line| {
+                let line = line.split('#').next()?;
+                let mut fields = line.split_whitespace();
+                let name = fields.next()?;
+                let (port, proto) = fields.next()?.split_once('/')?;
+                Some((port.parse().ok()?, proto, name))
+            })
+            .collect();
+        let mut services = HashMap::new();
+        for tcp in [true, false] {
+            for (port, proto, name) in &entries {
+                if (*proto == "tcp") == tcp {
+                    services.entry(*port).or_insert_with(|| String::from(*name));
+                }
+            }
+        }
+        Self { users, services }
+    }
+
+    /// Local name for an entry of a dimension, if it has one.
+    #[must_use]
+    pub fn name(&self, dimension: Dimension, key: &str) -> Option<&str> {
+        match dimension {
+            Dimension::Uid => self.users.get(&key.parse().ok()?),
+            Dimension::Port => self.services.get(&key.parse().ok()?),
+            _ => None,
+        }
+        .map(String::as_str)
+    }
+}
+
+/// One entry of a breakdown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Entry {
+    /// The entry as the daemon reports it, e.g. a uid.
+    pub key: String,
+    /// Its local name, e.g. a username.
+    pub name: Option<String>,
+    pub hits: u64,
+    /// Share of all hits in the dimension, in tenths of a percent.
+    pub per_mille: u64,
+}
+
+impl Entry {
+    /// The key, followed by its name if it has one, e.g. `443 (https)`.
+    #[must_use]
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("{} ({name})", self.key),
+            None => self.key.clone(),
+        }
+    }
+}
+
+/// A dimension's counts, most hits first.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Breakdown {
+    /// The top `TOP_N` entries.
+    pub entries: Vec<Entry>,
+    /// How many entries there are in all.
+    pub distinct: usize,
+    /// Hits across all entries.
+    pub total: u64,
+}
+
+impl Breakdown {
+    /// Breaks down a dimension of `stats`, naming entries from `names`, if given.
+    #[must_use]
+    pub fn new(stats: &pb::Statistics, dimension: Dimension, names: Option<&Names>) -> Self {
+        let counts = dimension.counts(stats);
+        let total = counts
+            .values()
+            .fold(0, |sum: u64, hits| sum.saturating_add(*hits));
+        let mut top: Vec<(&String, u64)> = counts.iter().map(|(key, hits)| (key, *hits)).collect();
+        top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(b.0)));
+        let entries = top
+            .into_iter()
+            .take(TOP_N)
+            .map(|(key, hits)| Entry {
+                key: key.clone(),
+                name: names
+                    .and_then(|names| names.name(dimension, key))
+                    .map(String::from),
+                hits,
+                per_mille: u64::try_from(u128::from(hits) * 1000 / u128::from(total.max(1)))
+                    .unwrap_or(1000),
+            })
+            .collect();
+        Self {
+            entries,
+            distinct: counts.len(),
+            total,
+        }
+    }
+}
+
+/// State of the stats view: its breakdown table and rate charts.
+#[derive(Clone, Debug)]
+pub struct StatsView {
+    /// Dimension the table breaks hits down by.
+    pub dimension: Dimension,
+    /// Index of the selected entry in the table.
+    pub cursor: usize,
+    /// How far back the charts go.
+    pub window: Window,
+}
+
+impl Default for StatsView {
+    fn default() -> Self {
+        Self {
+            dimension: Dimension::Host,
+            cursor: 0,
+            window: Window::FiveMinutes,
+        }
+    }
+}
+
+impl StatsView {
+    /// Handle a key press: left/right to switch dimension, up/down to move through the
+    /// breakdown's `entries`, `w` to switch the charts' window. Returns whether the key
+    /// was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent, entries: usize) -> bool {
+        match key_event.code {
+            KeyCode::Left | KeyCode::Right => {
+                self.dimension = self.dimension.step(key_event.code == KeyCode::Right);
+                self.cursor = 0;
+            }
+            KeyCode::Char('w') => self.window = self.window.next(),
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(entries.saturating_sub(1)),
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "\
+root:x:0:0:root:/root:/bin/bash
+alice:x:1000:1000:Alice,,,:/home/alice:/bin/bash
+broken line
+";
+
+    const SERVICES: &str = "\
+# Network services, Internet style
+domain\t\t53/tcp\t\t\t\t# Domain Name Server
+domain\t\t53/udp
+https\t\t443/tcp\t\t\t\t# http protocol over TLS/SSL
+bootpc\t\t68/udp
+biff\t\t512/udp\t\tcomsat
+exec\t\t512/tcp
+";
+
+    /// Test that uids and ports are named, preferring a port's TCP name.
+    #[test]
+    fn test_names() {
+        let names = Names::parse(PASSWD, SERVICES);
+        assert_eq!(names.name(Dimension::Uid, "1000"), Some("alice"));
+        assert_eq!(names.name(Dimension::Uid, "0"), Some("root"));
+        assert_eq!(names.name(Dimension::Uid, "1001"), None);
+        assert_eq!(names.name(Dimension::Port, "443"), Some("https"));
+        assert_eq!(names.name(Dimension::Port, "68"), Some("bootpc"));
+        assert_eq!(names.name(Dimension::Port, "512"), Some("exec"));
+        assert_eq!(names.name(Dimension::Host, "443"), None);
+        assert_eq!(Names::parse("", "").name(Dimension::Port, "443"), None);
+    }
+
+    /// Test that entries are ranked by hits, with their share and names, and cut at `TOP_N`.
+    #[test]
+    fn test_breakdown() {
+        let names = Names::parse(PASSWD, SERVICES);
+        let stats = pb::Statistics {
+            by_port: HashMap::from([
+                (String::from("443"), 6),
+                (String::from("53"), 3),
+                (String::from("8080"), 1),
+            ]),
+            by_host: (0..60)
+                .map(|i| (format!("{i:02}.example.com"), 1))
+                .collect(),
+            ..Default::default()
+        };
+        let breakdown = Breakdown::new(&stats, Dimension::Port, Some(&names));
+        assert_eq!((breakdown.distinct, breakdown.total), (3, 10));
+        let labels: Vec<String> = breakdown.entries.iter().map(Entry::label).collect();
+        assert_eq!(labels, ["443 (https)", "53 (domain)", "8080"]);
+        let unnamed = Breakdown::new(&stats, Dimension::Port, None);
+        assert!(unnamed.entries.iter().all(|entry| entry.name.is_none()));
+        let shares: Vec<u64> = breakdown.entries.iter().map(|e| e.per_mille).collect();
+        assert_eq!(shares, [600, 300, 100]);
+
+        let breakdown = Breakdown::new(&stats, Dimension::Host, Some(&names));
+        assert_eq!(breakdown.distinct, 60);
+        assert_eq!(breakdown.entries.len(), TOP_N);
+        assert_eq!(breakdown.entries[0].key, "00.example.com");
+
+        assert_eq!(
+            Breakdown::new(&stats, Dimension::Uid, Some(&names)),
+            Breakdown::default()
+        );
+        assert_eq!(Dimension::Host.step(false), Dimension::Uid);
+        assert_eq!(Dimension::Uid.step(true), Dimension::Host);
+    }
+}
@@ -1,4 +1,4 @@
-use clap::{Arg, Command};
+use clap::{Arg, ArgAction, Command};
 
 #[must_use]
 pub fn setup() -> Command {
@@ -8,29 +8,130 @@ pub fn setup() -> Command {
     .about("A Terminal UI control plane for OpenSnitch.")
     .arg(
         Arg::new("ip_port")
+        .global(true)
         .long("bind")
         .default_value("127.0.0.1:50051")
-        .help("IP address and port for OpenSnitch gRPC server to bind to. Format: \"A.B.C.D:port\" or \"[A:B:C::D]:port\".")
+        .help("Address for OpenSnitch gRPC server to bind to. Format: \"A.B.C.D:port\", \"[A:B:C::D]:port\" or \"unix:///path/to/socket\".")
+    )
+    .arg(
+        Arg::new("tls_cert")
+        .global(true)
+        .long("tls-cert")
+        .value_name("PEM")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .requires("tls_key")
+        .help("Server certificate (chain) for terminating TLS on the gRPC server. Daemons need a matching \"CACert\" under Server.Authentication.TLSOptions.")
+    )
+    .arg(
+        Arg::new("tls_key")
+        .global(true)
+        .long("tls-key")
+        .value_name("PEM")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .requires("tls_cert")
+        .help("Private key for --tls-cert.")
+    )
+    .arg(
+        Arg::new("tls_client_ca")
+        .global(true)
+        .long("tls-client-ca")
+        .value_name("PEM")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .requires("tls_cert")
+        .help("CA certificate used to verify daemon client certificates (mutual TLS, daemon Authentication type \"tls-mutual\").")
+    )
+    .arg(
+        Arg::new("tls_client_auth_optional")
+        .global(true)
+        .long("tls-client-auth-optional")
+        .action(ArgAction::SetTrue)
+        .requires("tls_client_ca")
+        .help("Also accept daemons that present no client certificate (daemon ClientAuthType \"verify-cert\"). Certificates that are presented must still verify.")
+    )
+    .arg(
+        Arg::new("allow_insecure_bind")
+        .global(true)
+        .long("allow-insecure-bind")
+        .action(ArgAction::SetTrue)
+        .help("Allow binding to a non-loopback address without TLS.")
     )
     .arg(
         Arg::new("dispo_seconds")
+        .global(true)
         .long("conn-dispo-timeout")
         .default_value("30")
         .value_parser(clap::value_parser!(u64).range(1..115))
         .help("Duration in seconds that the TUI will wait on a disposition (allow/deny) for a connection attempt. Upon timeout, daemon will perform default action. Max: 115.")
     )
+    .arg(
+        Arg::new("stale_seconds")
+        .global(true)
+        .long("stale-threshold")
+        .default_value("10")
+        .value_parser(clap::value_parser!(u64).range(1..))
+        .help("Duration in seconds without a ping from a daemon before it's flagged as stale. Three times this flags it as disconnected.")
+    )
     .arg(
         Arg::new("default_action")
+        .global(true)
         .long("default-action")
         .default_value("deny")
         .help("Default action (allow/deny/reject) to be conveyed to daemons when the TUI fails to disposition a connection attempt in time.")
     )
+    .arg(
+        Arg::new("exit_action")
+        .global(true)
+        .long("exit-action")
+        .value_parser(["allow", "deny", "reject"])
+        .help("Action (allow/deny/reject) applied once to connection attempts still awaiting disposition when the TUI exits. Defaults to --default-action.")
+    )
     .arg(
         Arg::new("temp_rule_lifetime")
+        .global(true)
         .long("temp-rule-lifetime")
         .value_parser(["until restart", "always", "once", "12h", "1h", "30m", "15m", "5m", "30s",]) // TODO: Single source of truth from constants.rs?
         .default_value("12h")
         .help("Lifetime of temporary rules created by TUI.")
     )
+    .args(store_args())
+    .subcommand(serve_command())
     .max_term_width(100)
 }
+
+/// Where the on-disk store is kept, if anywhere.
+fn store_args() -> [Arg; 2] {
+    [
+        Arg::new("data_dir")
+        .global(true)
+        .long("data-dir")
+        .value_name("DIR")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .help("Directory for the on-disk store of events, alerts, decisions and stats, kept across restarts. Defaults to $XDG_DATA_HOME/opensnitch-tui (~/.local/share/opensnitch-tui)."),
+        Arg::new("no_store")
+        .global(true)
+        .long("no-store")
+        .action(ArgAction::SetTrue)
+        .conflicts_with("data_dir")
+        .help("Don't keep an on-disk store. Nothing is kept once the TUI exits."),
+    ]
+}
+
+/// The `serve` subcommand, for running without the terminal UI.
+fn serve_command() -> Command {
+    Command::new("serve")
+    .about("Run the gRPC server. With --headless, run without a terminal UI, answering connection prompts from a policy and logging to stdout as JSON lines.")
+    .arg(
+        Arg::new("headless")
+        .long("headless")
+        .action(ArgAction::SetTrue)
+        .help("Don't start the terminal UI.")
+    )
+    .arg(
+        Arg::new("policy")
+        .long("policy")
+        .value_name("JSON")
+        .value_parser(clap::value_parser!(std::path::PathBuf))
+        .requires("headless")
+        .help("Policy file deciding connection prompts in headless mode. Without one, every prompt gets the default action, once.")
+    )
+}
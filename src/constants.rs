@@ -30,9 +30,35 @@ pub enum Operand {
 }
 
 impl Operand {
+    /// Every operand, in declaration order.
+    pub const ALL: [Operand; 22] = [
+        Operand::ProcessId,
+        Operand::ProcessPath,
+        Operand::ProcessCmd,
+        Operand::ProcessEnv,
+        Operand::ProcessHashMd5,
+        Operand::ProcessHashSha1,
+        Operand::UserId,
+        Operand::IfaceOut,
+        Operand::IfaceIn,
+        Operand::SrcIp,
+        Operand::SrcPort,
+        Operand::DstIp,
+        Operand::DstHost,
+        Operand::DstPort,
+        Operand::DstNetwork,
+        Operand::SrcNetwork,
+        Operand::Protocol,
+        Operand::List,
+        Operand::ListDomains,
+        Operand::ListDomainsRegexp,
+        Operand::ListIps,
+        Operand::ListNets,
+    ];
+
     /// Enum as string for `OpenSnitch` daemon.
     #[must_use]
-    pub fn get_str(&self) -> &str {
+    pub fn get_str(&self) -> &'static str {
         match self {
             Operand::ProcessId => "process.id",
             Operand::ProcessPath => "process.path",
@@ -71,9 +97,18 @@ pub enum RuleType {
 }
 
 impl RuleType {
+    /// Every rule type, in declaration order.
+    pub const ALL: [RuleType; 5] = [
+        RuleType::List,
+        RuleType::Lists,
+        RuleType::Simple,
+        RuleType::Regexp,
+        RuleType::Network,
+    ];
+
     /// Enum as string for `OpenSnitch` daemon.
     #[must_use]
-    pub fn get_str(&self) -> &str {
+    pub fn get_str(&self) -> &'static str {
         match self {
             RuleType::List => "list",
             RuleType::Lists => "lists",
@@ -135,7 +170,7 @@ impl Action {
 
     /// Enum as string for `OpenSnitch` daemon.
     #[must_use]
-    pub fn get_str(&self) -> &str {
+    pub fn get_str(&self) -> &'static str {
         match self {
             Action::Allow => "allow",
             Action::Deny => "deny",
@@ -173,6 +208,19 @@ pub enum Duration {
 }
 
 impl Duration {
+    /// Every duration, from shortest to longest.
+    pub const ALL: [Duration; 9] = [
+        Duration::Once,
+        Duration::Seconds30,
+        Duration::Minutes5,
+        Duration::Minutes15,
+        Duration::Minutes30,
+        Duration::Hours1,
+        Duration::Hours12,
+        Duration::UntilRestart,
+        Duration::Always,
+    ];
+
     /// Validates input duration and returns enum variant.
     /// # Errors
     /// Returns error if invalid enum variant.
@@ -195,7 +243,7 @@ impl Duration {
 
     /// Enum as string for `OpenSnitch` daemon.
     #[must_use]
-    pub fn get_str(&self) -> &str {
+    pub fn get_str(&self) -> &'static str {
         match self {
             Duration::UntilRestart => "until restart",
             Duration::Always => "always",
@@ -219,6 +267,13 @@ pub enum DefaultAction {
 }
 
 impl DefaultAction {
+    /// Every default action.
+    pub const ALL: [DefaultAction; 3] = [
+        DefaultAction::Allow,
+        DefaultAction::Deny,
+        DefaultAction::Reject,
+    ];
+
     /// Validates input action and returns enum variant.
     /// # Errors
     /// Returns error if invalid enum variant.
@@ -235,13 +290,23 @@ impl DefaultAction {
 
     /// Enum as string for `OpenSnitch` daemon.
     #[must_use]
-    pub fn get_str(&self) -> &str {
+    pub fn get_str(&self) -> &'static str {
         match self {
             DefaultAction::Allow => "allow",
             DefaultAction::Deny => "deny",
             DefaultAction::Reject => "reject",
         }
     }
+
+    /// The rule action with the same effect.
+    #[must_use]
+    pub fn as_action(&self) -> Action {
+        match self {
+            DefaultAction::Allow => Action::Allow,
+            DefaultAction::Deny => Action::Deny,
+            DefaultAction::Reject => Action::Reject,
+        }
+    }
 }
 
 /// Error type for bad option provided to enum constructor.
@@ -1,9 +1,13 @@
 use crate::alert::Alert;
-use crate::opensnitch_proto::pb::{Connection, Statistics};
+use crate::node::{NodeId, StreamState};
+use crate::opensnitch_json::OpenSnitchDaemonConfig;
+use crate::opensnitch_proto::pb::{ClientConfig, Connection, NotificationReply, Statistics};
+use crate::server::ServerState;
 use color_eyre::eyre::OptionExt;
-use futures::{FutureExt, StreamExt};
+use futures::StreamExt;
 use ratatui::crossterm::event::Event as CrosstermEvent;
 use std::time::{Duration, SystemTime};
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::sync::mpsc;
 
 /// The frequency at which tick events are emitted.
@@ -29,18 +33,89 @@ pub enum AppEvent {
     Alert(Alert),
     /// Daemon trapped a new connection that requires action.
     AskRule(ConnectionEvent),
-    /// Test-only: trigger a notification that does nothing.
-    TestNotify,
+    /// A daemon's `AskRule` call ended without an answer from the app.
+    AskRuleClosed { node: NodeId, id: u64 },
+    /// Daemon subscribed to this server and shared its configuration.
+    Subscribe(Box<SubscribeEvent>),
+    /// A daemon's notifications stream opened or closed.
+    NotificationStream { node: NodeId, state: StreamState },
+    /// Daemon replied to a notification over its notifications stream.
+    NotificationReply {
+        node: NodeId,
+        reply: NotificationReply,
+    },
+    /// The gRPC server started, stopped or failed.
+    ServerState(ServerState),
     /// Quit the application.
     Quit,
+    /// Some task panicked, quit the application with an error.
+    Panicked(String),
+}
+
+/// Description of the daemon on the other end of a gRPC request.
+/// A daemon makes all its calls over one connection, so a peer also tells apart daemons
+/// sharing an address, until it reconnects.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Peer {
+    /// Daemon connected over TCP.
+    Tcp(std::net::SocketAddr),
+    /// Daemon connected over a Unix domain socket.
+    /// Credentials are those reported by the kernel for the connecting process, if any.
+    Unix { pid: Option<i32>, uid: Option<u32> },
+}
+
+impl std::fmt::Display for Peer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Peer::Tcp(addr) => write!(f, "{addr}"),
+            Peer::Unix { pid: Some(pid), .. } => write!(f, "unix, pid {pid}"),
+            Peer::Unix { pid: None, .. } => write!(f, "unix"),
+        }
+    }
+}
+
+impl Peer {
+    /// Key for the node this peer belongs to, before it has subscribed.
+    /// TCP peers are keyed by IP alone, since the source port changes on every reconnect.
+    /// All Unix socket peers are necessarily local, so they share one key.
+    #[must_use]
+    pub fn node_id(&self) -> NodeId {
+        match self {
+            Peer::Tcp(addr) => addr.ip().to_string(),
+            Peer::Unix { .. } => String::from("unix:local"),
+        }
+    }
+
+    /// Whether the daemon runs on this host: over a Unix socket or from a loopback address.
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        match self {
+            Peer::Tcp(addr) => addr.ip().to_canonical().is_loopback(),
+            Peer::Unix { .. } => true,
+        }
+    }
+
+    /// Key for the node this peer belongs to, once it has subscribed under `name`
+    /// (`ClientConfig.name`), e.g. `laptop@10.0.0.1`. Daemons behind one address are told
+    /// apart by name, and keep the address so same-named hosts elsewhere don't merge.
+    #[must_use]
+    pub fn named_node_id(&self, name: &str) -> NodeId {
+        if name.is_empty() {
+            self.node_id()
+        } else {
+            format!("{name}@{}", self.node_id())
+        }
+    }
 }
 
 /// Wrapper for pings with extra metadata.
 #[derive(Clone, Debug)]
 pub struct PingEvent {
-    /// The peer addr+port that sent ping.
-    /// May be none if a domain socket was to be supported in the future.
-    pub peer: Option<std::net::SocketAddr>,
+    /// The node that sent ping.
+    pub node: NodeId,
+    /// The peer that sent ping.
+    /// May be none if the transport couldn't tell us who it was.
+    pub peer: Option<Peer>,
     /// Ping payload - stats.
     pub stats: Statistics,
 }
@@ -48,12 +123,30 @@ pub struct PingEvent {
 /// Wrapper for connections with extra metadata.
 #[derive(Clone, Debug)]
 pub struct ConnectionEvent {
+    /// ID of the `AskRule` request to route the answer to.
+    pub id: u64,
+    /// The node that trapped this connection.
+    pub node: NodeId,
     /// The connection that created this event.
     pub connection: Connection,
     /// Expiry timestamp at which point some default action is taken.
     pub expiry_ts: SystemTime,
 }
 
+/// Wrapper for subscriptions with extra metadata.
+#[derive(Clone, Debug)]
+pub struct SubscribeEvent {
+    /// The node that subscribed.
+    pub node: NodeId,
+    /// The peer that subscribed.
+    pub peer: Option<Peer>,
+    /// Subscribe payload - daemon config and state.
+    pub client_config: ClientConfig,
+    /// The daemon's config JSON from `client_config`, parsed.
+    /// None if the daemon sent something we couldn't parse.
+    pub config: Option<OpenSnitchDaemonConfig>,
+}
+
 /// Terminal event handler.
 #[derive(Debug)]
 pub struct EventHandler {
@@ -70,15 +163,21 @@ impl Default for EventHandler {
 }
 
 impl EventHandler {
-    /// Constructs a new instance of [`EventHandler`] and spawns a new thread to handle events.
+    /// Constructs a new instance of [`EventHandler`].
+    /// Terminal and tick events only flow once [`Self::start`] is called.
     #[must_use]
     pub fn new() -> Self {
         let (sender, receiver) = mpsc::unbounded_channel();
-        let actor = EventTask::new(sender.clone());
-        tokio::spawn(async { actor.run().await });
         Self { sender, receiver }
     }
 
+    /// Spawns a new task to emit ticks, and read terminal events if `terminal_input` is set.
+    /// Only read the terminal once it has been set up.
+    pub fn start(&self, terminal_input: bool) {
+        let actor = EventTask::new(self.sender.clone(), terminal_input);
+        tokio::spawn(async { actor.run().await });
+    }
+
     /// Receives an event from the sender.
     ///
     /// This function blocks until an event is received.
@@ -110,12 +209,17 @@ impl EventHandler {
 struct EventTask {
     /// Event sender channel.
     sender: mpsc::UnboundedSender<Event>,
+    /// Whether to read events from the terminal.
+    terminal_input: bool,
 }
 
 impl EventTask {
     /// Constructs a new instance of [`EventThread`].
-    fn new(sender: mpsc::UnboundedSender<Event>) -> Self {
-        Self { sender }
+    fn new(sender: mpsc::UnboundedSender<Event>, terminal_input: bool) -> Self {
+        Self {
+            sender,
+            terminal_input,
+        }
     }
 
     /// Runs the event thread.
@@ -123,15 +227,30 @@ impl EventTask {
     /// This function emits tick events at a fixed rate and polls for crossterm events in between.
     async fn run(self) -> color_eyre::Result<()> {
         let tick_rate = Duration::from_secs_f64(1.0 / TICK_FPS);
-        let mut reader = crossterm::event::EventStream::new();
+        let mut reader = self.terminal_input.then(crossterm::event::EventStream::new);
         let mut tick = tokio::time::interval(tick_rate);
+        // Being stopped by a service manager should wind down as cleanly as ctrl+C does.
+        // In raw mode ctrl+C arrives as a key press instead, but not without a terminal.
+        let mut sigterm = signal(SignalKind::terminate())?;
+        let mut sigint = signal(SignalKind::interrupt())?;
         loop {
             let tick_delay = tick.tick();
-            let crossterm_event = reader.next().fuse();
+            let crossterm_event = async {
+                match reader.as_mut() {
+                    Some(reader) => reader.next().await,
+                    None => std::future::pending().await,
+                }
+            };
             tokio::select! {
               () = self.sender.closed() => {
                 break;
               }
+              _ = sigterm.recv() => {
+                self.send(Event::App(Box::new(AppEvent::Quit)));
+              }
+              _ = sigint.recv() => {
+                self.send(Event::App(Box::new(AppEvent::Quit)));
+              }
               _ = tick_delay => {
                 self.send(Event::Tick);
               }
@@ -0,0 +1,652 @@
+//! The daemon's system firewall (`SysFirewall`) as shown in the firewall view:
+//! which rows are listed, and how chains and rules are described.
+
+use std::collections::HashSet;
+use std::hash::BuildHasher;
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+use crate::firewall_editor::FwRuleEditor;
+use crate::form::Field;
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+
+/// Policies a base chain can fall back to.
+pub const POLICIES: [&str; 2] = ["accept", "drop"];
+
+/// What a rule can do with a match.
+pub const TARGETS: [&str; 8] = [
+    "accept", "drop", "reject", "return", "jump", "goto", "queue", "log",
+];
+
+/// Comparisons a statement can make. An empty one means `==`.
+pub const COMPARISONS: [&str; 6] = ["==", "!=", "<", ">", "<=", ">="];
+
+/// Statements the expression builder offers, with the keys each one takes.
+/// Interface statements have no keys: the interface name takes the key's place.
+pub const STATEMENTS: [(&str, &[&str]); 8] = [
+    ("tcp", &["dport", "sport"]),
+    ("udp", &["dport", "sport"]),
+    ("ip", &["saddr", "daddr"]),
+    ("ip6", &["saddr", "daddr"]),
+    ("ct", &["state", "mark"]),
+    ("meta", &["l4proto", "mark", "skuid", "skgid", "nfproto"]),
+    ("iifname", &[]),
+    ("oifname", &[]),
+];
+
+/// Connection tracking states `ct state` can match.
+pub const CT_STATES: [&str; 5] = ["new", "established", "related", "invalid", "untracked"];
+
+/// Where a chain sits in a `SysFirewall`: which `FwChains` group, and which chain in it.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChainIdx {
+    pub group: usize,
+    pub chain: usize,
+}
+
+impl ChainIdx {
+    #[must_use]
+    pub fn get(self, firewall: &pb::SysFirewall) -> Option<&pb::FwChain> {
+        firewall
+            .system_rules
+            .get(self.group)
+            .and_then(|group| group.chains.get(self.chain))
+    }
+
+    #[must_use]
+    pub fn get_mut(self, firewall: &mut pb::SysFirewall) -> Option<&mut pb::FwChain> {
+        firewall
+            .system_rules
+            .get_mut(self.group)
+            .and_then(|group| group.chains.get_mut(self.chain))
+    }
+}
+
+/// One line of the firewall view.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Row {
+    Chain(ChainIdx),
+    /// A rule, by index into its chain's rules.
+    Rule(ChainIdx, usize),
+}
+
+impl Row {
+    /// The chain this row is, or belongs to.
+    #[must_use]
+    pub fn chain(self) -> ChainIdx {
+        match self {
+            Row::Chain(chain) | Row::Rule(chain, _) => chain,
+        }
+    }
+}
+
+/// Key a chain is remembered by across updates, e.g. `inet mangle output`.
+#[must_use]
+pub fn chain_key(chain: &pb::FwChain) -> String {
+    format!("{} {} {}", chain.family, chain.table, chain.name)
+}
+
+/// Every chain, in the order the daemon sent them.
+/// The deprecated iptables rule held by each group alongside its chains isn't listed.
+#[must_use]
+pub fn chains(firewall: &pb::SysFirewall) -> Vec<(ChainIdx, &pb::FwChain)> {
+    firewall
+        .system_rules
+        .iter()
+        .enumerate()
+        .flat_map(|(group, chains)| {
+            chains
+                .chains
+                .iter()
+                .enumerate()
+                .map(move |(chain, fw_chain)| (ChainIdx { group, chain }, fw_chain))
+        })
+        .collect()
+}
+
+/// Rows of the firewall view: each chain, followed by its rules if it's expanded.
+#[must_use]
+pub fn rows<S: BuildHasher>(firewall: &pb::SysFirewall, expanded: &HashSet<String, S>) -> Vec<Row> {
+    let mut rows = Vec::new();
+    for (idx, chain) in chains(firewall) {
+        rows.push(Row::Chain(idx));
+        if expanded.contains(&chain_key(chain)) {
+            rows.extend((0..chain.rules.len()).map(|rule| Row::Rule(idx, rule)));
+        }
+    }
+    rows
+}
+
+/// A statement the way `nft` would write it, e.g. `tcp dport != 22` or
+/// `ct state established,related`.
+/// Consecutive values with the same key are listed together.
+#[must_use]
+pub fn statement_summary(statement: &pb::Statement) -> String {
+    format_statement(statement, false)
+}
+
+/// A statement in `nft` syntax, with its comparison spelled out even when it's `==`,
+/// so it reads back the same.
+#[must_use]
+pub fn statement_nft(statement: &pb::Statement) -> String {
+    format_statement(statement, true)
+}
+
+fn format_statement(statement: &pb::Statement, explicit_eq: bool) -> String {
+    let mut parts = vec![statement.name.clone()];
+    let mut op =
+        Some(statement.op.as_str()).filter(|op| !op.is_empty() && (explicit_eq || *op != "=="));
+    let mut values = statement.values.iter().peekable();
+    while let Some(first) = values.next() {
+        let mut merged = vec![first.value.as_str()];
+        while let Some(next) = values.next_if(|next| next.key == first.key) {
+            merged.push(next.value.as_str());
+        }
+        merged.retain(|value| !value.is_empty());
+        // A key without a value is the value itself, as in `iifname lo`.
+        // Either way the comparison goes before the first value it applies to.
+        if merged.is_empty() {
+            parts.extend(op.take().map(String::from));
+            parts.push(first.key.clone());
+        } else {
+            if !first.key.is_empty() {
+                parts.push(first.key.clone());
+            }
+            parts.extend(op.take().map(String::from));
+            parts.push(merged.join(","));
+        }
+    }
+    parts.join(" ")
+}
+
+/// What a rule matches, one statement per entry.
+#[must_use]
+pub fn rule_statements(rule: &pb::FwRule) -> Vec<String> {
+    rule.expressions
+        .iter()
+        .filter_map(|expression| expression.statement.as_ref())
+        .map(statement_summary)
+        .collect()
+}
+
+/// What a rule does with a match, e.g. `reject with tcp reset`.
+#[must_use]
+pub fn rule_target(rule: &pb::FwRule) -> String {
+    if rule.target_parameters.is_empty() {
+        rule.target.clone()
+    } else {
+        format!("{} {}", rule.target, rule.target_parameters)
+    }
+}
+
+/// One-line description of a rule, e.g. `tcp dport 22 → accept`.
+#[must_use]
+pub fn rule_summary(rule: &pb::FwRule) -> String {
+    let statements = rule_statements(rule);
+    let target = rule_target(rule);
+    if statements.is_empty() {
+        target
+    } else {
+        format!("{} → {target}", statements.join(" "))
+    }
+}
+
+/// The keys a builder statement takes, or none if it isn't one of [`STATEMENTS`].
+#[must_use]
+pub fn statement_keys(name: &str) -> Option<&'static [&'static str]> {
+    STATEMENTS
+        .iter()
+        .find(|(statement, _)| *statement == name)
+        .map(|(_, keys)| *keys)
+}
+
+/// Checks a statement makes sense before it's sent to the daemon.
+/// Statements outside of [`STATEMENTS`] are left to the daemon, since they're only ever
+/// passed along as received.
+/// # Errors
+/// Returns a description of what's wrong with the statement.
+pub fn validate_statement(statement: &pb::Statement) -> Result<(), String> {
+    let name = statement.name.as_str();
+    if !statement.op.is_empty() && !COMPARISONS.contains(&statement.op.as_str()) {
+        return Err(format!("{name}: unknown comparison {}", statement.op));
+    }
+    let Some(keys) = statement_keys(name) else {
+        return Ok(());
+    };
+    if statement.values.is_empty() {
+        return Err(format!("{name}: nothing to match"));
+    }
+    for value in &statement.values {
+        let (key, data) = (value.key.as_str(), value.value.trim());
+        if keys.is_empty() {
+            if key.trim().is_empty() || !data.is_empty() {
+                return Err(format!("{name}: name one interface to match"));
+            }
+            continue;
+        }
+        if !keys.contains(&key) {
+            return Err(format!("{name}: can't match on {key}"));
+        }
+        if data.is_empty() {
+            return Err(format!("{name} {key}: nothing to match"));
+        }
+        let valid = match (name, key) {
+            ("tcp" | "udp", _) => data.split(',').all(is_port_or_range),
+            ("ip", _) => is_address(data, false),
+            ("ip6", _) => is_address(data, true),
+            ("ct", "state") => CT_STATES.contains(&data),
+            (_, "mark") => parse_mark(data).is_some(),
+            _ => true,
+        };
+        if !valid {
+            return Err(format!("{name} {key}: {data} isn't valid here"));
+        }
+    }
+    Ok(())
+}
+
+/// Checks a rule's target and statements.
+/// # Errors
+/// Returns a description of the first problem found.
+pub fn validate_rule(rule: &pb::FwRule) -> Result<(), String> {
+    if rule.target.is_empty() {
+        return Err(String::from("Every rule needs a target"));
+    }
+    if matches!(rule.target.as_str(), "jump" | "goto") && rule.target_parameters.trim().is_empty() {
+        return Err(format!("Name the chain to {}", rule.target));
+    }
+    rule.expressions
+        .iter()
+        .filter_map(|expression| expression.statement.as_ref())
+        .try_for_each(validate_statement)
+}
+
+/// Checks a whole firewall before it's sent to the daemon, which replaces its own with it.
+/// # Errors
+/// Returns a description of the first problem found, and where.
+pub fn validate(firewall: &pb::SysFirewall) -> Result<(), String> {
+    let mut uuids = HashSet::new();
+    for (_, chain) in chains(firewall) {
+        let key = chain_key(chain);
+        if !chain.hook.is_empty() && !POLICIES.contains(&chain.policy.as_str()) {
+            return Err(format!("{key}: unknown policy {}", chain.policy));
+        }
+        for rule in &chain.rules {
+            validate_rule(rule).map_err(|err| format!("{key}: {err}"))?;
+            if !rule.uuid.is_empty() && !uuids.insert(rule.uuid.as_str()) {
+                return Err(format!("{key}: rule {} appears twice", rule.uuid));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Whether `data` is a port, or a range of them like 1024-2048.
+fn is_port_or_range(data: &str) -> bool {
+    let port = |data: &str| data.trim().parse::<u16>().ok();
+    match data.split_once('-') {
+        Some((low, high)) => port(low)
+            .zip(port(high))
+            .is_some_and(|(low, high)| low <= high),
+        None => port(data).is_some(),
+    }
+}
+
+/// Whether `data` is an address, network or range of addresses of the given family.
+fn is_address(data: &str, v6: bool) -> bool {
+    let address = |data: &str| {
+        data.trim()
+            .parse::<IpAddr>()
+            .is_ok_and(|addr| addr.is_ipv6() == v6)
+    };
+    if let Some((addr, prefix)) = data.split_once('/') {
+        let max = if v6 { 128 } else { 32 };
+        return address(addr) && prefix.parse::<u8>().is_ok_and(|prefix| prefix <= max);
+    }
+    match data.split_once('-') {
+        Some((low, high)) => address(low) && address(high),
+        None => address(data),
+    }
+}
+
+/// A packet mark, in decimal or hex like `0x10`.
+fn parse_mark(data: &str) -> Option<u32> {
+    match data.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16).ok(),
+        None => data.parse().ok(),
+    }
+}
+
+/// Where to export a node's firewall to, as an `nft` ruleset.
+#[derive(Clone, Debug)]
+pub struct Export {
+    /// Node whose firewall is exported.
+    pub node: NodeId,
+    /// File to write, starting at one named after the node in the working directory.
+    pub path: Field,
+    /// Whether the user is being asked to replace the file already at `path`.
+    pub overwrite: bool,
+}
+
+impl Export {
+    #[must_use]
+    pub fn new(node: &NodeId) -> Self {
+        Self {
+            node: node.clone(),
+            path: Field::text("Path", &export_file_name(node)),
+            overwrite: false,
+        }
+    }
+
+    /// The path typed in, made absolute against the working directory.
+    /// # Errors
+    /// Returns a description of the problem if there's no usable path.
+    pub fn target(&self) -> Result<PathBuf, String> {
+        if self.path.value.is_empty() {
+            return Err(String::from("Type a path to write to"));
+        }
+        std::path::absolute(&self.path.value)
+            .map_err(|err| format!("Unusable path '{}': {err}", self.path.value))
+    }
+}
+
+/// File a node's firewall is exported to by default, e.g. `10.0.0.1-firewall.nft`.
+#[must_use]
+pub fn export_file_name(node: &NodeId) -> String {
+    let node: String = node
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | '_') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{node}-firewall.nft")
+}
+
+/// State of the firewall view.
+#[derive(Clone, Debug, Default)]
+pub struct FirewallView {
+    /// Index of the selected row.
+    pub cursor: usize,
+    /// Chains showing their rules, by [`chain_key`].
+    pub expanded: HashSet<String>,
+    /// What became of the last action in the view: a note on success, or why it
+    /// couldn't be done.
+    pub status: Option<Result<String, String>>,
+    /// Whether the `nft` ruleset is shown rather than the chains table.
+    pub nft: bool,
+    /// How many lines of the `nft` ruleset are scrolled past.
+    pub nft_scroll: u16,
+    /// Firewall rule being added or edited, shown in place of the view.
+    pub editor: Option<FwRuleEditor>,
+    /// Export being asked about, if any.
+    pub export: Option<Export>,
+}
+
+impl FirewallView {
+    /// Rows of `firewall` as listed.
+    #[must_use]
+    pub fn rows(&self, firewall: &pb::SysFirewall) -> Vec<Row> {
+        rows(firewall, &self.expanded)
+    }
+
+    /// The row under the cursor. The cursor may have run past the end if rows went
+    /// away, so it's clamped here.
+    #[must_use]
+    pub fn selected_row(&self, firewall: &pb::SysFirewall) -> Option<Row> {
+        let rows = self.rows(firewall);
+        let idx = self.cursor.min(rows.len().checked_sub(1)?);
+        Some(rows[idx])
+    }
+
+    /// Show or hide the rules of the selected chain.
+    fn toggle_chain(&mut self, firewall: &pb::SysFirewall) {
+        let Some(idx) = self.selected_row(firewall).map(Row::chain) else {
+            return;
+        };
+        let Some(key) = idx.get(firewall).map(chain_key) else {
+            return;
+        };
+        if !self.expanded.remove(&key) {
+            self.expanded.insert(key);
+        }
+        // Keep the chain selected when its rules fold away from under the cursor.
+        self.cursor = self
+            .rows(firewall)
+            .iter()
+            .position(|row| *row == Row::Chain(idx))
+            .unwrap_or_default();
+    }
+
+    /// Show the rules of every chain, or hide them all if they're all shown.
+    fn toggle_chains(&mut self, firewall: &pb::SysFirewall) {
+        let keys: HashSet<String> = chains(firewall)
+            .into_iter()
+            .map(|(_, chain)| chain_key(chain))
+            .collect();
+        if self.expanded.is_superset(&keys) {
+            self.expanded.clear();
+            self.cursor = 0;
+        } else {
+            self.expanded = keys;
+        }
+    }
+
+    /// Handle a key press for the node's `firewall`, if it has sent one: `v` to switch
+    /// between the chains table and the `nft` ruleset, up/down to move through either,
+    /// `esc` to leave the ruleset, and in the table, `enter`/`space` on a chain to fold
+    /// it and `a` to fold them all. Returns whether the key was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent, firewall: Option<&pb::SysFirewall>) -> bool {
+        let rows = firewall.map_or(0, |firewall| self.rows(firewall).len());
+        match (self.nft, key_event.code) {
+            (_, KeyCode::Char('v')) => {
+                self.nft = !self.nft;
+                self.nft_scroll = 0;
+            }
+            (true, KeyCode::Up) => self.nft_scroll = self.nft_scroll.saturating_sub(1),
+            (true, KeyCode::Down) => self.nft_scroll = self.nft_scroll.saturating_add(1),
+            (true, KeyCode::Esc) => self.nft = false,
+            (false, KeyCode::Up) => self.cursor = self.cursor.saturating_sub(1),
+            (false, KeyCode::Down) => self.cursor = (self.cursor + 1).min(rows.saturating_sub(1)),
+            (false, KeyCode::Enter | KeyCode::Char(' ')) => {
+                let Some(firewall) = firewall else {
+                    return true;
+                };
+                if matches!(self.selected_row(firewall), Some(Row::Rule(..)))
+                    && key_event.code == KeyCode::Enter
+                {
+                    return false;
+                }
+                self.toggle_chain(firewall);
+            }
+            (false, KeyCode::Char('a')) => {
+                if let Some(firewall) = firewall {
+                    self.toggle_chains(firewall);
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement(op: &str, name: &str, values: &[(&str, &str)]) -> pb::Expressions {
+        pb::Expressions {
+            statement: Some(pb::Statement {
+                op: String::from(op),
+                name: String::from(name),
+                values: values
+                    .iter()
+                    .map(|(key, value)| pb::StatementValues {
+                        key: String::from(*key),
+                        value: String::from(*value),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    /// A firewall like the daemon's default, with the filter chains in one group and
+    /// a mangle chain with two rules in another.
+    fn firewall() -> pb::SysFirewall {
+        let chain = |table: &str, name: &str, hook: &str, rules| pb::FwChain {
+            name: String::from(name),
+            table: String::from(table),
+            family: String::from("inet"),
+            priority: String::from("0"),
+            r#type: String::from("filter"),
+            hook: String::from(hook),
+            policy: String::from("accept"),
+            rules,
+        };
+        pb::SysFirewall {
+            enabled: true,
+            version: 1,
+            system_rules: vec![
+                pb::FwChains {
+                    rule: None,
+                    chains: vec![
+                        chain("filter", "input", "input", vec![]),
+                        chain("filter", "output", "output", vec![]),
+                    ],
+                },
+                pb::FwChains {
+                    rule: None,
+                    chains: vec![chain(
+                        "mangle",
+                        "output",
+                        "output",
+                        vec![
+                            pb::FwRule {
+                                uuid: String::from("a"),
+                                enabled: true,
+                                description: String::from("allow ssh"),
+                                expressions: vec![statement("==", "tcp", &[("dport", "22")])],
+                                target: String::from("accept"),
+                                ..Default::default()
+                            },
+                            pb::FwRule {
+                                uuid: String::from("b"),
+                                enabled: false,
+                                expressions: vec![
+                                    statement("", "ct", &[("state", "new"), ("state", "invalid")]),
+                                    statement("!=", "iifname", &[("lo", "")]),
+                                ],
+                                target: String::from("reject"),
+                                target_parameters: String::from("with tcp reset"),
+                                ..Default::default()
+                            },
+                        ],
+                    )],
+                },
+            ],
+        }
+    }
+
+    /// Test that the view folds chains, leaves `enter` on a rule to the caller, and keeps
+    /// the ruleset read only.
+    #[test]
+    fn test_view_keys() {
+        let firewall = firewall();
+        let mut view = FirewallView::default();
+        let key = |code| KeyEvent::from(code);
+        view.cursor = 2;
+        assert!(view.handle_key(key(KeyCode::Enter), Some(&firewall)));
+        assert_eq!(view.rows(&firewall).len(), 5);
+        assert!(view.handle_key(key(KeyCode::Down), Some(&firewall)));
+        assert!(!view.handle_key(key(KeyCode::Enter), Some(&firewall)));
+        // Folding from a rule leaves its chain selected.
+        assert!(view.handle_key(key(KeyCode::Char(' ')), Some(&firewall)));
+        assert_eq!(view.cursor, 2);
+        assert!(view.handle_key(key(KeyCode::Char('a')), Some(&firewall)));
+        assert_eq!(view.rows(&firewall).len(), 5);
+
+        assert!(view.handle_key(key(KeyCode::Char('v')), Some(&firewall)));
+        assert!(view.handle_key(key(KeyCode::Down), Some(&firewall)));
+        assert_eq!((view.nft_scroll, view.cursor), (1, 2));
+        assert!(!view.handle_key(key(KeyCode::Char('a')), Some(&firewall)));
+        assert!(view.handle_key(key(KeyCode::Esc), None));
+        assert!(!view.nft);
+    }
+
+    /// Test that chains across groups are listed, with rules only under expanded chains.
+    #[test]
+    fn test_rows() {
+        let firewall = firewall();
+        let mangle = ChainIdx { group: 1, chain: 0 };
+        assert_eq!(rows(&firewall, &HashSet::new()).len(), 3);
+        let expanded = HashSet::from([String::from("inet mangle output")]);
+        let rows = rows(&firewall, &expanded);
+        assert_eq!(
+            rows[2..],
+            [
+                Row::Chain(mangle),
+                Row::Rule(mangle, 0),
+                Row::Rule(mangle, 1)
+            ]
+        );
+        assert_eq!(rows[4].chain().get(&firewall).unwrap().table, "mangle");
+    }
+
+    /// Test describing rules the way `nft` would write them.
+    #[test]
+    fn test_rule_summary() {
+        let firewall = firewall();
+        let rules = &firewall.system_rules[1].chains[0].rules;
+        assert_eq!(rule_summary(&rules[0]), "tcp dport 22 → accept");
+        assert_eq!(
+            rule_summary(&rules[1]),
+            "ct state new,invalid iifname != lo → reject with tcp reset"
+        );
+        assert_eq!(
+            statement_summary(
+                statement("", "quota", &[("over", ""), ("bytes", "1000")])
+                    .statement
+                    .as_ref()
+                    .unwrap()
+            ),
+            "quota over bytes 1000"
+        );
+    }
+
+    /// Test that statements, rules and chains are checked before they're sent.
+    #[test]
+    fn test_validate() {
+        let check = |op, name, values| {
+            validate_statement(statement(op, name, values).statement.as_ref().unwrap())
+        };
+        assert!(check("==", "tcp", &[("dport", "22,8000-8080")]).is_ok());
+        assert!(check("==", "tcp", &[("dport", "8080-8000")]).is_err());
+        assert!(check("==", "udp", &[("dport", "70000")]).is_err());
+        assert!(check("=~", "tcp", &[("dport", "22")]).is_err());
+        assert!(check("==", "tcp", &[("daddr", "22")]).is_err());
+        assert!(check("!=", "ip", &[("saddr", "192.168.0.0/16")]).is_ok());
+        assert!(check("==", "ip", &[("saddr", "fd00::1")]).is_err());
+        assert!(check("==", "ip6", &[("daddr", "fd00::1-fd00::ff")]).is_ok());
+        assert!(check("", "ct", &[("state", "new"), ("state", "related")]).is_ok());
+        assert!(check("==", "meta", &[("mark", "0x10")]).is_ok());
+        assert!(check("==", "oifname", &[("", "")]).is_err());
+        // Statements outside the builder are the daemon's business.
+        assert!(check("", "counter", &[("name", "")]).is_ok());
+
+        let mut firewall = firewall();
+        assert!(validate(&firewall).is_ok());
+        firewall.system_rules[1].chains[0].rules[1].uuid = String::from("a");
+        assert_eq!(
+            validate(&firewall),
+            Err(String::from("inet mangle output: rule a appears twice"))
+        );
+        firewall.system_rules[0].chains[0].policy = String::from("reject");
+        assert!(validate(&firewall).is_err());
+    }
+}
@@ -0,0 +1,498 @@
+//! Firewall rule editor: a form for one `FwRule` of a chain, with its statements built
+//! from structured fields rather than typed as `nft` syntax.
+
+use std::hash::{BuildHasher, Hasher, RandomState};
+use std::time::SystemTime;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::firewall;
+use crate::form::{Field, Form};
+use crate::opensnitch_proto::pb;
+
+const DESCRIPTION: &str = "Description";
+const ENABLED: &str = "Enabled";
+const TARGET: &str = "Target";
+const TARGET_PARAMETERS: &str = "Target parameters";
+
+const STATEMENT: &str = "Statement";
+const COMPARISON: &str = "Comparison";
+const KEY: &str = "Match on";
+const VALUE: &str = "Value";
+
+/// One statement of the rule.
+#[derive(Clone, Debug)]
+pub enum Expression {
+    /// A statement from the builder: statement, comparison, key and value, in that order.
+    Built(Form),
+    /// A statement the builder can't express, kept as received. It can be removed,
+    /// but not edited.
+    Kept(pb::Statement),
+}
+
+impl Expression {
+    fn new(statement: &pb::Statement) -> Self {
+        let Some(keys) = firewall::statement_keys(&statement.name) else {
+            return Expression::Kept(statement.clone());
+        };
+        let Some(first) = statement.values.first() else {
+            return Expression::Kept(statement.clone());
+        };
+        let same_key = statement.values.iter().all(|value| value.key == first.key);
+        // Only `ct state` takes several values, one per state.
+        let single = statement.values.len() == 1
+            || (statement.name == "ct" && first.key == "state" && same_key);
+        let (key, value) = if keys.is_empty() {
+            (None, first.key.clone())
+        } else {
+            let values: Vec<&str> = statement
+                .values
+                .iter()
+                .map(|value| value.value.as_str())
+                .collect();
+            (Some(first.key.as_str()), values.join(","))
+        };
+        let representable = single
+            && match key {
+                None => first.value.is_empty(),
+                Some(key) => keys.contains(&key),
+            };
+        if !representable {
+            return Expression::Kept(statement.clone());
+        }
+        let names = firewall::STATEMENTS.map(|(name, _)| name);
+        let op = if statement.op.is_empty() {
+            "=="
+        } else {
+            &statement.op
+        };
+        Expression::Built(Form::new(vec![
+            Field::choice(STATEMENT, &names, Some(&statement.name)),
+            Field::choice(COMPARISON, &firewall::COMPARISONS, Some(op)),
+            Field::choice(KEY, keys, key),
+            Field::text(VALUE, &value),
+        ]))
+    }
+
+    /// A fresh statement, matching a TCP destination port.
+    fn blank() -> Self {
+        Self::new(&pb::Statement {
+            op: String::from("=="),
+            name: String::from("tcp"),
+            values: vec![pb::StatementValues {
+                key: String::from("dport"),
+                value: String::new(),
+            }],
+        })
+    }
+
+    /// Indexes of the fields shown. Interface statements have no key to pick.
+    #[must_use]
+    pub fn visible_fields(&self) -> Vec<usize> {
+        match self {
+            Expression::Built(form)
+                if firewall::statement_keys(&form.field(STATEMENT).value)
+                    .is_some_and(<[&str]>::is_empty) =>
+            {
+                vec![0, 1, 3]
+            }
+            Expression::Built(_) => vec![0, 1, 2, 3],
+            Expression::Kept(_) => vec![0],
+        }
+    }
+
+    /// Offer the keys of the statement picked, after it's changed.
+    fn sync_keys(&mut self) {
+        let Expression::Built(form) = self else {
+            return;
+        };
+        let keys = firewall::statement_keys(&form.field(STATEMENT).value).unwrap_or_default();
+        if !keys.contains(&form.field(KEY).value.as_str()) {
+            form.fields[2] = Field::choice(KEY, keys, keys.first().copied());
+        }
+    }
+
+    /// The statement as the daemon takes it.
+    fn to_statement(&self) -> Result<pb::Statement, String> {
+        let form = match self {
+            Expression::Built(form) => form,
+            Expression::Kept(statement) => return Ok(statement.clone()),
+        };
+        let field = |label| form.field(label).value.as_str();
+        let (name, key, data) = (field(STATEMENT), field(KEY), field(VALUE).trim());
+        let value = |key: &str, value: &str| pb::StatementValues {
+            key: String::from(key),
+            value: String::from(value),
+        };
+        let values = if firewall::statement_keys(name).is_some_and(<[&str]>::is_empty) {
+            vec![value(data, "")]
+        } else if name == "ct" && key == "state" {
+            data.split(',')
+                .map(|state| value(key, state.trim()))
+                .collect()
+        } else {
+            vec![value(key, data)]
+        };
+        let statement = pb::Statement {
+            op: String::from(field(COMPARISON)),
+            name: String::from(name),
+            values,
+        };
+        firewall::validate_statement(&statement)?;
+        Ok(statement)
+    }
+}
+
+/// Where a row of the editor points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Row {
+    /// A field of the rule itself.
+    Rule(usize),
+    /// A field of a statement: index into the expressions, then into its fields.
+    Expression(usize, usize),
+}
+
+/// A firewall rule being added to a chain or edited.
+#[derive(Clone, Debug)]
+pub struct FwRuleEditor {
+    /// Chain the rule belongs to, by [`firewall::chain_key`].
+    pub chain: String,
+    /// Whether the rule is already in the chain, rather than being added to it.
+    pub editing: bool,
+    /// The rule as loaded, for what the form doesn't cover: UUID, position and parameters.
+    base: pb::FwRule,
+    /// Description, enabled flag, target and target parameters.
+    pub form: Form,
+    pub expressions: Vec<Expression>,
+    /// Index of the selected row, see [`FwRuleEditor::rows`].
+    pub cursor: usize,
+    /// ID of the last `RELOAD_FW_RULES` notification sent from this editor.
+    pub sent: Option<u64>,
+    /// Why the rule couldn't be sent or changed, if it couldn't.
+    pub error: Option<String>,
+}
+
+impl FwRuleEditor {
+    /// An editor for a new rule at the end of a chain, accepting TCP traffic to start with.
+    #[must_use]
+    pub fn new_rule(chain: &pb::FwChain) -> Self {
+        let position = chain
+            .rules
+            .iter()
+            .map(|rule| rule.position + 1)
+            .max()
+            .unwrap_or_default();
+        let rule = pb::FwRule {
+            uuid: new_uuid(),
+            enabled: true,
+            position,
+            target: String::from("accept"),
+            ..Default::default()
+        };
+        let mut editor = Self::edit(chain, &rule);
+        editor.editing = false;
+        editor.expressions.push(Expression::blank());
+        editor
+    }
+
+    /// An editor loaded with one of a chain's rules.
+    #[must_use]
+    pub fn edit(chain: &pb::FwChain, rule: &pb::FwRule) -> Self {
+        let form = Form::new(vec![
+            Field::text(DESCRIPTION, &rule.description),
+            Field::toggle(ENABLED, Some(rule.enabled)),
+            Field::choice(TARGET, &firewall::TARGETS, Some(&rule.target)),
+            Field::text(TARGET_PARAMETERS, &rule.target_parameters),
+        ]);
+        Self {
+            chain: firewall::chain_key(chain),
+            editing: true,
+            base: rule.clone(),
+            form,
+            expressions: rule
+                .expressions
+                .iter()
+                .filter_map(|expression| expression.statement.as_ref())
+                .map(Expression::new)
+                .collect(),
+            cursor: 0,
+            sent: None,
+            error: None,
+        }
+    }
+
+    /// UUID of the rule, which the daemon knows it by.
+    #[must_use]
+    pub fn uuid(&self) -> &str {
+        &self.base.uuid
+    }
+
+    /// Every row, rule fields first, then each statement's fields.
+    #[must_use]
+    pub fn rows(&self) -> Vec<Row> {
+        let rule_rows = (0..self.form.fields.len()).map(Row::Rule);
+        let expression_rows = self
+            .expressions
+            .iter()
+            .enumerate()
+            .flat_map(|(idx, entry)| {
+                entry
+                    .visible_fields()
+                    .into_iter()
+                    .map(move |field| Row::Expression(idx, field))
+            });
+        rule_rows.chain(expression_rows).collect()
+    }
+
+    /// The field a row points at, none for statements kept as received.
+    #[must_use]
+    pub fn field(&self, row: Row) -> Option<&Field> {
+        match row {
+            Row::Rule(field) => self.form.fields.get(field),
+            Row::Expression(idx, field) => match self.expressions.get(idx)? {
+                Expression::Built(form) => form.fields.get(field),
+                Expression::Kept(_) => None,
+            },
+        }
+    }
+
+    /// Handle a key press. Up/down move between rows, ctrl+A adds a statement after the
+    /// selected one, ctrl+D removes the selected statement, and anything else edits the
+    /// selected field.
+    /// Returns whether the rule changed. Problems are left in `error`.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        let rows = self.rows();
+        let row = rows.get(self.cursor).copied();
+        let result = match key_event.code {
+            KeyCode::Up => {
+                self.cursor = self.cursor.saturating_sub(1);
+                return false;
+            }
+            KeyCode::Down => {
+                self.cursor = std::cmp::min(self.cursor + 1, rows.len().saturating_sub(1));
+                return false;
+            }
+            KeyCode::Char('a') if key_event.modifiers == KeyModifiers::CONTROL => {
+                let at = match row {
+                    Some(Row::Expression(idx, _)) => idx + 1,
+                    _ => self.expressions.len(),
+                };
+                self.expressions.insert(at, Expression::blank());
+                self.select(Row::Expression(at, 0));
+                Ok(())
+            }
+            KeyCode::Char('d') if key_event.modifiers == KeyModifiers::CONTROL => match row {
+                Some(Row::Expression(idx, _)) => {
+                    self.expressions.remove(idx);
+                    self.cursor = std::cmp::min(self.cursor, self.rows().len().saturating_sub(1));
+                    Ok(())
+                }
+                _ => Err(String::from("Select a statement to remove")),
+            },
+            _ => {
+                let edited = match row {
+                    Some(Row::Rule(field)) => self.form.fields[field].handle_key(key_event),
+                    Some(Row::Expression(idx, field)) => match &mut self.expressions[idx] {
+                        Expression::Built(form) => form.fields[field].handle_key(key_event),
+                        Expression::Kept(_) => false,
+                    },
+                    None => false,
+                };
+                if !edited {
+                    return false;
+                }
+                if let Some(Row::Expression(idx, 0)) = row {
+                    self.expressions[idx].sync_keys();
+                }
+                Ok(())
+            }
+        };
+        let changed = result.is_ok();
+        self.error = result.err();
+        changed
+    }
+
+    fn select(&mut self, row: Row) {
+        if let Some(idx) = self.rows().iter().position(|other| *other == row) {
+            self.cursor = idx;
+        }
+    }
+
+    /// Builds the rule to send.
+    /// # Errors
+    /// Returns a description of the first problem found.
+    pub fn build(&self) -> Result<pb::FwRule, String> {
+        self.form.validate()?;
+        let field = |label| self.form.field(label).value.trim();
+        let expressions = self
+            .expressions
+            .iter()
+            .map(|expression| {
+                expression.to_statement().map(|statement| pb::Expressions {
+                    statement: Some(statement),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let rule = pb::FwRule {
+            description: String::from(field(DESCRIPTION)),
+            enabled: self.form.field(ENABLED).as_bool().unwrap_or(true),
+            target: String::from(field(TARGET)),
+            target_parameters: String::from(field(TARGET_PARAMETERS)),
+            expressions,
+            ..self.base.clone()
+        };
+        firewall::validate_rule(&rule)?;
+        Ok(rule)
+    }
+}
+
+/// A random (version 4) UUID for a new rule.
+fn new_uuid() -> String {
+    let nanos = SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    // Every `RandomState` is seeded differently.
+    let random = || {
+        let mut hasher = RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        hasher.finish()
+    };
+    let bits =
+        (u128::from(random()) << 64 | u128::from(random())) & !(0xf000 << 64) | (0x4000 << 64);
+    let bits = bits & !(0xc << 60) | (0x8 << 60);
+    let hex = format!("{bits:032x}");
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn type_text(editor: &mut FwRuleEditor, text: &str) {
+        for c in text.chars() {
+            editor.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    fn statement(op: &str, name: &str, values: &[(&str, &str)]) -> pb::Expressions {
+        pb::Expressions {
+            statement: Some(pb::Statement {
+                op: String::from(op),
+                name: String::from(name),
+                values: values
+                    .iter()
+                    .map(|(key, value)| pb::StatementValues {
+                        key: String::from(*key),
+                        value: String::from(*value),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn chain() -> pb::FwChain {
+        pb::FwChain {
+            name: String::from("input"),
+            table: String::from("filter"),
+            family: String::from("inet"),
+            hook: String::from("input"),
+            policy: String::from("accept"),
+            ..Default::default()
+        }
+    }
+
+    /// Test that a rule loads and builds back the same, keeping what the builder
+    /// can't express.
+    #[test]
+    fn test_round_trip() {
+        let rule = pb::FwRule {
+            uuid: String::from("c0ffee"),
+            enabled: true,
+            position: 3,
+            description: String::from("established traffic"),
+            expressions: vec![
+                statement(
+                    "==",
+                    "ct",
+                    &[("state", "established"), ("state", "related")],
+                ),
+                statement("!=", "iifname", &[("lo", "")]),
+                statement("", "quota", &[("over", ""), ("bytes", "1000")]),
+                statement("==", "ip", &[("daddr", "10.0.0.0/8")]),
+            ],
+            target: String::from("accept"),
+            ..Default::default()
+        };
+        let editor = FwRuleEditor::edit(&chain(), &rule);
+        assert!(matches!(editor.expressions[2], Expression::Kept(_)));
+        assert_eq!(editor.rows().len(), 4 + 4 + 3 + 1 + 4);
+        assert_eq!(
+            editor
+                .field(Row::Expression(0, 3))
+                .map(|field| field.value.as_str()),
+            Some("established,related")
+        );
+        assert_eq!(editor.build(), Ok(rule));
+    }
+
+    /// Test building a rule from scratch with the keyboard.
+    #[test]
+    fn test_build_new() {
+        let mut editor = FwRuleEditor::new_rule(&chain());
+        assert!(!editor.editing);
+        assert_eq!(editor.uuid().len(), 36);
+        assert!(editor.build().is_err());
+
+        editor.select(Row::Expression(0, 3));
+        type_text(&mut editor, "22,80-90");
+        editor.handle_key(ctrl('a'));
+        // Picking `ct` offers its keys instead of the port ones.
+        for _ in 0..4 {
+            editor.handle_key(KeyEvent::from(KeyCode::Right));
+        }
+        assert_eq!(
+            editor.field(Row::Expression(1, 2)).unwrap().value,
+            String::from("state")
+        );
+        editor.select(Row::Expression(1, 3));
+        type_text(&mut editor, "new,bogus");
+        assert_eq!(
+            editor.build(),
+            Err(String::from("ct state: bogus isn't valid here"))
+        );
+        for _ in 0.."bogus".len() {
+            editor.handle_key(KeyEvent::from(KeyCode::Backspace));
+        }
+        type_text(&mut editor, "established");
+
+        let rule = editor.build().expect("build failed");
+        assert_eq!(rule.expressions.len(), 2);
+        let ct = rule.expressions[1].statement.as_ref().unwrap();
+        assert_eq!(ct.values.len(), 2);
+        assert_eq!(ct.values[1].value, "established");
+        assert_eq!(rule.target, "accept");
+
+        // Jumping needs a chain to jump to.
+        editor.select(Row::Rule(2));
+        while editor.form.field(TARGET).value != "jump" {
+            editor.handle_key(KeyEvent::from(KeyCode::Right));
+        }
+        assert_eq!(editor.build(), Err(String::from("Name the chain to jump")));
+
+        editor.select(Row::Expression(0, 0));
+        editor.handle_key(ctrl('d'));
+        assert_eq!(editor.expressions.len(), 1);
+    }
+}
@@ -0,0 +1,316 @@
+//! Editable fields for the TUI's forms, and how key presses change them.
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+/// What a field holds, and so how it's edited.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    /// One of a fixed set of values, cycled with left/right.
+    Choice(Vec<String>),
+    /// On or off, flipped with left/right or space.
+    Toggle,
+    /// Unsigned integer within an inclusive range, typed in.
+    Number { min: u64, max: u64 },
+    /// Free text, typed in.
+    Text,
+}
+
+/// One editable value in a form.
+/// Values are kept as text, with an empty value meaning unset.
+#[derive(Clone, Debug)]
+pub struct Field {
+    /// Label shown next to the value.
+    pub label: String,
+    pub kind: FieldKind,
+    /// Current value.
+    pub value: String,
+    /// Value the form was loaded with, to tell what's been edited.
+    initial: String,
+}
+
+impl Field {
+    /// A field picking one of `options`.
+    /// A current value that isn't among the options is kept as an extra option,
+    /// so loading a form never changes a value on its own.
+    #[must_use]
+    pub fn choice(label: &str, options: &[&str], value: Option<&str>) -> Self {
+        let mut options: Vec<String> = options.iter().map(|option| String::from(*option)).collect();
+        if let Some(value) = value
+            && !options.iter().any(|option| option == value)
+        {
+            options.push(String::from(value));
+        }
+        Self::new(label, FieldKind::Choice(options), value.unwrap_or_default())
+    }
+
+    #[must_use]
+    pub fn toggle(label: &str, value: Option<bool>) -> Self {
+        Self::new(
+            label,
+            FieldKind::Toggle,
+            &value.map_or_else(String::new, |value| value.to_string()),
+        )
+    }
+
+    #[must_use]
+    pub fn number(label: &str, min: u64, max: u64, value: Option<u64>) -> Self {
+        Self::new(
+            label,
+            FieldKind::Number { min, max },
+            &value.map_or_else(String::new, |value| value.to_string()),
+        )
+    }
+
+    #[must_use]
+    pub fn text(label: &str, value: &str) -> Self {
+        Self::new(label, FieldKind::Text, value)
+    }
+
+    fn new(label: &str, kind: FieldKind, value: &str) -> Self {
+        Self {
+            label: String::from(label),
+            kind,
+            value: String::from(value),
+            initial: String::from(value),
+        }
+    }
+
+    /// Whether the value differs from what the form was loaded with.
+    #[must_use]
+    pub fn changed(&self) -> bool {
+        self.value != self.initial
+    }
+
+    /// Checks an edited value. Untouched values are accepted as they came.
+    /// # Errors
+    /// Returns a description of what's wrong with the value.
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.changed() {
+            return Ok(());
+        }
+        match &self.kind {
+            FieldKind::Choice(_) | FieldKind::Toggle | FieldKind::Text => Ok(()),
+            FieldKind::Number { min, max } => match self.value.parse::<u64>() {
+                Ok(number) if (*min..=*max).contains(&number) => Ok(()),
+                _ => Err(format!(
+                    "{} must be a number from {min} to {max}",
+                    self.label
+                )),
+            },
+        }
+    }
+
+    /// Value as a number, if it is one.
+    #[must_use]
+    pub fn as_number(&self) -> Option<u64> {
+        self.value.parse().ok()
+    }
+
+    /// Value as a flag, if it is one.
+    #[must_use]
+    pub fn as_bool(&self) -> Option<bool> {
+        self.value.parse().ok()
+    }
+
+    /// Value for display, with unset values shown as a dash.
+    #[must_use]
+    pub fn display(&self) -> &str {
+        if self.value.is_empty() {
+            "-"
+        } else {
+            &self.value
+        }
+    }
+
+    /// Step to the next or previous value of a choice or toggle.
+    fn cycle(&mut self, forward: bool) {
+        match &self.kind {
+            FieldKind::Choice(options) if !options.is_empty() => {
+                let len = options.len();
+                let next = match options.iter().position(|option| *option == self.value) {
+                    None => 0,
+                    Some(idx) if forward => (idx + 1) % len,
+                    Some(idx) => (idx + len - 1) % len,
+                };
+                self.value.clone_from(&options[next]);
+            }
+            FieldKind::Toggle => {
+                self.value = (!self.as_bool().unwrap_or(false)).to_string();
+            }
+            _ => {}
+        }
+    }
+
+    /// Handle a key aimed at this field. Returns whether the key was used.
+    /// Keys held with ctrl or alt are left for whoever owns the form.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        if key_event
+            .modifiers
+            .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT)
+        {
+            return false;
+        }
+        match (&self.kind, key_event.code) {
+            (FieldKind::Choice(_) | FieldKind::Toggle, KeyCode::Right | KeyCode::Char(' ')) => {
+                self.cycle(true);
+            }
+            (FieldKind::Choice(_) | FieldKind::Toggle, KeyCode::Left) => self.cycle(false),
+            (FieldKind::Number { .. }, KeyCode::Char(c)) if c.is_ascii_digit() => {
+                self.value.push(c);
+            }
+            (FieldKind::Text, KeyCode::Char(c)) => self.value.push(c),
+            (FieldKind::Number { .. } | FieldKind::Text, KeyCode::Backspace) => {
+                self.value.pop();
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Fields edited together, with one of them selected.
+#[derive(Clone, Debug)]
+pub struct Form {
+    pub fields: Vec<Field>,
+    /// Index of the selected field.
+    pub cursor: usize,
+}
+
+impl Form {
+    #[must_use]
+    pub fn new(fields: Vec<Field>) -> Self {
+        Self { fields, cursor: 0 }
+    }
+
+    /// Handle a key press: up/down to move between fields, anything else goes to the
+    /// selected field. Returns whether the key was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        match key_event.code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => {
+                self.cursor = std::cmp::min(self.cursor + 1, self.fields.len().saturating_sub(1));
+            }
+            _ => {
+                return self
+                    .fields
+                    .get_mut(self.cursor)
+                    .is_some_and(|field| field.handle_key(key_event));
+            }
+        }
+        true
+    }
+
+    /// The field with the given label.
+    /// # Panics
+    /// If there's no such field, which is a bug in whoever built the form.
+    #[must_use]
+    pub fn field(&self, label: &str) -> &Field {
+        self.fields
+            .iter()
+            .find(|field| field.label == label)
+            .unwrap_or_else(|| panic!("no form field {label}"))
+    }
+
+    /// Whether any field has been edited.
+    #[must_use]
+    pub fn is_dirty(&self) -> bool {
+        self.fields.iter().any(Field::changed)
+    }
+
+    /// Checks every field.
+    /// # Errors
+    /// Returns the problems found, one per invalid field.
+    pub fn validate(&self) -> Result<(), String> {
+        let errors: Vec<String> = self
+            .fields
+            .iter()
+            .filter_map(|field| field.validate().err())
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(form: &mut Form, code: KeyCode) -> bool {
+        form.handle_key(KeyEvent::from(code))
+    }
+
+    /// Test that keys move between fields and edit each kind of field.
+    #[test]
+    fn test_handle_key() {
+        let mut form = Form::new(vec![
+            Field::choice("Action", &["allow", "deny"], Some("deny")),
+            Field::toggle("Flag", None),
+            Field::number("Count", 1, 100, Some(25)),
+        ]);
+        assert!(!form.is_dirty());
+
+        assert!(press(&mut form, KeyCode::Right));
+        assert_eq!(form.field("Action").value, "allow");
+        assert!(press(&mut form, KeyCode::Left));
+        assert_eq!(form.field("Action").value, "deny");
+        assert!(!press(&mut form, KeyCode::Char('7')));
+
+        press(&mut form, KeyCode::Down);
+        press(&mut form, KeyCode::Char(' '));
+        assert_eq!(form.field("Flag").as_bool(), Some(true));
+
+        press(&mut form, KeyCode::Down);
+        press(&mut form, KeyCode::Down);
+        assert_eq!(form.cursor, 2);
+        press(&mut form, KeyCode::Backspace);
+        press(&mut form, KeyCode::Char('0'));
+        assert!(!press(&mut form, KeyCode::Char('x')));
+        assert_eq!(form.field("Count").as_number(), Some(20));
+        assert!(form.is_dirty());
+        assert!(form.validate().is_ok());
+    }
+
+    /// Test that text fields take any character, but not ones held with ctrl.
+    #[test]
+    fn test_text() {
+        let mut form = Form::new(vec![Field::text("Name", "ab")]);
+        for c in ['c', ' ', 'd'] {
+            assert!(press(&mut form, KeyCode::Char(c)));
+        }
+        press(&mut form, KeyCode::Backspace);
+        assert!(!form.handle_key(KeyEvent::new(KeyCode::Char('a'), KeyModifiers::CONTROL)));
+        assert_eq!(form.field("Name").value, "abc ");
+        assert_eq!(Field::text("Name", "").display(), "-");
+    }
+
+    /// Test that edited values are validated, while values as loaded are left alone.
+    #[test]
+    fn test_validate() {
+        let mut form = Form::new(vec![
+            Field::number("Count", 1, 100, Some(500)),
+            Field::choice("Method", &["ebpf", "proc"], Some("audit")),
+        ]);
+        assert!(form.validate().is_ok());
+        assert_eq!(
+            form.field("Method").kind,
+            FieldKind::Choice(vec![
+                String::from("ebpf"),
+                String::from("proc"),
+                String::from("audit")
+            ])
+        );
+
+        form.fields[0].value.clear();
+        assert_eq!(
+            form.validate(),
+            Err(String::from("Count must be a number from 1 to 100"))
+        );
+        form.fields[0].value = String::from("0");
+        assert!(form.validate().is_err());
+        form.fields[0].value = String::from("100");
+        assert!(form.validate().is_ok());
+    }
+}
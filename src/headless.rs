@@ -0,0 +1,210 @@
+//! Headless mode: serve daemons without a terminal, answering prompts from a policy and
+//! logging what happens as JSON lines on stdout.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Value, json};
+
+use crate::alert::Alert;
+use crate::app::App;
+use crate::event::{AppEvent, ConnectionEvent, Event};
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+use crate::policy::Policy;
+use crate::server::ServerState;
+
+/// Minimum time between stats lines for a node. Daemons ping far more often than that.
+const STATS_LOG_INTERVAL: Duration = Duration::from_mins(1);
+
+/// Run the app's event loop without a terminal until asked to quit.
+/// # Errors
+/// Returns an error if some task panicked.
+pub async fn run(mut app: App, policy: Policy) -> color_eyre::Result<()> {
+    let mut log = Logger::new(std::io::stdout());
+    app.start(false);
+    while app.running {
+        let event = app.events.next().await?;
+        if let Event::App(app_event) = &event {
+            log.app_event(app_event);
+        }
+        app.handle_event(event)?;
+
+        for (conn, rule) in app.answer_pending_prompts(|conn| policy.decide(&conn.connection)) {
+            log.decision(&conn, &rule);
+        }
+        // Everything worth alerting on ends up here, whether from a daemon or the app.
+        for alert in app.current_alerts.drain(..) {
+            log.alert(&alert);
+        }
+    }
+    log.emit("stopping", json!({}));
+    app.finish().await
+}
+
+/// Writes one JSON object per line for each thing worth logging.
+struct Logger<W: Write> {
+    out: W,
+    /// When stats were last logged for each node.
+    last_stats: HashMap<NodeId, SystemTime>,
+}
+
+impl<W: Write> Logger<W> {
+    fn new(out: W) -> Self {
+        Self {
+            out,
+            last_stats: HashMap::new(),
+        }
+    }
+
+    /// Writes a line with `fields`, plus the event name and a timestamp.
+    fn emit(&mut self, event: &str, mut fields: Value) {
+        let ts_ms = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .map_or(0, |ts| ts.as_millis());
+        fields["ts_ms"] = json!(ts_ms);
+        fields["event"] = json!(event);
+        // Nowhere better to report a broken stdout, so carry on regardless.
+        let _ = writeln!(self.out, "{fields}");
+        let _ = self.out.flush();
+    }
+
+    /// Logs events from the server that don't otherwise show up as alerts or decisions.
+    fn app_event(&mut self, app_event: &AppEvent) {
+        match app_event {
+            AppEvent::Update(ping) => {
+                let now = SystemTime::now();
+                let due = self.last_stats.get(&ping.node).is_none_or(|last| {
+                    now.duration_since(*last).unwrap_or_default() >= STATS_LOG_INTERVAL
+                });
+                if due {
+                    self.last_stats.insert(ping.node.clone(), now);
+                    self.stats(&ping.node, &ping.stats);
+                }
+            }
+            AppEvent::Subscribe(evt) => self.emit(
+                "subscribe",
+                json!({
+                    "node": evt.node,
+                    "peer": evt.peer.as_ref().map(ToString::to_string),
+                    "name": evt.client_config.name,
+                    "version": evt.client_config.version,
+                }),
+            ),
+            AppEvent::NotificationStream { node, state } => self.emit(
+                "notification_stream",
+                json!({"node": node, "state": format!("{state:?}")}),
+            ),
+            AppEvent::ServerState(ServerState::Listening) => {
+                self.emit("server", json!({"state": "listening"}));
+            }
+            _ => {}
+        }
+    }
+
+    fn stats(&mut self, node: &NodeId, stats: &pb::Statistics) {
+        self.emit(
+            "stats",
+            json!({
+                "node": node,
+                "daemon_version": stats.daemon_version,
+                "uptime": stats.uptime,
+                "rules": stats.rules,
+                "connections": stats.connections,
+                "accepted": stats.accepted,
+                "dropped": stats.dropped,
+                "ignored": stats.ignored,
+                "dns_responses": stats.dns_responses,
+                "rule_hits": stats.rule_hits,
+                "rule_misses": stats.rule_misses,
+            }),
+        );
+    }
+
+    fn decision(&mut self, conn: &ConnectionEvent, rule: &pb::Rule) {
+        let connection = &conn.connection;
+        self.emit(
+            "decision",
+            json!({
+                "node": conn.node,
+                "id": conn.id,
+                "action": rule.action,
+                "duration": rule.duration,
+                "rule": rule.name,
+                "process_path": connection.process_path,
+                "pid": connection.process_id,
+                "uid": connection.user_id,
+                "protocol": connection.protocol,
+                "dst_ip": connection.dst_ip,
+                "dst_host": connection.dst_host,
+                "dst_port": connection.dst_port,
+            }),
+        );
+    }
+
+    fn alert(&mut self, alert: &Alert) {
+        self.emit(
+            "alert",
+            json!({
+                "node": alert.node,
+                "type": format!("{:?}", alert.r#type),
+                "priority": format!("{:?}", alert.priority),
+                "what": format!("{:?}", alert.what),
+                "msg": alert.msg,
+            }),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::event::PingEvent;
+
+    fn lines(log: &Logger<Vec<u8>>) -> Vec<Value> {
+        String::from_utf8(log.out.clone())
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str(line).expect("not JSON"))
+            .collect()
+    }
+
+    /// Test that each log line is a standalone JSON object, and stats are throttled per node.
+    #[test]
+    fn test_logger() {
+        let mut log = Logger::new(Vec::new());
+        let ping = |node: &str| {
+            AppEvent::Update(PingEvent {
+                node: String::from(node),
+                peer: None,
+                stats: pb::Statistics {
+                    uptime: 5,
+                    ..Default::default()
+                },
+            })
+        };
+        log.app_event(&ping("10.0.0.1"));
+        log.app_event(&ping("10.0.0.1"));
+        log.app_event(&ping("10.0.0.2"));
+        log.alert(&Alert {
+            node: None,
+            timestamp: SystemTime::now(),
+            priority: crate::alert::Priority::High,
+            r#type: crate::alert::Type::Error,
+            what: crate::alert::What::Generic,
+            msg: String::from("boom"),
+        });
+
+        let lines = lines(&log);
+        let events: Vec<&str> = lines
+            .iter()
+            .map(|line| line["event"].as_str().unwrap())
+            .collect();
+        assert_eq!(events, vec!["stats", "stats", "alert"]);
+        assert_eq!(lines[0]["node"], "10.0.0.1");
+        assert_eq!(lines[0]["uptime"], 5);
+        assert_eq!(lines[2]["msg"], "boom");
+        assert!(lines[2]["ts_ms"].as_u64().is_some());
+    }
+}
@@ -0,0 +1,232 @@
+//! Connection history, gathered from the recent events each daemon sends with its pings.
+
+use std::collections::{HashSet, VecDeque};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+
+/// Max number of events kept, across all nodes.
+pub const HISTORY_CAPACITY: usize = 1000;
+
+/// An event, and the node that reported it.
+#[derive(Clone, Debug)]
+pub struct HistoryEvent {
+    pub node: NodeId,
+    pub event: pb::Event,
+}
+
+/// Identifies an event across pings. Daemons too old to send `unixnano` fall back to
+/// the event's time, which is only to the second.
+type EventKey = (NodeId, i64, String);
+
+fn key(node: &NodeId, event: &pb::Event) -> EventKey {
+    let time = if event.unixnano == 0 {
+        event.time.clone()
+    } else {
+        String::new()
+    };
+    (node.clone(), event.unixnano, time)
+}
+
+/// Events from all nodes, oldest first, each kept once however many pings carry it.
+#[derive(Debug)]
+pub struct EventHistory {
+    capacity: usize,
+    events: VecDeque<HistoryEvent>,
+    /// Keys of everything in `events`.
+    seen: HashSet<EventKey>,
+}
+
+impl Default for EventHistory {
+    fn default() -> Self {
+        Self::new(HISTORY_CAPACITY)
+    }
+}
+
+impl EventHistory {
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: VecDeque::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// Adds the events from a node's ping that haven't been seen yet, oldest first,
+    /// dropping the oldest events overall once full. Returns how many were added.
+    pub fn extend(&mut self, node: &NodeId, events: &[pb::Event]) -> usize {
+        let mut new: Vec<&pb::Event> = events
+            .iter()
+            .filter(|event| !self.seen.contains(&key(node, event)))
+            .collect();
+        new.sort_by(|a, b| (a.unixnano, &a.time).cmp(&(b.unixnano, &b.time)));
+        new.dedup_by_key(|event| key(node, event));
+        for event in &new {
+            if self.events.len() >= self.capacity
+                && let Some(oldest) = self.events.pop_front()
+            {
+                self.seen.remove(&key(&oldest.node, &oldest.event));
+            }
+            self.seen.insert(key(node, event));
+            self.events.push_back(HistoryEvent {
+                node: node.clone(),
+                event: (*event).clone(),
+            });
+        }
+        new.len()
+    }
+
+    /// Files a node's events under another, dropping any that node already has.
+    pub fn rekey(&mut self, from: &NodeId, to: &NodeId) {
+        let seen = &mut self.seen;
+        self.events.retain_mut(|entry| {
+            if entry.node != *from {
+                return true;
+            }
+            seen.remove(&key(from, &entry.event));
+            entry.node.clone_from(to);
+            seen.insert(key(to, &entry.event))
+        });
+    }
+
+    /// The last `count` events added, oldest first.
+    pub fn newest(&self, count: usize) -> impl Iterator<Item = &pb::Event> {
+        self.events
+            .iter()
+            .skip(self.events.len().saturating_sub(count))
+            .map(|entry| &entry.event)
+    }
+
+    /// A node's events, newest first.
+    pub fn for_node<'a>(&'a self, node: &'a NodeId) -> impl Iterator<Item = &'a pb::Event> {
+        self.events
+            .iter()
+            .rev()
+            .filter(move |entry| entry.node == *node)
+            .map(|entry| &entry.event)
+    }
+
+    /// Number of events kept, across all nodes.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.events.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+}
+
+/// State of the history view.
+#[derive(Clone, Debug, Default)]
+pub struct HistoryView {
+    /// Index of the selected event in the table, newest first.
+    pub cursor: usize,
+    /// Whether the selected event's details are shown over the table.
+    pub detail: bool,
+}
+
+impl HistoryView {
+    /// Handle a key press: up/down to move through the node's `events`, `enter` to show
+    /// or hide the selected event's details, `esc` to hide them. Returns whether the key
+    /// was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent, events: usize) -> bool {
+        match key_event.code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => {
+                self.cursor = (self.cursor + 1).min(events.saturating_sub(1));
+            }
+            KeyCode::Enter => self.detail = !self.detail,
+            KeyCode::Esc => self.detail = false,
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(unixnano: i64, host: &str) -> pb::Event {
+        pb::Event {
+            time: String::from("2025-01-01 00:00:00"),
+            unixnano,
+            connection: Some(pb::Connection {
+                dst_host: String::from(host),
+                ..Default::default()
+            }),
+            rule: None,
+        }
+    }
+
+    fn hosts(history: &EventHistory, node: &NodeId) -> Vec<String> {
+        history
+            .for_node(node)
+            .map(|event| event.connection.as_ref().unwrap().dst_host.clone())
+            .collect()
+    }
+
+    /// Test that events repeated across pings are kept once, per node, newest first.
+    #[test]
+    fn test_extend() {
+        let mut history = EventHistory::new(10);
+        let a = String::from("10.0.0.1");
+        let b = String::from("10.0.0.2");
+        assert_eq!(
+            history.extend(&a, &[event(2, "b.com"), event(1, "a.com")]),
+            2
+        );
+        assert_eq!(
+            history.extend(
+                &a,
+                &[event(2, "b.com"), event(3, "c.com"), event(3, "c.com")]
+            ),
+            1
+        );
+        assert_eq!(history.extend(&b, &[event(1, "a.com")]), 1);
+        assert_eq!(hosts(&history, &a), ["c.com", "b.com", "a.com"]);
+        assert_eq!(hosts(&history, &b), ["a.com"]);
+
+        // Without unixnano, the time tells events apart.
+        let mut old = event(0, "d.com");
+        assert_eq!(history.extend(&b, &[old.clone(), old.clone()]), 1);
+        old.time = String::from("2025-01-01 00:00:01");
+        assert_eq!(history.extend(&b, &[old]), 1);
+        assert_eq!(history.len(), 6);
+    }
+
+    /// Test that a node's events can be moved to another, kept once.
+    #[test]
+    fn test_rekey() {
+        let mut history = EventHistory::new(10);
+        let a = String::from("10.0.0.1");
+        let b = String::from("laptop@10.0.0.1");
+        history.extend(&a, &[event(1, "a.com"), event(2, "b.com")]);
+        history.extend(&b, &[event(2, "b.com")]);
+        history.rekey(&a, &b);
+        assert!(hosts(&history, &a).is_empty());
+        assert_eq!(hosts(&history, &b), ["b.com", "a.com"]);
+        assert_eq!(history.len(), 2);
+        assert_eq!(history.seen.len(), 2);
+    }
+
+    /// Test that the oldest events go once the history is full, and can then be seen again.
+    #[test]
+    fn test_capacity() {
+        let mut history = EventHistory::new(2);
+        let node = String::from("10.0.0.1");
+        history.extend(
+            &node,
+            &[event(1, "a.com"), event(2, "b.com"), event(3, "c.com")],
+        );
+        assert_eq!(hosts(&history, &node), ["c.com", "b.com"]);
+        assert_eq!(history.extend(&node, &[event(1, "a.com")]), 1);
+        assert_eq!(hosts(&history, &node), ["a.com", "c.com"]);
+        assert_eq!(history.seen.len(), 2);
+    }
+}
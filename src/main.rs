@@ -1,33 +1,103 @@
 #![warn(clippy::pedantic)]
 
+pub mod admin;
 pub mod alert;
 pub mod app;
+pub mod audit;
+pub mod breakdown;
 pub mod cli;
 pub mod constants;
 pub mod event;
+pub mod firewall;
+pub mod firewall_editor;
+pub mod form;
+pub mod headless;
+pub mod history;
+pub mod nft;
+pub mod node;
+pub mod notification;
 pub mod opensnitch_json;
 pub mod opensnitch_proto;
 pub mod operator_util;
+pub mod policy;
+pub mod rates;
+pub mod rule_editor;
+pub mod rules;
 pub mod serde_impl;
 pub mod server;
+pub mod settings;
+pub mod sockets;
+pub mod store;
+pub mod task;
 pub mod ui;
 
+use color_eyre::eyre::eyre;
+
 /// Main.
 /// # Errors
-/// Returns an error if there was bad input at init.
+/// Returns an error if there was bad input at init, or the server couldn't bind.
 #[tokio::main]
 async fn main() -> color_eyre::Result<()> {
-    let matches = cli::setup().get_matches();
+    let root_matches = cli::setup().get_matches();
+    // Shared flags may come before or after the subcommand, and the subcommand sees both.
+    let serve_matches = root_matches.subcommand_matches("serve");
+    let matches = serve_matches.unwrap_or(&root_matches);
 
     color_eyre::install()?;
+    let tls = matches
+        .get_one::<std::path::PathBuf>("tls_cert")
+        .map(|cert| server::TlsOptions {
+            cert: cert.clone(),
+            key: matches
+                .get_one::<std::path::PathBuf>("tls_key")
+                .unwrap()
+                .clone(),
+            client_ca: matches
+                .get_one::<std::path::PathBuf>("tls_client_ca")
+                .cloned(),
+            client_auth_optional: matches.get_flag("tls_client_auth_optional"),
+        });
+    let store = if matches.get_flag("no_store") {
+        app::StoreLocation::Off
+    } else {
+        matches
+            .get_one::<std::path::PathBuf>("data_dir")
+            .cloned()
+            .or_else(store::default_dir)
+            .map_or(app::StoreLocation::NoDataDir, |dir| {
+                app::StoreLocation::File(dir.join(store::STORE_FILE))
+            })
+    };
+    let options = app::AppOptions {
+        bind: matches.get_one::<String>("ip_port").unwrap().clone(),
+        tls,
+        allow_insecure_bind: matches.get_flag("allow_insecure_bind"),
+        default_action: matches.get_one::<String>("default_action").unwrap().clone(),
+        exit_action: matches.get_one::<String>("exit_action").cloned(),
+        temp_rule_lifetime: matches
+            .get_one::<String>("temp_rule_lifetime")
+            .unwrap()
+            .clone(),
+        dispo_seconds: *matches.get_one::<u64>("dispo_seconds").unwrap(),
+        stale_seconds: *matches.get_one::<u64>("stale_seconds").unwrap(),
+        store,
+    };
+    // Fail before taking over the terminal, so the reason stays readable.
+    let mut app = app::App::new(&options).map_err(|err| eyre!("Initialization failed: {err}"))?;
+    app.server.bind().map_err(|err| eyre!(err))?;
+
+    if serve_matches.is_some_and(|serve| serve.get_flag("headless")) {
+        let default_action = constants::DefaultAction::new(&options.default_action)
+            .map_err(|err| eyre!("Invalid default action: {}", err.input))?;
+        let fallback = (default_action.as_action(), constants::Duration::Once);
+        let policy = match matches.get_one::<std::path::PathBuf>("policy") {
+            Some(path) => policy::Policy::load(path, fallback).map_err(|err| eyre!(err))?,
+            None => policy::Policy::new(fallback),
+        };
+        return headless::run(app, policy).await;
+    }
+
     let terminal = ratatui::init();
-    let app = app::App::new(
-        matches.get_one::<String>("ip_port").unwrap(),
-        matches.get_one::<String>("default_action").unwrap(),
-        matches.get_one::<String>("temp_rule_lifetime").unwrap(),
-        matches.get_one::<u64>("dispo_seconds").unwrap(),
-    )
-    .expect("Initialization failed: ");
     let result = app.run(terminal).await;
     ratatui::restore();
     result
@@ -0,0 +1,532 @@
+//! The daemon's system firewall as an `nft -f` ruleset, and back.
+//!
+//! What `nft` has no syntax for is kept in comments: the firewall's version, the daemon's
+//! own chain types, and each rule's UUID, position and whether it's enabled.
+//! Disabled rules are commented out.
+//! Reading a ruleset back groups chains by table, and reads the statements the firewall
+//! rule editor builds. The iptables-only rule parameters aren't kept.
+
+use std::fmt::Write;
+
+use crate::firewall;
+use crate::opensnitch_proto::pb;
+
+const HEADER: &str = "# SysFirewall";
+const CHAIN: &str = "# chain";
+const RULE: &str = "# rule";
+
+/// The firewall as an `nft` ruleset, one table per family and table name, in the order
+/// they first appear.
+#[must_use]
+pub fn render(firewall: &pb::SysFirewall) -> String {
+    let chains = firewall::chains(firewall);
+    let mut tables: Vec<(&str, &str)> = Vec::new();
+    for (_, chain) in &chains {
+        let table = (chain.family.as_str(), chain.table.as_str());
+        if !tables.contains(&table) {
+            tables.push(table);
+        }
+    }
+
+    let mut out = format!(
+        "{HEADER} version={} enabled={}\n",
+        firewall.version, firewall.enabled
+    );
+    for (family, table) in tables {
+        let _ = writeln!(out, "\ntable {family} {table} {{");
+        let in_table = chains
+            .iter()
+            .filter(|(_, chain)| chain.family == family && chain.table == table);
+        for (idx, (_, chain)) in in_table.enumerate() {
+            if idx > 0 {
+                out.push('\n');
+            }
+            render_chain(chain, &mut out);
+        }
+        out.push_str("}\n");
+    }
+    out
+}
+
+fn render_chain(chain: &pb::FwChain, out: &mut String) {
+    let _ = writeln!(out, "\tchain {} {{", chain.name);
+    if !chain.hook.is_empty() {
+        let (r#type, priority) = nft_chain_type(chain);
+        let _ = write!(
+            out,
+            "\t\ttype {type} hook {} priority {priority};",
+            chain.hook
+        );
+        if !chain.policy.is_empty() {
+            let _ = write!(out, " policy {};", chain.policy);
+        }
+        out.push('\n');
+        if r#type != chain.r#type || priority != chain.priority {
+            let _ = writeln!(
+                out,
+                "\t\t{CHAIN} type={} priority={}",
+                chain.r#type, chain.priority
+            );
+        }
+    }
+    for rule in &chain.rules {
+        let _ = writeln!(
+            out,
+            "\t\t{RULE} uuid={} position={} enabled={}",
+            rule.uuid, rule.position, rule.enabled
+        );
+        let disabled = if rule.enabled { "" } else { "# " };
+        let _ = writeln!(out, "\t\t{disabled}{}", render_rule(rule));
+    }
+    out.push_str("\t}\n");
+}
+
+/// The `nft` type and priority of a base chain. The daemon has chain types of its own,
+/// and works out a priority for chains that don't have one.
+fn nft_chain_type(chain: &pb::FwChain) -> (&str, &str) {
+    let r#type = match chain.r#type.as_str() {
+        "mangle" if chain.hook == "output" => "route",
+        "" | "mangle" | "conntrack" => "filter",
+        "natdest" | "natsource" => "nat",
+        other => other,
+    };
+    let priority = match (chain.priority.as_str(), chain.r#type.as_str()) {
+        ("", "mangle") => "mangle",
+        ("", "natdest") => "dstnat",
+        ("", "natsource") => "srcnat",
+        ("", "conntrack") => "-200",
+        ("", _) => "filter",
+        (priority, _) => priority,
+    };
+    (r#type, priority)
+}
+
+/// A rule as one line of `nft` syntax, e.g. `tcp dport == 22 accept comment "ssh"`.
+/// Double quotes in the description become single ones, as `nft` can't take them.
+#[must_use]
+pub fn render_rule(rule: &pb::FwRule) -> String {
+    let mut parts: Vec<String> = rule
+        .expressions
+        .iter()
+        .filter_map(|expression| expression.statement.as_ref())
+        .map(firewall::statement_nft)
+        .collect();
+    parts.push(firewall::rule_target(rule));
+    if !rule.description.is_empty() {
+        parts.push(format!(
+            "comment \"{}\"",
+            rule.description.replace('"', "'")
+        ));
+    }
+    parts.join(" ")
+}
+
+/// Reads back a ruleset written by [`render`].
+/// # Errors
+/// Returns the first line that can't be read, and why.
+pub fn parse(text: &str) -> Result<pb::SysFirewall, String> {
+    let mut firewall = pb::SysFirewall::default();
+    // Table being read, as its family and name.
+    let mut table: Option<(String, String)> = None;
+    let mut chain: Option<pb::FwChain> = None;
+    // UUID, position and enabled flag of the rule on the next line.
+    let mut meta: Option<(String, u64, bool)> = None;
+
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        let error = |err: String| format!("line {}: {err}", number + 1);
+        if let Some(header) = line.strip_prefix(HEADER) {
+            let fields = key_values(header);
+            firewall.version = fields("version").parse().unwrap_or_default();
+            firewall.enabled = fields("enabled") == "true";
+        } else if let Some(rule_meta) = line.strip_prefix(RULE) {
+            let fields = key_values(rule_meta);
+            meta = Some((
+                fields("uuid"),
+                fields("position").parse().unwrap_or_default(),
+                fields("enabled") != "false",
+            ));
+        } else if let Some(chain_meta) = line.strip_prefix(CHAIN) {
+            let chain = chain
+                .as_mut()
+                .ok_or_else(|| error(String::from("chain type outside of a chain")))?;
+            let fields = key_values(chain_meta);
+            chain.r#type = fields("type");
+            chain.priority = fields("priority");
+        } else if line.is_empty() || (line.starts_with('#') && meta.is_none()) {
+            // Nothing to read from blank lines and other comments.
+        } else if let Some(rest) = line.strip_prefix("table ") {
+            let words: Vec<&str> = rest.trim_end_matches('{').split_whitespace().collect();
+            let [family, name] = words[..] else {
+                return Err(error(String::from("expected table <family> <name> {")));
+            };
+            table = Some((String::from(family), String::from(name)));
+        } else if let Some(rest) = line.strip_prefix("chain ") {
+            let (family, table) = table
+                .clone()
+                .ok_or_else(|| error(String::from("chain outside of a table")))?;
+            chain = Some(pb::FwChain {
+                name: String::from(rest.trim_end_matches('{').trim()),
+                table,
+                family,
+                ..Default::default()
+            });
+        } else if line == "}" {
+            match chain.take() {
+                Some(chain) => push_chain(&mut firewall, chain),
+                None => table = None,
+            }
+        } else if let Some(chain) = chain.as_mut() {
+            if let Some(hook) = line.strip_prefix("type ") {
+                parse_hook(hook, chain).map_err(error)?;
+            } else {
+                let (uuid, position, enabled) =
+                    meta.take().unwrap_or_else(|| (String::new(), 0, true));
+                let line = if enabled {
+                    line
+                } else {
+                    line.trim_start_matches('#').trim_start()
+                };
+                let mut rule = parse_rule(line).map_err(error)?;
+                rule.uuid = uuid;
+                rule.position = position;
+                rule.enabled = enabled;
+                chain.rules.push(rule);
+            }
+        } else {
+            return Err(error(format!("unexpected {line}")));
+        }
+    }
+    Ok(firewall)
+}
+
+/// Looks up `key=value` words.
+fn key_values(text: &str) -> impl Fn(&str) -> String {
+    let pairs: Vec<(String, String)> = text
+        .split_whitespace()
+        .filter_map(|word| word.split_once('='))
+        .map(|(key, value)| (String::from(key), String::from(value)))
+        .collect();
+    move |key| {
+        pairs
+            .iter()
+            .find(|(known, _)| known == key)
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    }
+}
+
+/// Chains of the same table are grouped together, as they're written.
+fn push_chain(firewall: &mut pb::SysFirewall, chain: pb::FwChain) {
+    let group = firewall.system_rules.iter_mut().find(|group| {
+        group
+            .chains
+            .first()
+            .is_some_and(|first| first.family == chain.family && first.table == chain.table)
+    });
+    match group {
+        Some(group) => group.chains.push(chain),
+        None => firewall.system_rules.push(pb::FwChains {
+            rule: None,
+            chains: vec![chain],
+        }),
+    }
+}
+
+/// Reads `filter hook input priority 0; policy accept;`, after its `type`.
+fn parse_hook(text: &str, chain: &mut pb::FwChain) -> Result<(), String> {
+    let words: Vec<&str> = text
+        .split(|c: char| c.is_whitespace() || c == ';')
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut words = words.iter();
+    chain.r#type = String::from(*words.next().ok_or("chain type missing")?);
+    while let Some(word) = words.next() {
+        let value = String::from(*words.next().ok_or(format!("{word} missing its value"))?);
+        match *word {
+            "hook" => chain.hook = value,
+            "priority" => chain.priority = value,
+            "policy" => chain.policy = value,
+            _ => return Err(format!("unknown chain setting {word}")),
+        }
+    }
+    Ok(())
+}
+
+/// Reads a rule written by [`render_rule`].
+/// # Errors
+/// Returns the first part of the rule that can't be read.
+pub fn parse_rule(line: &str) -> Result<pb::FwRule, String> {
+    let words = split_words(line)?;
+    let mut words = words.iter().map(String::as_str).peekable();
+    let mut rule = pb::FwRule::default();
+    while let Some(word) = words.next() {
+        if word == "comment" {
+            let comment = words.next().ok_or("comment missing its text")?;
+            rule.description = String::from(comment.trim_matches('"'));
+        } else if firewall::TARGETS.contains(&word) {
+            rule.target = String::from(word);
+            let mut parameters = Vec::new();
+            while let Some(parameter) = words.next_if(|next| *next != "comment") {
+                parameters.push(parameter);
+            }
+            rule.target_parameters = parameters.join(" ");
+        } else if !rule.target.is_empty() {
+            return Err(format!("{word} after the rule's target"));
+        } else {
+            let keys = firewall::statement_keys(word)
+                .ok_or_else(|| format!("can't read back statement {word}"))?;
+            let key = if keys.is_empty() {
+                None
+            } else {
+                Some(
+                    words
+                        .next()
+                        .ok_or(format!("{word} missing what to match"))?,
+                )
+            };
+            let op = words
+                .next_if(|next| firewall::COMPARISONS.contains(next))
+                .unwrap_or_default();
+            let value = words.next().ok_or(format!("{word} missing a value"))?;
+            let values = match key {
+                None => vec![(value, "")],
+                Some(key) if word == "ct" && key == "state" => {
+                    value.split(',').map(|state| (key, state)).collect()
+                }
+                Some(key) => vec![(key, value)],
+            };
+            rule.expressions.push(pb::Expressions {
+                statement: Some(pb::Statement {
+                    op: String::from(op),
+                    name: String::from(word),
+                    values: values
+                        .into_iter()
+                        .map(|(key, value)| pb::StatementValues {
+                            key: String::from(key),
+                            value: String::from(value),
+                        })
+                        .collect(),
+                }),
+            });
+        }
+    }
+    if rule.target.is_empty() {
+        return Err(String::from("rule without a target"));
+    }
+    Ok(rule)
+}
+
+/// Splits on whitespace, keeping double quoted text, quotes included, as one word.
+fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word = String::new();
+    let mut quoted = false;
+    for c in line.chars() {
+        match c {
+            '"' => {
+                quoted = !quoted;
+                word.push(c);
+            }
+            c if c.is_whitespace() && !quoted => {
+                if !word.is_empty() {
+                    words.push(std::mem::take(&mut word));
+                }
+            }
+            c => word.push(c),
+        }
+    }
+    if quoted {
+        return Err(String::from("unterminated quote"));
+    }
+    if !word.is_empty() {
+        words.push(word);
+    }
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn statement(op: &str, name: &str, values: &[(&str, &str)]) -> pb::Expressions {
+        pb::Expressions {
+            statement: Some(pb::Statement {
+                op: String::from(op),
+                name: String::from(name),
+                values: values
+                    .iter()
+                    .map(|(key, value)| pb::StatementValues {
+                        key: String::from(*key),
+                        value: String::from(*value),
+                    })
+                    .collect(),
+            }),
+        }
+    }
+
+    fn rule(uuid: &str, position: u64, enabled: bool) -> pb::FwRule {
+        pb::FwRule {
+            uuid: String::from(uuid),
+            position,
+            enabled,
+            target: String::from("accept"),
+            ..Default::default()
+        }
+    }
+
+    /// Chains like the daemon's default system firewall, with a rule of each kind the
+    /// rule editor builds.
+    fn firewall() -> pb::SysFirewall {
+        let chain = |table: &str, name: &str, hook: &str, priority: &str, rules| pb::FwChain {
+            name: String::from(name),
+            table: String::from(table),
+            family: String::from("inet"),
+            priority: String::from(priority),
+            r#type: String::from(if table == "mangle" {
+                "mangle"
+            } else {
+                "filter"
+            }),
+            hook: String::from(hook),
+            policy: String::from("accept"),
+            rules,
+        };
+        pb::SysFirewall {
+            enabled: true,
+            version: 1,
+            system_rules: vec![
+                pb::FwChains {
+                    rule: None,
+                    chains: vec![
+                        chain(
+                            "filter",
+                            "input",
+                            "input",
+                            "0",
+                            vec![
+                                pb::FwRule {
+                                    description: String::from("ssh from the \"lan\""),
+                                    expressions: vec![
+                                        statement("==", "tcp", &[("dport", "22,2222")]),
+                                        statement("", "ip", &[("saddr", "192.168.0.0/16")]),
+                                    ],
+                                    ..rule("a1", 0, true)
+                                },
+                                pb::FwRule {
+                                    expressions: vec![statement("!=", "iifname", &[("lo", "")])],
+                                    target: String::from("reject"),
+                                    target_parameters: String::from("with tcp reset"),
+                                    ..rule("a2", 1, false)
+                                },
+                            ],
+                        ),
+                        chain("filter", "forward", "forward", "0", vec![]),
+                    ],
+                },
+                pb::FwChains {
+                    rule: None,
+                    chains: vec![chain(
+                        "mangle",
+                        "output",
+                        "output",
+                        "",
+                        vec![
+                            pb::FwRule {
+                                expressions: vec![statement(
+                                    "",
+                                    "ct",
+                                    &[("state", "related"), ("state", "established")],
+                                )],
+                                ..rule("b1", 0, true)
+                            },
+                            pb::FwRule {
+                                expressions: vec![
+                                    statement("==", "meta", &[("l4proto", "udp")]),
+                                    statement(">=", "udp", &[("dport", "1024")]),
+                                    statement("==", "ip6", &[("daddr", "fd00::/8")]),
+                                ],
+                                target: String::from("jump"),
+                                target_parameters: String::from("audit"),
+                                ..rule("b2", 1, true)
+                            },
+                        ],
+                    )],
+                },
+            ],
+        }
+    }
+
+    /// Test the ruleset `nft` is given.
+    #[test]
+    fn test_render() {
+        let text = render(&firewall());
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines[0], "# SysFirewall version=1 enabled=true");
+        assert_eq!(lines[2], "table inet filter {");
+        assert_eq!(lines[3], "\tchain input {");
+        assert_eq!(
+            lines[4],
+            "\t\ttype filter hook input priority 0; policy accept;"
+        );
+        assert_eq!(
+            lines[6],
+            "\t\ttcp dport == 22,2222 ip saddr 192.168.0.0/16 accept comment \"ssh from the 'lan'\""
+        );
+        assert_eq!(lines[8], "\t\t# iifname != lo reject with tcp reset");
+        assert!(text.contains("\t\tct state related,established accept\n"));
+        assert!(text.contains(
+            "\t\tmeta l4proto == udp udp dport >= 1024 ip6 daddr == fd00::/8 jump audit\n"
+        ));
+        assert!(text.contains(
+            "\t\ttype route hook output priority mangle; policy accept;\n\t\t# chain type=mangle priority=\n"
+        ));
+        assert_eq!(text.matches("table ").count(), 2);
+    }
+
+    /// Test that a rendered ruleset reads back as the firewall it came from.
+    #[test]
+    fn test_round_trip() {
+        let mut expected = firewall();
+        expected.system_rules[0].chains[0].rules[0].description =
+            String::from("ssh from the 'lan'");
+        assert_eq!(parse(&render(&firewall())), Ok(expected.clone()));
+
+        // Tables come back grouped, however they were sent.
+        let mut mixed = expected.clone();
+        let forward = mixed.system_rules[0].chains.pop().unwrap();
+        mixed.system_rules.push(pb::FwChains {
+            rule: None,
+            chains: vec![forward],
+        });
+        assert_eq!(parse(&render(&mixed)), Ok(expected));
+
+        let empty = pb::SysFirewall::default();
+        assert_eq!(parse(&render(&empty)), Ok(empty));
+    }
+
+    /// Test that what can't be read back is reported with its line.
+    #[test]
+    fn test_parse_errors() {
+        assert_eq!(
+            parse("table inet filter {\n\tchain input {\n\t\tcounter accept\n\t}\n}"),
+            Err(String::from("line 3: can't read back statement counter"))
+        );
+        assert!(parse("chain input {").is_err());
+        assert_eq!(
+            parse_rule("tcp dport 22"),
+            Err(String::from("rule without a target"))
+        );
+        assert!(parse_rule("accept comment \"open").is_err());
+        assert_eq!(
+            parse_rule("drop comment \"x\" tcp dport 22"),
+            Err(String::from("tcp after the rule's target"))
+        );
+        assert_eq!(
+            parse_rule("reject with icmp type host-unreachable")
+                .unwrap()
+                .target_parameters,
+            "with icmp type host-unreachable"
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::event::{ConnectionEvent, Peer};
+use crate::opensnitch_json::OpenSnitchDaemonConfig;
+use crate::opensnitch_proto::pb;
+use crate::rates::RateSeries;
+
+/// Identity of a daemon, stable across reconnects from the same host.
+/// Derived from the daemon's name and address once it subscribes, and from its address
+/// alone before then, see [`Peer::named_node_id`] and [`Peer::node_id`].
+pub type NodeId = String;
+
+/// State of a node's notifications stream, as seen by the server.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum StreamState {
+    /// The daemon hasn't opened the stream yet.
+    Unknown,
+    /// The stream is open.
+    Open,
+    /// The daemon closed the stream or it failed.
+    Closed,
+}
+
+/// How alive a node looks.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Health {
+    /// Pinging on schedule.
+    Connected,
+    /// Missed pings for longer than the staleness threshold.
+    Stale,
+    /// Notifications stream closed, or missed pings for several thresholds in a row.
+    Disconnected,
+}
+
+impl std::fmt::Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Health::Connected => write!(f, "connected"),
+            Health::Stale => write!(f, "stale"),
+            Health::Disconnected => write!(f, "disconnected"),
+        }
+    }
+}
+
+/// Number of staleness thresholds without a ping before a node counts as disconnected.
+const DISCONNECTED_THRESHOLDS: u32 = 3;
+
+/// Everything the app knows about one connected (or previously connected) daemon.
+#[derive(Debug)]
+pub struct Node {
+    /// Key this node is tracked under.
+    pub id: NodeId,
+    /// Daemon-reported name (`ClientConfig.name`), known once it has subscribed.
+    pub name: Option<String>,
+    /// Daemon-reported version (`ClientConfig.version`), known once it has subscribed.
+    pub version: Option<String>,
+    /// Daemon configuration as of its last subscription, if it could be parsed.
+    pub config: Option<OpenSnitchDaemonConfig>,
+    /// Daemon configuration JSON as it was sent, which settings edits are merged into.
+    pub config_json: Option<String>,
+    /// Whether the daemon's system firewall is up, as of its last subscription or toggle.
+    pub firewall_running: Option<bool>,
+    /// Daemon's system firewall chains and rules, as of its last subscription.
+    pub system_firewall: Option<pb::SysFirewall>,
+    /// Daemon's log level (`ClientConfig.logLevel`), as of its last subscription or change.
+    pub log_level: Option<u32>,
+    /// Whether the daemon is intercepting connections. Only known once set from here.
+    pub interception: Option<bool>,
+    /// Daemon's rules as of its last subscription, plus changes made from here since.
+    pub rules: Vec<pb::Rule>,
+    /// Most recent peer description seen for this node.
+    pub peer: Option<Peer>,
+    /// Rx Pings.
+    pub rx_pings: u64,
+    /// When the last Ping arrived.
+    pub last_ping: Option<SystemTime>,
+    /// State of the notifications stream.
+    pub stream_state: StreamState,
+    /// Health as of the last check, to spot transitions.
+    pub last_health: Health,
+    /// Latest stats to present to UI.
+    pub stats: Option<pb::Statistics>,
+    /// Counter rates between recent pings.
+    pub rates: RateSeries,
+    /// Connections awaiting a rule determination, in arrival order.
+    pub pending_connections: VecDeque<ConnectionEvent>,
+    /// Index into `pending_connections` of the prompt being looked at.
+    pub prompt_cursor: usize,
+}
+
+impl Node {
+    #[must_use]
+    pub fn new(id: NodeId) -> Self {
+        Self {
+            id,
+            name: None,
+            version: None,
+            config: None,
+            config_json: None,
+            firewall_running: None,
+            interception: None,
+            log_level: None,
+            system_firewall: None,
+            rules: Vec::new(),
+            peer: None,
+            rx_pings: 0,
+            last_ping: None,
+            stream_state: StreamState::Unknown,
+            last_health: Health::Connected,
+            stats: None,
+            rates: RateSeries::default(),
+            pending_connections: VecDeque::new(),
+            prompt_cursor: 0,
+        }
+    }
+
+    /// How alive this node looks at `now`.
+    /// A node that has never pinged counts as connected until it's been quiet for a while,
+    /// since daemons subscribe before their first ping.
+    #[must_use]
+    pub fn health(&self, now: SystemTime, stale_threshold: Duration) -> Health {
+        if self.stream_state == StreamState::Closed {
+            return Health::Disconnected;
+        }
+        let Some(last_ping) = self.last_ping else {
+            return Health::Connected;
+        };
+        let silence = now.duration_since(last_ping).unwrap_or_default();
+        if silence >= stale_threshold * DISCONNECTED_THRESHOLDS {
+            Health::Disconnected
+        } else if silence >= stale_threshold {
+            Health::Stale
+        } else {
+            Health::Connected
+        }
+    }
+
+    /// The prompt being looked at, if any are pending.
+    #[must_use]
+    pub fn current_connection(&self) -> Option<&ConnectionEvent> {
+        self.pending_connections.get(self.prompt_cursor)
+    }
+
+    /// Queue a new prompt behind the others.
+    pub fn push_connection(&mut self, evt: ConnectionEvent) {
+        self.pending_connections.push_back(evt);
+    }
+
+    /// Drop the prompt for an `AskRule` request, returning it if it was pending.
+    pub fn remove_connection(&mut self, id: u64) -> Option<ConnectionEvent> {
+        let idx = self
+            .pending_connections
+            .iter()
+            .position(|conn| conn.id == id)?;
+        // Keep looking at the same prompt if an earlier one goes away.
+        if idx < self.prompt_cursor {
+            self.prompt_cursor -= 1;
+        }
+        let evt = self.pending_connections.remove(idx);
+        self.clamp_prompt_cursor();
+        evt
+    }
+
+    /// Drop prompts whose `AskRule` call has timed out on the daemon side.
+    /// Returns whether any were dropped.
+    pub fn expire_connections(&mut self, now: SystemTime) -> bool {
+        let expired: Vec<u64> = self
+            .pending_connections
+            .iter()
+            .filter(|conn| now >= conn.expiry_ts)
+            .map(|conn| conn.id)
+            .collect();
+        for id in &expired {
+            self.remove_connection(*id);
+        }
+        !expired.is_empty()
+    }
+
+    /// Move through the prompt queue, stopping at either end.
+    pub fn move_prompt_cursor(&mut self, forward: bool) {
+        self.prompt_cursor = if forward {
+            self.prompt_cursor.saturating_add(1)
+        } else {
+            self.prompt_cursor.saturating_sub(1)
+        };
+        self.clamp_prompt_cursor();
+    }
+
+    fn clamp_prompt_cursor(&mut self) {
+        self.prompt_cursor = std::cmp::min(
+            self.prompt_cursor,
+            self.pending_connections.len().saturating_sub(1),
+        );
+    }
+
+    /// Fold in what was learnt about this daemon under another key before it subscribed:
+    /// its pings, stats and rates, and the prompts it's waiting on. Stats and rates only
+    /// replace this node's if they're more recent.
+    pub fn absorb(&mut self, early: Node) {
+        self.rx_pings = self.rx_pings.saturating_add(early.rx_pings);
+        if early.last_ping > self.last_ping {
+            self.last_ping = early.last_ping;
+            self.last_health = early.last_health;
+            self.stats = early.stats;
+            self.rates = early.rates;
+        }
+        self.pending_connections
+            .extend(early.pending_connections.into_iter().map(|mut conn| {
+                conn.node.clone_from(&self.id);
+                conn
+            }));
+    }
+
+    /// Add a rule, replacing any rule of the same name as the daemon does.
+    pub fn upsert_rule(&mut self, rule: pb::Rule) {
+        match self.rules.iter_mut().find(|other| other.name == rule.name) {
+            Some(other) => *other = rule,
+            None => self.rules.push(rule),
+        }
+    }
+
+    /// Drop a rule by name, returning it if it was known.
+    pub fn remove_rule(&mut self, name: &str) -> Option<pb::Rule> {
+        let idx = self.rules.iter().position(|rule| rule.name == name)?;
+        Some(self.rules.remove(idx))
+    }
+
+    /// Human-friendly label, preferring the daemon's own name over its address.
+    #[must_use]
+    pub fn label(&self) -> String {
+        let Some(name) = &self.name else {
+            return self.id.clone();
+        };
+        match self
+            .id
+            .strip_prefix(name.as_str())
+            .and_then(|rest| rest.strip_prefix('@'))
+        {
+            Some(addr) => format!("{name} ({addr})"),
+            None if *name == self.id => self.id.clone(),
+            None => format!("{name} ({})", self.id),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Test health transitions as pings go missing and the stream closes.
+    #[test]
+    fn test_health() {
+        let threshold = Duration::from_secs(10);
+        let now = SystemTime::now();
+        let mut node = Node::new(String::from("10.0.0.1"));
+        assert_eq!(node.health(now, threshold), Health::Connected);
+
+        node.last_ping = Some(now - Duration::from_secs(9));
+        assert_eq!(node.health(now, threshold), Health::Connected);
+        node.last_ping = Some(now - Duration::from_secs(10));
+        assert_eq!(node.health(now, threshold), Health::Stale);
+        node.last_ping = Some(now - Duration::from_secs(30));
+        assert_eq!(node.health(now, threshold), Health::Disconnected);
+
+        node.last_ping = Some(now);
+        node.stream_state = StreamState::Closed;
+        assert_eq!(node.health(now, threshold), Health::Disconnected);
+    }
+
+    /// Test that rules are keyed by name.
+    #[test]
+    fn test_rules() {
+        let rule = |name: &str, action: &str| pb::Rule {
+            name: String::from(name),
+            action: String::from(action),
+            ..Default::default()
+        };
+        let mut node = Node::new(String::from("10.0.0.1"));
+        node.upsert_rule(rule("curl", "allow"));
+        node.upsert_rule(rule("wget", "allow"));
+        node.upsert_rule(rule("curl", "deny"));
+        assert_eq!(node.rules.len(), 2);
+        assert_eq!(node.rules[0].action, "deny");
+
+        assert!(node.remove_rule("curl").is_some());
+        assert!(node.remove_rule("curl").is_none());
+        assert_eq!(node.rules.len(), 1);
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+
+/// How long to wait on a daemon's `NotificationReply` before giving up on it.
+pub const REPLY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Max number of settled notifications kept around for the log view. Pending ones
+/// are always kept, or their replies would go unmatched.
+const LOG_CAPACITY: usize = 256;
+
+/// Where a notification stands with the daemon it was sent to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// Sent, awaiting a reply.
+    Pending,
+    /// Daemon replied OK, with whatever data it attached.
+    Ok(String),
+    /// Daemon replied with an error.
+    Error(String),
+    /// Daemon didn't reply within [`REPLY_TIMEOUT`].
+    Timeout,
+    /// Never made it onto the node's notifications stream.
+    SendFailed(String),
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Pending => write!(f, "pending"),
+            Outcome::Ok(data) if data.is_empty() => write!(f, "ok"),
+            Outcome::Ok(data) => write!(f, "ok: {data}"),
+            Outcome::Error(data) => write!(f, "error: {data}"),
+            Outcome::Timeout => write!(f, "timed out"),
+            Outcome::SendFailed(err) => write!(f, "send failed: {err}"),
+        }
+    }
+}
+
+/// A notification sent to a daemon, and what became of it.
+#[derive(Clone, Debug)]
+pub struct Record {
+    /// Node the notification was sent to.
+    pub node: NodeId,
+    /// The notification as sent.
+    pub notification: pb::Notification,
+    /// When it was sent.
+    pub sent_at: SystemTime,
+    /// When the outcome was settled, if it has been.
+    pub settled_at: Option<SystemTime>,
+    /// Where it stands.
+    pub outcome: Outcome,
+}
+
+impl Record {
+    /// Daemon command this notification carries.
+    #[must_use]
+    pub fn action(&self) -> pb::Action {
+        self.notification.r#type()
+    }
+}
+
+/// Assigns notification IDs and matches daemon replies back to what was sent.
+#[derive(Debug)]
+pub struct NotificationTracker {
+    /// ID for the next notification. IDs are unique across all nodes.
+    next_id: u64,
+    /// Everything sent recently, oldest first. Doubles as the in-flight table,
+    /// since in-flight entries are the ones still [`Outcome::Pending`].
+    log: VecDeque<Record>,
+}
+
+impl Default for NotificationTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl NotificationTracker {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            // Start at 1, the daemon may treat a zero ID as unset.
+            next_id: 1,
+            log: VecDeque::new(),
+        }
+    }
+
+    /// Builds a notification with a fresh ID and starts tracking it as pending.
+    pub fn track(
+        &mut self,
+        node: &NodeId,
+        action: pb::Action,
+        data: String,
+        rules: Vec<pb::Rule>,
+        sys_firewall: Option<pb::SysFirewall>,
+    ) -> pb::Notification {
+        let notification = pb::Notification {
+            id: self.next_id,
+            client_name: String::default(),
+            server_name: String::default(),
+            r#type: action.into(),
+            data,
+            rules,
+            sys_firewall,
+        };
+        self.next_id += 1;
+
+        if self.log.len() >= LOG_CAPACITY
+            && let Some(oldest_settled) = self
+                .log
+                .iter()
+                .position(|record| record.outcome != Outcome::Pending)
+        {
+            self.log.remove(oldest_settled);
+        }
+        self.log.push_back(Record {
+            node: node.clone(),
+            notification: notification.clone(),
+            sent_at: SystemTime::now(),
+            settled_at: None,
+            outcome: Outcome::Pending,
+        });
+        notification
+    }
+
+    /// Settles a notification that couldn't be handed to its node's stream.
+    pub fn send_failed(&mut self, id: u64, err: String) {
+        self.settle(id, Outcome::SendFailed(err));
+    }
+
+    /// Matches a daemon's reply to the notification it answers.
+    /// Returns the settled record, or none if the ID is unknown or was already settled.
+    pub fn reply(&mut self, node: &NodeId, reply: &pb::NotificationReply) -> Option<&Record> {
+        // IDs are unique, but make sure one node can't settle another's notification.
+        if !self
+            .log
+            .iter()
+            .any(|record| record.notification.id == reply.id && record.node == *node)
+        {
+            return None;
+        }
+        let outcome = match reply.code() {
+            pb::NotificationReplyCode::Ok => Outcome::Ok(reply.data.clone()),
+            pb::NotificationReplyCode::Error => Outcome::Error(reply.data.clone()),
+        };
+        self.settle(reply.id, outcome)
+    }
+
+    /// Times out notifications that have waited too long for a reply.
+    /// Returns the records that timed out.
+    pub fn expire(&mut self, now: SystemTime) -> Vec<Record> {
+        let mut expired = Vec::new();
+        for record in &mut self.log {
+            let waited = now.duration_since(record.sent_at).unwrap_or_default();
+            if record.outcome == Outcome::Pending && waited >= REPLY_TIMEOUT {
+                record.outcome = Outcome::Timeout;
+                record.settled_at = Some(now);
+                expired.push(record.clone());
+            }
+        }
+        expired
+    }
+
+    /// A recent notification, by ID.
+    #[must_use]
+    pub fn get(&self, id: u64) -> Option<&Record> {
+        self.log.iter().find(|record| record.notification.id == id)
+    }
+
+    /// Whether a node has yet to reply to a notification carrying `action`.
+    #[must_use]
+    pub fn pending(&self, node: &NodeId, action: pb::Action) -> bool {
+        self.log_for(node)
+            .any(|record| record.action() == action && record.outcome == Outcome::Pending)
+    }
+
+    /// Recent notifications sent to a node, oldest first.
+    #[must_use]
+    pub fn log_for<'a>(&'a self, node: &'a NodeId) -> impl DoubleEndedIterator<Item = &'a Record> {
+        self.log.iter().filter(move |record| record.node == *node)
+    }
+
+    fn settle(&mut self, id: u64, outcome: Outcome) -> Option<&Record> {
+        let record = self
+            .log
+            .iter_mut()
+            .find(|record| record.notification.id == id && record.outcome == Outcome::Pending)?;
+        record.outcome = outcome;
+        record.settled_at = Some(SystemTime::now());
+        Some(record)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reply(id: u64, code: pb::NotificationReplyCode, data: &str) -> pb::NotificationReply {
+        pb::NotificationReply {
+            id,
+            code: code.into(),
+            data: String::from(data),
+        }
+    }
+
+    /// Test that IDs are unique and replies settle the matching notification only.
+    #[test]
+    fn test_reply_matching() {
+        let mut tracker = NotificationTracker::new();
+        let node_a = String::from("10.0.0.1");
+        let node_b = String::from("10.0.0.2");
+        let first = tracker.track(
+            &node_a,
+            pb::Action::ReloadFwRules,
+            String::new(),
+            vec![],
+            None,
+        );
+        let second = tracker.track(&node_b, pb::Action::Stop, String::new(), vec![], None);
+        assert_ne!(first.id, second.id);
+        assert_eq!(first.r#type(), pb::Action::ReloadFwRules);
+
+        // Wrong node, unknown ID.
+        assert!(
+            tracker
+                .reply(&node_b, &reply(first.id, pb::NotificationReplyCode::Ok, ""))
+                .is_none()
+        );
+        assert!(
+            tracker
+                .reply(&node_a, &reply(99, pb::NotificationReplyCode::Ok, ""))
+                .is_none()
+        );
+
+        let settled = tracker
+            .reply(
+                &node_b,
+                &reply(second.id, pb::NotificationReplyCode::Error, "nope"),
+            )
+            .expect("reply not matched");
+        assert_eq!(settled.action(), pb::Action::Stop);
+        assert_eq!(settled.outcome, Outcome::Error(String::from("nope")));
+
+        // Settled notifications stay settled.
+        assert!(
+            tracker
+                .reply(
+                    &node_b,
+                    &reply(second.id, pb::NotificationReplyCode::Ok, "")
+                )
+                .is_none()
+        );
+        assert_eq!(tracker.log_for(&node_a).count(), 1);
+        assert_eq!(
+            tracker.log_for(&node_a).next().unwrap().outcome,
+            Outcome::Pending
+        );
+    }
+
+    /// Test that unanswered notifications time out.
+    #[test]
+    fn test_expire() {
+        let mut tracker = NotificationTracker::new();
+        let node = String::from("10.0.0.1");
+        let sent = tracker.track(&node, pb::Action::Stop, String::new(), vec![], None);
+        assert!(tracker.expire(SystemTime::now()).is_empty());
+
+        let expired = tracker.expire(SystemTime::now() + REPLY_TIMEOUT);
+        assert_eq!(expired.len(), 1);
+        assert_eq!(expired[0].notification.id, sent.id);
+        assert_eq!(expired[0].outcome, Outcome::Timeout);
+        assert!(tracker.expire(SystemTime::now() + REPLY_TIMEOUT).is_empty());
+    }
+
+    /// Test that a full log drops the oldest settled notification, never a pending one.
+    #[test]
+    fn test_log_capacity() {
+        let mut tracker = NotificationTracker::new();
+        let node = String::from("10.0.0.1");
+        let pending = tracker.track(&node, pb::Action::Stop, String::new(), vec![], None);
+        for _ in 1..LOG_CAPACITY {
+            let sent = tracker.track(&node, pb::Action::Stop, String::new(), vec![], None);
+            tracker.send_failed(sent.id, String::from("gone"));
+        }
+        let next = tracker.track(&node, pb::Action::Stop, String::new(), vec![], None);
+        assert_eq!(tracker.log_for(&node).count(), LOG_CAPACITY);
+        let ids: Vec<u64> = tracker.log_for(&node).map(|r| r.notification.id).collect();
+        assert!(ids.contains(&pending.id));
+        assert!(!ids.contains(&(pending.id + 1)));
+        assert!(ids.contains(&next.id));
+        assert!(
+            tracker
+                .reply(&node, &reply(pending.id, pb::NotificationReplyCode::Ok, ""))
+                .is_some()
+        );
+
+        // With nothing settled to drop, the log grows past its capacity.
+        let mut tracker = NotificationTracker::new();
+        for _ in 0..=LOG_CAPACITY {
+            tracker.track(&node, pb::Action::Stop, String::new(), vec![], None);
+        }
+        assert_eq!(tracker.log_for(&node).count(), LOG_CAPACITY + 1);
+    }
+}
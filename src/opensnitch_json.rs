@@ -1,24 +1,320 @@
-use serde;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Keys this file doesn't model, kept as-is so they survive a round trip.
+type Extra = Map<String, Value>;
 
 /// From opensnitch/daemon/ui/config/config.go
-/// Only selective fields implemented to save time.
-#[derive(serde::Serialize)]
+/// Every field is optional: fields the daemon didn't send aren't invented on the way back,
+/// and fields this struct doesn't know about are carried along in `extra`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
 #[allow(non_snake_case)]
 pub struct OpenSnitchDaemonConfig {
-    // LogLevel: int32,
-    // Firewall: string,
-    pub DefaultAction: String,
-    // DefaultDuration: string,
-    // ProcMonitorMethod: string ,
-    // FwOptions: FwOptions,
-    // Audit: audit.Config,
-    // Ebpf: ebpf.Config,
-    // Server: ServerConfig,
-    // Rules: RulesOptions,
-    // Internal: InternalOptions,
-    // Stats: statistics.StatsConfig,
-    // Tasks: TasksOptions,
-    // InterceptUnknown: bool,
-    // LogUTC: bool,
-    // LogMicro: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Server: Option<ServerConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub DefaultAction: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub DefaultDuration: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub InterceptUnknown: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ProcMonitorMethod: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub LogLevel: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub LogUTC: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub LogMicro: Option<bool>,
+    /// Deprecated in favour of `FwOptions.Firewall`, still sent by older daemons.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Firewall: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub FwOptions: Option<FwOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Rules: Option<RulesOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Ebpf: Option<EbpfOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Stats: Option<StatsConfig>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Internal: Option<InternalOptions>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Audit: Option<AuditOptions>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Server`: how the daemon reaches its UI.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct ServerConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Authentication: Option<ServerAuth>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub LogFile: Option<String>,
+    /// Remote loggers (syslog etc), each with its own shape.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Loggers: Option<Vec<Value>>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Server.Authentication`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct ServerAuth {
+    /// simple, tls-simple or tls-mutual.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub TLSOptions: Option<TlsOptions>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Server.Authentication.TLSOptions`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct TlsOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub CACert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ServerCert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ServerKey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ClientCert: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ClientKey: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub SkipVerify: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ClientAuthType: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `FwOptions`: the daemon's own firewall.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct FwOptions {
+    /// iptables or nftables.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Firewall: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ConfigPath: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub MonitorInterval: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub QueueNum: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub QueueBypass: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Rules`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct RulesOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Path: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub EnableChecksums: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Ebpf`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct EbpfOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ModulesPath: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub EventsWorkers: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub QueueEventsSize: Option<u32>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Stats`: how much the daemon keeps around for pings.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct StatsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub MaxEvents: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub MaxStats: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub Workers: Option<u32>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Internal`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct InternalOptions {
+    /// Go GC percent, where -1 turns GC off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub GCPercent: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub FlushConnsOnStart: Option<bool>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+/// `Audit`.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+#[allow(non_snake_case)]
+pub struct AuditOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub AudispSocketPath: Option<String>,
+    #[serde(flatten)]
+    pub extra: Extra,
+}
+
+impl OpenSnitchDaemonConfig {
+    /// Parses the config JSON a daemon sends in `ClientConfig.config`.
+    /// # Errors
+    /// Returns a description of the problem if the JSON doesn't look like a daemon config.
+    pub fn parse(json: &str) -> Result<Self, String> {
+        serde_json::from_str(json).map_err(|err| format!("Invalid daemon config: {err}"))
+    }
+
+    /// Serializes back to the daemon's JSON format.
+    /// # Errors
+    /// Not really, every field serializes.
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string(self).map_err(|err| err.to_string())
+    }
+
+    /// Writes the fields that are set over the config JSON a daemon sent, see [`merge`].
+    /// # Errors
+    /// Returns a description of the problem if `json` isn't a JSON object.
+    pub fn merge_into(&self, json: &str) -> Result<String, String> {
+        let changes = serde_json::to_value(self).map_err(|err| err.to_string())?;
+        merge(json, &changes)
+    }
+}
+
+/// Applies `changes` over the config JSON a daemon sent: objects merge key by key, and
+/// anything else replaces what was there. Keys `changes` doesn't mention go back exactly as
+/// they came, including explicit nulls and values of a type this file doesn't expect.
+/// # Errors
+/// Returns a description of the problem if `json` isn't a JSON object.
+pub fn merge(json: &str, changes: &Value) -> Result<String, String> {
+    let mut config: Value =
+        serde_json::from_str(json).map_err(|err| format!("Invalid daemon config: {err}"))?;
+    if !config.is_object() {
+        return Err(String::from("Invalid daemon config: not a JSON object"));
+    }
+    merge_value(&mut config, changes);
+    serde_json::to_string(&config).map_err(|err| err.to_string())
+}
+
+fn merge_value(target: &mut Value, changes: &Value) {
+    match (target, changes) {
+        (Value::Object(target), Value::Object(changes)) => {
+            for (key, change) in changes {
+                merge_value(target.entry(key.clone()).or_insert(Value::Null), change);
+            }
+        }
+        (target, change) => *target = change.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Trimmed opensnitch/daemon/default-config.json, plus a key from some future daemon.
+    const DAEMON_CONFIG: &str = r#"{
+        "Server": {
+            "Address": "unix:///tmp/osui.sock",
+            "Authentication": {"Type": "simple", "TLSOptions": {"CACert": "", "SkipVerify": false}},
+            "LogFile": "/var/log/opensnitchd.log",
+            "Loggers": [{"Name": "syslog", "Server": "127.0.0.1:514", "Protocol": "udp"}]
+        },
+        "DefaultAction": "allow",
+        "DefaultDuration": "once",
+        "InterceptUnknown": false,
+        "ProcMonitorMethod": "ebpf",
+        "LogLevel": 2,
+        "LogUTC": true,
+        "LogMicro": false,
+        "Firewall": "nftables",
+        "FwOptions": {"ConfigPath": "/etc/opensnitchd/system-fw.json", "MonitorInterval": "15s", "QueueBypass": true},
+        "Rules": {"Path": "/etc/opensnitchd/rules/", "EnableChecksums": false},
+        "Ebpf": {"ModulesPath": "", "EventsWorkers": 8, "QueueEventsSize": 0},
+        "Stats": {"MaxEvents": 150, "MaxStats": 25, "Workers": 6},
+        "Internal": {"GCPercent": 100, "FlushConnsOnStart": true},
+        "Tasks": {"ConfigPath": "/etc/opensnitchd/tasks/tasks.json"}
+    }"#;
+
+    /// Test that a daemon config survives a round trip untouched, unknown keys included.
+    #[test]
+    fn test_round_trip() {
+        let config = OpenSnitchDaemonConfig::parse(DAEMON_CONFIG).expect("parse failed");
+        assert_eq!(config.ProcMonitorMethod.as_deref(), Some("ebpf"));
+        assert_eq!(config.Stats.as_ref().unwrap().MaxEvents, Some(150));
+        assert!(config.extra.contains_key("Tasks"));
+
+        let json = config.to_json().unwrap();
+        let expected: Value = serde_json::from_str(DAEMON_CONFIG).unwrap();
+        let actual: Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    /// Test that absent fields stay absent, and malformed configs are rejected.
+    #[test]
+    fn test_sparse_and_invalid() {
+        let config = OpenSnitchDaemonConfig::parse(r#"{"LogLevel": 1}"#).unwrap();
+        assert_eq!(config.to_json().unwrap(), r#"{"LogLevel":1}"#);
+        assert!(OpenSnitchDaemonConfig::parse(r#"{"LogLevel": "debug"}"#).is_err());
+        assert!(OpenSnitchDaemonConfig::parse("[]").is_err());
+    }
+
+    /// Test that merging changes only the given keys, leaving nulls and keys of an
+    /// unexpected type alone.
+    #[test]
+    fn test_merge() {
+        let json = r#"{"DefaultAction": "allow", "LogLevel": "debug", "Audit": null,
+            "Stats": {"MaxEvents": 150, "Workers": null}}"#;
+        let changes = serde_json::json!({"DefaultAction": "deny", "Stats": {"MaxStats": 25}});
+        let merged: Value = serde_json::from_str(&merge(json, &changes).unwrap()).unwrap();
+        assert_eq!(
+            merged,
+            serde_json::json!({
+                "DefaultAction": "deny",
+                "LogLevel": "debug",
+                "Audit": null,
+                "Stats": {"MaxEvents": 150, "MaxStats": 25, "Workers": null}
+            })
+        );
+        assert!(merge("[]", &changes).is_err());
+        assert!(merge("not json", &changes).is_err());
+
+        let config = OpenSnitchDaemonConfig {
+            LogLevel: Some(1),
+            ..Default::default()
+        };
+        let merged = config.merge_into(r#"{"LogLevel": 2, "Audit": null}"#);
+        assert_eq!(merged.as_deref(), Ok(r#"{"Audit":null,"LogLevel":1}"#));
+    }
 }
@@ -0,0 +1,210 @@
+//! Rule decisions for connections when nobody is around to answer prompts.
+
+use std::path::Path;
+
+use serde::Deserialize;
+
+use crate::constants;
+use crate::opensnitch_proto::pb;
+
+/// What to do about a connection: the rule action, and how long the rule lasts.
+pub type Decision = (constants::Action, constants::Duration);
+
+/// A policy entry as written in the policy file: a decision, and the connection
+/// attributes it applies to. Unset attributes match anything.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+    /// allow/deny/reject
+    action: String,
+    /// Rule lifetime, same values as `--temp-rule-lifetime`. Defaults to once.
+    duration: Option<String>,
+    process_path: Option<String>,
+    user_id: Option<u32>,
+    dst_host: Option<String>,
+    dst_ip: Option<String>,
+    dst_port: Option<u32>,
+    protocol: Option<String>,
+}
+
+impl Entry {
+    fn matches(&self, conn: &pb::Connection) -> bool {
+        self.process_path
+            .as_ref()
+            .is_none_or(|path| *path == conn.process_path)
+            && self.user_id.is_none_or(|uid| uid == conn.user_id)
+            && self
+                .dst_host
+                .as_ref()
+                .is_none_or(|host| host.eq_ignore_ascii_case(&conn.dst_host))
+            && self.dst_ip.as_ref().is_none_or(|ip| *ip == conn.dst_ip)
+            && self.dst_port.is_none_or(|port| port == conn.dst_port)
+            && self
+                .protocol
+                .as_ref()
+                .is_none_or(|proto| proto.eq_ignore_ascii_case(&conn.protocol))
+    }
+}
+
+/// Decision for connections no entry matches, as written in the policy file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DefaultFile {
+    action: String,
+    duration: Option<String>,
+}
+
+/// Layout of the policy file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct PolicyFile {
+    /// Entries in priority order, first match wins.
+    #[serde(default)]
+    rules: Vec<Entry>,
+    default: Option<DefaultFile>,
+}
+
+/// Ordered list of decisions keyed on connection attributes, with a fallback.
+#[derive(Debug)]
+pub struct Policy {
+    /// Entries in priority order, along with their validated decision.
+    entries: Vec<(Entry, Decision)>,
+    /// Decision for connections no entry matches.
+    fallback: Decision,
+}
+
+impl Policy {
+    /// A policy that decides every connection with `fallback`.
+    #[must_use]
+    pub fn new(fallback: Decision) -> Self {
+        Self {
+            entries: Vec::new(),
+            fallback,
+        }
+    }
+
+    /// Reads a JSON policy file.
+    /// * `fallback`: Decision for unmatched connections, if the file doesn't give one.
+    /// # Errors
+    /// Returns a description of the problem if the file can't be read or is invalid.
+    pub fn load(path: &Path, fallback: Decision) -> Result<Self, String> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|err| format!("Unable to read policy {}: {err}", path.display()))?;
+        Self::parse(&json, fallback)
+            .map_err(|err| format!("Invalid policy {}: {err}", path.display()))
+    }
+
+    /// Parses a JSON policy, e.g.
+    /// `{"rules": [{"action": "allow", "duration": "always", "process_path": "/usr/bin/curl"}],
+    /// "default": {"action": "deny"}}`.
+    /// # Errors
+    /// Returns a description of the problem if the policy is invalid.
+    pub fn parse(json: &str, fallback: Decision) -> Result<Self, String> {
+        let file: PolicyFile = serde_json::from_str(json).map_err(|err| err.to_string())?;
+        let fallback = match file.default {
+            Some(default) => decision_of(&default.action, default.duration.as_deref())?,
+            None => fallback,
+        };
+        let entries = file
+            .rules
+            .into_iter()
+            .map(|entry| {
+                let decision = decision_of(&entry.action, entry.duration.as_deref())?;
+                Ok((entry, decision))
+            })
+            .collect::<Result<_, String>>()?;
+        Ok(Self { entries, fallback })
+    }
+
+    /// Decision for a connection: that of the first matching entry, else the fallback.
+    #[must_use]
+    pub fn decide(&self, conn: &pb::Connection) -> Decision {
+        self.entries
+            .iter()
+            .find(|(entry, _)| entry.matches(conn))
+            .map_or(self.fallback, |(_, decision)| *decision)
+    }
+}
+
+/// Validates an entry's action and duration.
+fn decision_of(action: &str, duration: Option<&str>) -> Result<Decision, String> {
+    let action = constants::DefaultAction::new(action)
+        .map_err(|err| format!("Invalid action: {}", err.input))?
+        .as_action();
+    let duration = match duration {
+        Some(duration) => constants::Duration::new(duration)
+            .map_err(|err| format!("Invalid duration: {}", err.input))?,
+        None => constants::Duration::Once,
+    };
+    Ok((action, duration))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FALLBACK: Decision = (constants::Action::Deny, constants::Duration::Once);
+
+    fn connection(process_path: &str, dst_host: &str, dst_port: u32) -> pb::Connection {
+        pb::Connection {
+            protocol: String::from("tcp"),
+            dst_host: String::from(dst_host),
+            dst_port,
+            process_path: String::from(process_path),
+            ..Default::default()
+        }
+    }
+
+    /// Test that entries match in order and unmatched connections get the fallback.
+    #[test]
+    fn test_decide() {
+        let policy = Policy::parse(
+            r#"{"rules": [
+                {"action": "reject", "dst_host": "Tracker.example", "protocol": "TCP"},
+                {"action": "allow", "duration": "always", "process_path": "/usr/bin/curl"}
+            ]}"#,
+            FALLBACK,
+        )
+        .expect("parse failed");
+
+        let decide = |conn| {
+            let (action, duration) = policy.decide(&conn);
+            (action.get_str().to_owned(), duration.get_str().to_owned())
+        };
+        assert_eq!(
+            decide(connection("/usr/bin/curl", "tracker.example", 443)),
+            (String::from("reject"), String::from("once"))
+        );
+        assert_eq!(
+            decide(connection("/usr/bin/curl", "example.com", 443)),
+            (String::from("allow"), String::from("always"))
+        );
+        assert_eq!(
+            decide(connection("/usr/bin/wget", "example.com", 443)),
+            (String::from("deny"), String::from("once"))
+        );
+
+        let policy = Policy::parse(r#"{"default": {"action": "allow"}}"#, FALLBACK).unwrap();
+        assert_eq!(
+            policy
+                .decide(&connection("/usr/bin/wget", "example.com", 443))
+                .0
+                .get_str(),
+            "allow"
+        );
+    }
+
+    /// Test that mistakes in the policy are caught up front.
+    #[test]
+    fn test_parse_errors() {
+        for json in [
+            r#"{"rules": [{"action": "accept"}]}"#,
+            r#"{"rules": [{"action": "allow", "duration": "forever"}]}"#,
+            r#"{"rules": [{"action": "allow", "dst_hots": "example.com"}]}"#,
+            r#"{"default": {"action": "allow", "dst_port": 53}}"#,
+            "[]",
+        ] {
+            assert!(Policy::parse(json, FALLBACK).is_err(), "{json}");
+        }
+    }
+}
@@ -0,0 +1,229 @@
+//! Rates of a daemon's counters, from the change in its cumulative stats between pings.
+
+use std::collections::VecDeque;
+use std::time::{Duration, SystemTime};
+
+use crate::opensnitch_proto::pb;
+
+/// A counter that's charted as a rate.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Counter {
+    Connections,
+    Dropped,
+    RuleMisses,
+}
+
+impl Counter {
+    /// Every charted counter, in the order shown.
+    pub const ALL: [Counter; 3] = [Counter::Connections, Counter::Dropped, Counter::RuleMisses];
+
+    /// Chart label.
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            Counter::Connections => "Connections/s",
+            Counter::Dropped => "Drops/s",
+            Counter::RuleMisses => "Rule misses/s",
+        }
+    }
+
+    fn value(self, stats: &pb::Statistics) -> u64 {
+        match self {
+            Counter::Connections => stats.connections,
+            Counter::Dropped => stats.dropped,
+            Counter::RuleMisses => stats.rule_misses,
+        }
+    }
+
+    fn idx(self) -> usize {
+        self as usize
+    }
+}
+
+/// How far back the charts go.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Window {
+    Minute,
+    FiveMinutes,
+    FifteenMinutes,
+    Hour,
+}
+
+impl Window {
+    /// Every window, shortest first.
+    pub const ALL: [Window; 4] = [
+        Window::Minute,
+        Window::FiveMinutes,
+        Window::FifteenMinutes,
+        Window::Hour,
+    ];
+
+    #[must_use]
+    pub fn duration(self) -> Duration {
+        match self {
+            Window::Minute => Duration::from_mins(1),
+            Window::FiveMinutes => Duration::from_mins(5),
+            Window::FifteenMinutes => Duration::from_mins(15),
+            Window::Hour => Duration::from_hours(1),
+        }
+    }
+
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            Window::Minute => "1m",
+            Window::FiveMinutes => "5m",
+            Window::FifteenMinutes => "15m",
+            Window::Hour => "1h",
+        }
+    }
+
+    /// The next longer window, wrapping around to the shortest.
+    #[must_use]
+    pub fn next(self) -> Window {
+        let idx = Window::ALL
+            .iter()
+            .position(|window| *window == self)
+            .unwrap_or(0);
+        Window::ALL[(idx + 1) % Window::ALL.len()]
+    }
+}
+
+/// Rates worked out from one ping, per second since the ping before it.
+#[derive(Clone, Debug, PartialEq)]
+struct Sample {
+    at: SystemTime,
+    rates: [f64; Counter::ALL.len()],
+}
+
+/// A node's counter rates over the longest window, oldest first.
+#[derive(Clone, Debug, Default)]
+pub struct RateSeries {
+    samples: VecDeque<Sample>,
+    /// When the last ping came, its uptime and counter values.
+    last: Option<(SystemTime, u64, [u64; Counter::ALL.len()])>,
+}
+
+impl RateSeries {
+    /// Take in the stats from a ping received at `at`. After a daemon restart, or
+    /// anything else that sets a counter back, the counter's value is all new.
+    pub fn record(&mut self, at: SystemTime, stats: &pb::Statistics) {
+        let values = Counter::ALL.map(|counter| counter.value(stats));
+        if let Some((last_at, last_uptime, last_values)) = self.last
+            && let Ok(elapsed) = at.duration_since(last_at)
+            && !elapsed.is_zero()
+        {
+            let restarted = stats.uptime < last_uptime;
+            let mut rates = [0.0; Counter::ALL.len()];
+            for (idx, rate) in rates.iter_mut().enumerate() {
+                let delta = if restarted || values[idx] < last_values[idx] {
+                    values[idx]
+                } else {
+                    values[idx] - last_values[idx]
+                };
+                *rate = f64::from(u32::try_from(delta).unwrap_or(u32::MAX)) / elapsed.as_secs_f64();
+            }
+            self.samples.push_back(Sample { at, rates });
+        }
+        self.last = Some((at, stats.uptime, values));
+
+        let longest = Window::Hour.duration();
+        while self
+            .samples
+            .front()
+            .is_some_and(|sample| at.duration_since(sample.at).unwrap_or_default() > longest)
+        {
+            self.samples.pop_front();
+        }
+    }
+
+    /// A counter's rate over a window ending at `now`, as (seconds before `now`, rate)
+    /// points, oldest first. Seconds before `now` are negative.
+    #[must_use]
+    pub fn points(&self, counter: Counter, window: Window, now: SystemTime) -> Vec<(f64, f64)> {
+        self.samples
+            .iter()
+            .filter_map(|sample| {
+                let ago = now.duration_since(sample.at).unwrap_or_default();
+                (ago <= window.duration())
+                    .then(|| (-ago.as_secs_f64(), sample.rates[counter.idx()]))
+            })
+            .collect()
+    }
+
+    /// A counter's rate as of the last ping, if there have been two.
+    #[must_use]
+    pub fn latest(&self, counter: Counter) -> Option<f64> {
+        self.samples
+            .back()
+            .map(|sample| sample.rates[counter.idx()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(uptime: u64, connections: u64, dropped: u64) -> pb::Statistics {
+        pb::Statistics {
+            uptime,
+            connections,
+            dropped,
+            ..Default::default()
+        }
+    }
+
+    /// Test that rates come from the change between pings, counting a reset counter's
+    /// value as all new.
+    #[test]
+    fn test_record() {
+        let mut series = RateSeries::default();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_hours(1);
+        let at = |secs| start + Duration::from_secs(secs);
+        series.record(at(0), &stats(100, 50, 10));
+        assert_eq!(series.latest(Counter::Connections), None);
+
+        series.record(at(2), &stats(102, 60, 10));
+        assert_eq!(series.latest(Counter::Connections), Some(5.0));
+        assert_eq!(series.latest(Counter::Dropped), Some(0.0));
+
+        // The daemon restarted: counters start over.
+        series.record(at(4), &stats(1, 8, 2));
+        assert_eq!(series.latest(Counter::Connections), Some(4.0));
+        assert_eq!(series.latest(Counter::Dropped), Some(1.0));
+
+        // A repeated ping time can't make a rate.
+        series.record(at(4), &stats(1, 9, 2));
+        assert_eq!(series.samples.len(), 2);
+    }
+
+    /// Test that points are placed before `now`, within the window, and that samples
+    /// older than the longest window go.
+    #[test]
+    fn test_points() {
+        let mut series = RateSeries::default();
+        let start = SystemTime::UNIX_EPOCH + Duration::from_hours(1);
+        for (idx, secs) in [0, 10, 70, 100].into_iter().enumerate() {
+            let connections = u64::try_from(idx).unwrap() * 100;
+            series.record(
+                start + Duration::from_secs(secs),
+                &stats(secs, connections, 0),
+            );
+        }
+        let now = start + Duration::from_secs(100);
+        assert_eq!(
+            series.points(Counter::Connections, Window::Minute, now),
+            [(-30.0, 100.0 / 60.0), (0.0, 100.0 / 30.0)]
+        );
+        assert_eq!(
+            series
+                .points(Counter::Connections, Window::FiveMinutes, now)
+                .len(),
+            3
+        );
+
+        series.record(now + Duration::from_hours(1), &stats(3700, 400, 0));
+        assert_eq!(series.samples.len(), 2);
+        assert_eq!(Window::Hour.next(), Window::Minute);
+    }
+}
@@ -0,0 +1,598 @@
+//! Rule editor: a form for a rule's settings and its operator tree, sent to the daemon
+//! as a `CHANGE_RULE` notification.
+
+use std::net::IpAddr;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+
+use crate::constants::{self, Operand, RuleType};
+use crate::form::{Field, Form};
+use crate::opensnitch_proto::pb;
+
+const NAME: &str = "Name";
+const DESCRIPTION: &str = "Description";
+const ACTION: &str = "Action";
+const DURATION: &str = "Duration";
+const ENABLED: &str = "Enabled";
+const PRECEDENCE: &str = "Precedence";
+const NOLOG: &str = "No log";
+
+const TYPE: &str = "Type";
+const OPERAND: &str = "Operand";
+const ENV_VAR: &str = "Variable";
+const DATA: &str = "Data";
+const SENSITIVE: &str = "Case sensitive";
+
+/// Actions a rule can take on a connection.
+const RULE_ACTIONS: [&str; 3] = ["allow", "deny", "reject"];
+
+/// One operator of the tree, flattened: the members of a list follow it, a level deeper.
+#[derive(Clone, Debug)]
+pub struct OperatorEntry {
+    /// How many lists this operator is nested in.
+    pub depth: usize,
+    /// Type, operand, environment variable, data and case sensitivity, in that order.
+    pub form: Form,
+}
+
+impl OperatorEntry {
+    fn new(depth: usize, operator: &pb::Operator) -> Self {
+        let env_prefix = Operand::ProcessEnv.get_str();
+        let (operand, env_var) = match operator.operand.strip_prefix(env_prefix) {
+            Some(var) => (env_prefix, var),
+            None => (operator.operand.as_str(), ""),
+        };
+        let types = RuleType::ALL.map(|r#type| r#type.get_str());
+        // Lists hold other operators rather than match on an operand themselves.
+        let operands: Vec<&str> = Operand::ALL
+            .iter()
+            .filter(|operand| !matches!(operand, Operand::List))
+            .map(Operand::get_str)
+            .collect();
+        let is_list = operator.r#type == RuleType::List.get_str();
+        Self {
+            depth,
+            form: Form::new(vec![
+                Field::choice(TYPE, &types, Some(&operator.r#type)),
+                // Lists get an operand to start from in case they're turned into something else.
+                Field::choice(
+                    OPERAND,
+                    &operands,
+                    Some(if is_list {
+                        Operand::ProcessPath.get_str()
+                    } else {
+                        operand
+                    }),
+                ),
+                Field::text(ENV_VAR, env_var),
+                Field::text(DATA, if is_list { "" } else { &operator.data }),
+                Field::toggle(SENSITIVE, Some(operator.sensitive)),
+            ]),
+        }
+    }
+
+    /// A fresh operator, matching on the process path.
+    fn blank(depth: usize) -> Self {
+        Self::new(
+            depth,
+            &pb::Operator {
+                r#type: String::from(RuleType::Simple.get_str()),
+                operand: String::from(Operand::ProcessPath.get_str()),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Whether this operator is a list of other operators.
+    #[must_use]
+    pub fn is_list(&self) -> bool {
+        self.form.field(TYPE).value == RuleType::List.get_str()
+    }
+
+    /// Indexes of the fields that apply, given the type and operand picked so far.
+    #[must_use]
+    pub fn visible_fields(&self) -> Vec<usize> {
+        if self.is_list() {
+            return vec![0];
+        }
+        if self.form.field(OPERAND).value == Operand::ProcessEnv.get_str() {
+            vec![0, 1, 2, 3, 4]
+        } else {
+            vec![0, 1, 3, 4]
+        }
+    }
+
+    /// The operator as the daemon takes it, members aside.
+    fn to_operator(&self) -> Result<pb::Operator, String> {
+        let field = |label| &self.form.field(label).value;
+        let r#type = field(TYPE).clone();
+        if self.is_list() {
+            return Ok(pb::Operator {
+                r#type,
+                operand: String::from(Operand::List.get_str()),
+                ..Default::default()
+            });
+        }
+
+        let mut operand = field(OPERAND).clone();
+        let data = field(DATA).trim();
+        if operand.is_empty() {
+            return Err(String::from("Every operator needs an operand"));
+        }
+        if data.is_empty() {
+            return Err(format!("Data to match {operand} against can't be empty"));
+        }
+        if operand == Operand::ProcessEnv.get_str() {
+            let var = field(ENV_VAR).trim();
+            if var.is_empty() {
+                return Err(String::from("Name the environment variable to match"));
+            }
+            operand.push_str(var);
+        }
+        validate_operator(&r#type, &operand, data)?;
+        Ok(pb::Operator {
+            r#type,
+            operand,
+            data: String::from(data),
+            sensitive: self.form.field(SENSITIVE).as_bool().unwrap_or(false),
+            list: Vec::new(),
+        })
+    }
+}
+
+/// Checks an operator's data makes sense for its type and operand.
+fn validate_operator(r#type: &str, operand: &str, data: &str) -> Result<(), String> {
+    let is_lists_operand = operand.starts_with("lists.");
+    let is_network_operand = operand.ends_with(".network");
+    if (r#type == RuleType::Lists.get_str()) != is_lists_operand {
+        return Err(format!(
+            "{operand}: lists.* operands go with the lists type, and only those"
+        ));
+    }
+    if (r#type == RuleType::Network.get_str()) != is_network_operand {
+        return Err(format!(
+            "{operand}: *.network operands go with the network type, and only those"
+        ));
+    }
+    if r#type == RuleType::Network.get_str() && !is_cidr(data) {
+        return Err(format!("{operand}: {data} isn't a network like 10.0.0.0/8"));
+    }
+    if r#type != RuleType::Simple.get_str() {
+        return Ok(());
+    }
+    let numeric_max = match operand {
+        "dest.port" | "source.port" => Some(u64::from(u16::MAX)),
+        "process.id" | "user.id" => Some(u64::from(u32::MAX)),
+        _ => None,
+    };
+    if let Some(max) = numeric_max
+        && !data.parse::<u64>().is_ok_and(|number| number <= max)
+    {
+        return Err(format!("{operand} must be a number up to {max}"));
+    }
+    if matches!(operand, "dest.ip" | "source.ip") && data.parse::<IpAddr>().is_err() {
+        return Err(format!("{operand}: {data} isn't an IP address"));
+    }
+    Ok(())
+}
+
+/// Whether `data` is an address and prefix length, e.g. 192.168.0.0/16.
+fn is_cidr(data: &str) -> bool {
+    let Some((addr, prefix)) = data.split_once('/') else {
+        return false;
+    };
+    let max_prefix = match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(_)) => 32,
+        Ok(IpAddr::V6(_)) => 128,
+        Err(_) => return false,
+    };
+    prefix
+        .parse::<u8>()
+        .is_ok_and(|prefix| prefix <= max_prefix)
+}
+
+/// Where a row of the editor points.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Row {
+    /// A field of the rule itself.
+    Rule(usize),
+    /// A field of an operator: index into the operators, then into its fields.
+    Operator(usize, usize),
+}
+
+/// A rule being created or edited.
+#[derive(Clone, Debug)]
+pub struct RuleEditor {
+    /// Name of the rule being edited, none when creating a rule.
+    pub editing: Option<String>,
+    /// When the rule being edited was created, kept as is.
+    created: i64,
+    /// Name, description, action, duration and flags.
+    pub form: Form,
+    /// The operator tree, flattened. The first entry is the root.
+    pub operators: Vec<OperatorEntry>,
+    /// Index of the selected row, see [`RuleEditor::rows`].
+    pub cursor: usize,
+    /// ID of the last `CHANGE_RULE` notification sent from this editor.
+    pub sent: Option<u64>,
+    /// Why the rule couldn't be sent or changed, if it couldn't.
+    pub error: Option<String>,
+}
+
+impl RuleEditor {
+    /// An editor for a new rule, matching on process path to start with.
+    #[must_use]
+    pub fn new_rule() -> Self {
+        let rule = pb::Rule {
+            action: String::from(constants::Action::Allow.get_str()),
+            duration: String::from(constants::Duration::Always.get_str()),
+            enabled: true,
+            ..Default::default()
+        };
+        Self {
+            editing: None,
+            ..Self::edit(&rule)
+        }
+    }
+
+    /// An editor loaded with an existing rule.
+    #[must_use]
+    pub fn edit(rule: &pb::Rule) -> Self {
+        let durations = constants::Duration::ALL.map(|duration| duration.get_str());
+        let form = Form::new(vec![
+            Field::text(NAME, &rule.name),
+            Field::text(DESCRIPTION, &rule.description),
+            Field::choice(ACTION, &RULE_ACTIONS, Some(&rule.action)),
+            Field::choice(DURATION, &durations, Some(&rule.duration)),
+            Field::toggle(ENABLED, Some(rule.enabled)),
+            Field::toggle(PRECEDENCE, Some(rule.precedence)),
+            Field::toggle(NOLOG, Some(rule.nolog)),
+        ]);
+        let mut operators = Vec::new();
+        match &rule.operator {
+            Some(operator) => flatten(operator, 0, &mut operators),
+            None => operators.push(OperatorEntry::blank(0)),
+        }
+        Self {
+            editing: Some(rule.name.clone()),
+            created: rule.created,
+            form,
+            operators,
+            cursor: 0,
+            sent: None,
+            error: None,
+        }
+    }
+
+    /// Every editable row, rule fields first, then each operator's fields in tree order.
+    #[must_use]
+    pub fn rows(&self) -> Vec<Row> {
+        let rule_rows = (0..self.form.fields.len()).map(Row::Rule);
+        let operator_rows = self.operators.iter().enumerate().flat_map(|(idx, entry)| {
+            entry
+                .visible_fields()
+                .into_iter()
+                .map(move |field| Row::Operator(idx, field))
+        });
+        rule_rows.chain(operator_rows).collect()
+    }
+
+    /// The field a row points at.
+    #[must_use]
+    pub fn field(&self, row: Row) -> &Field {
+        match row {
+            Row::Rule(field) => &self.form.fields[field],
+            Row::Operator(idx, field) => &self.operators[idx].form.fields[field],
+        }
+    }
+
+    /// The operator the cursor is on, if it's on one.
+    fn current_operator(&self) -> Option<usize> {
+        match self.rows().get(self.cursor) {
+            Some(Row::Operator(idx, _)) => Some(*idx),
+            _ => None,
+        }
+    }
+
+    /// Handle a key press. Up/down move between rows, ctrl+A adds an operator next to the
+    /// selected one, ctrl+N adds one inside the selected list, ctrl+D removes the
+    /// selected operator, and anything else edits the selected field.
+    /// Returns whether the rule changed. Problems are left in `error`.
+    pub fn handle_key(&mut self, key_event: KeyEvent) -> bool {
+        let rows = self.rows();
+        let result = match key_event.code {
+            KeyCode::Up => {
+                self.cursor = self.cursor.saturating_sub(1);
+                return false;
+            }
+            KeyCode::Down => {
+                self.cursor = std::cmp::min(self.cursor + 1, rows.len().saturating_sub(1));
+                return false;
+            }
+            KeyCode::Char('a') if key_event.modifiers == KeyModifiers::CONTROL => {
+                self.add_operator(false)
+            }
+            KeyCode::Char('n') if key_event.modifiers == KeyModifiers::CONTROL => {
+                self.add_operator(true)
+            }
+            KeyCode::Char('d') if key_event.modifiers == KeyModifiers::CONTROL => {
+                self.remove_operator()
+            }
+            _ => {
+                let field = match rows.get(self.cursor) {
+                    Some(Row::Rule(field)) => self.form.fields.get_mut(*field),
+                    Some(Row::Operator(idx, field)) => {
+                        self.operators[*idx].form.fields.get_mut(*field)
+                    }
+                    None => None,
+                };
+                if !field.is_some_and(|field| field.handle_key(key_event)) {
+                    return false;
+                }
+                // A type change can hide or show fields under the cursor.
+                self.clamp_cursor();
+                Ok(())
+            }
+        };
+        let changed = result.is_ok();
+        self.error = result.err();
+        changed
+    }
+
+    /// Add a blank operator after the selected one, or inside it if it's a list.
+    /// With the cursor on the rule's own fields, the root operator is the one selected.
+    fn add_operator(&mut self, inside: bool) -> Result<(), String> {
+        let idx = self.current_operator().unwrap_or(0);
+        let entry = &self.operators[idx];
+        let depth = if inside {
+            if !entry.is_list() {
+                return Err(String::from("Only list operators can have members"));
+            }
+            entry.depth + 1
+        } else {
+            if entry.depth == 0 {
+                return Err(String::from(
+                    "Make the top operator a list to match on more than one thing",
+                ));
+            }
+            entry.depth
+        };
+        let at = self.subtree_end(idx);
+        self.operators.insert(at, OperatorEntry::blank(depth));
+        self.cursor = self
+            .rows()
+            .iter()
+            .position(|row| *row == Row::Operator(at, 0))
+            .unwrap_or(self.cursor);
+        Ok(())
+    }
+
+    /// Remove the selected operator, along with its members.
+    fn remove_operator(&mut self) -> Result<(), String> {
+        match self.current_operator() {
+            None | Some(0) => Err(String::from("The top operator can't be removed")),
+            Some(idx) => {
+                let end = self.subtree_end(idx);
+                self.operators.drain(idx..end);
+                self.clamp_cursor();
+                Ok(())
+            }
+        }
+    }
+
+    /// Index just past an operator's members, and their members in turn.
+    fn subtree_end(&self, idx: usize) -> usize {
+        let depth = self.operators[idx].depth;
+        self.operators[idx + 1..]
+            .iter()
+            .position(|entry| entry.depth <= depth)
+            .map_or(self.operators.len(), |offset| idx + 1 + offset)
+    }
+
+    fn clamp_cursor(&mut self) {
+        self.cursor = std::cmp::min(self.cursor, self.rows().len().saturating_sub(1));
+    }
+
+    /// Builds the rule to send.
+    /// * `taken`: Names of the node's other rules, which this one mustn't reuse.
+    /// # Errors
+    /// Returns a description of the first problem found.
+    pub fn build(&self, taken: &[&str]) -> Result<pb::Rule, String> {
+        self.form.validate()?;
+        let field = |label| &self.form.field(label).value;
+        let name = field(NAME).trim();
+        if name.is_empty() {
+            return Err(String::from("The rule needs a name"));
+        }
+        // The daemon saves each rule to a file named after it.
+        if name.contains('/') || name.starts_with('.') {
+            return Err(String::from(
+                "Rule names can't contain / or start with a dot",
+            ));
+        }
+        if self.editing.as_deref() != Some(name) && taken.contains(&name) {
+            return Err(format!("There's already a rule named {name}"));
+        }
+        let (operator, end) = build_operator(&self.operators, 0)?;
+        debug_assert_eq!(end, self.operators.len());
+
+        Ok(pb::Rule {
+            created: self.created,
+            name: String::from(name),
+            description: field(DESCRIPTION).trim().to_owned(),
+            enabled: self.form.field(ENABLED).as_bool().unwrap_or(true),
+            precedence: self.form.field(PRECEDENCE).as_bool().unwrap_or(false),
+            nolog: self.form.field(NOLOG).as_bool().unwrap_or(false),
+            action: field(ACTION).clone(),
+            duration: field(DURATION).clone(),
+            operator: Some(operator),
+        })
+    }
+}
+
+/// Flattens an operator and its members into `entries`, depth first.
+fn flatten(operator: &pb::Operator, depth: usize, entries: &mut Vec<OperatorEntry>) {
+    entries.push(OperatorEntry::new(depth, operator));
+    for member in &operator.list {
+        flatten(member, depth + 1, entries);
+    }
+}
+
+/// Rebuilds the operator at `idx` with its members.
+/// Returns the operator, and the index just past its members.
+fn build_operator(entries: &[OperatorEntry], idx: usize) -> Result<(pb::Operator, usize), String> {
+    let entry = &entries[idx];
+    let mut operator = entry.to_operator()?;
+    let mut next = idx + 1;
+    while next < entries.len() && entries[next].depth > entry.depth {
+        if !entry.is_list() {
+            return Err(format!(
+                "Only list operators can have members, not {}",
+                operator.r#type
+            ));
+        }
+        let (member, after) = build_operator(entries, next)?;
+        operator.list.push(member);
+        next = after;
+    }
+    if entry.is_list() {
+        if operator.list.is_empty() {
+            return Err(String::from(
+                "Lists need at least one member, add one with ctrl+N",
+            ));
+        }
+        // The daemon reads list members from the data field when loading rules from disk.
+        operator.data = serde_json::to_string(&operator.list).map_err(|err| err.to_string())?;
+    }
+    Ok((operator, next))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_util;
+
+    fn ctrl(c: char) -> KeyEvent {
+        KeyEvent::new(KeyCode::Char(c), KeyModifiers::CONTROL)
+    }
+
+    fn type_text(editor: &mut RuleEditor, text: &str) {
+        for c in text.chars() {
+            editor.handle_key(KeyEvent::from(KeyCode::Char(c)));
+        }
+    }
+
+    /// Move the cursor to a row.
+    fn select(editor: &mut RuleEditor, row: Row) {
+        editor.cursor = editor
+            .rows()
+            .iter()
+            .position(|other| *other == row)
+            .unwrap();
+    }
+
+    /// Test that an existing rule loads and builds back the same.
+    #[test]
+    fn test_round_trip() {
+        let members = vec![
+            operator_util::match_proc_path("/usr/bin/curl"),
+            operator_util::match_dst_port(443),
+        ];
+        let rule = pb::Rule {
+            created: 1_700_000_000,
+            name: String::from("curl-https"),
+            description: String::from("curl to https"),
+            enabled: true,
+            precedence: true,
+            nolog: false,
+            action: String::from("allow"),
+            duration: String::from("always"),
+            operator: Some(pb::Operator {
+                r#type: String::from("list"),
+                operand: String::from("list"),
+                data: serde_json::to_string(&members).unwrap(),
+                sensitive: false,
+                list: members,
+            }),
+        };
+        let editor = RuleEditor::edit(&rule);
+        assert_eq!(editor.operators.len(), 3);
+        assert_eq!(editor.rows().len(), 7 + 1 + 4 + 4);
+        // Its own name isn't taken.
+        assert_eq!(editor.build(&["curl-https", "other"]), Ok(rule));
+    }
+
+    /// Test building a nested rule from scratch with the keyboard.
+    #[test]
+    fn test_build_nested() {
+        let mut editor = RuleEditor::new_rule();
+        type_text(&mut editor, "web");
+        assert_eq!(
+            editor.build(&[]),
+            Err(String::from(
+                "Data to match process.path against can't be empty"
+            ))
+        );
+
+        // Can't have two top operators, so make the top one a list.
+        select(&mut editor, Row::Operator(0, 0));
+        assert!(!editor.handle_key(ctrl('a')));
+        assert!(editor.error.is_some());
+        editor.handle_key(KeyEvent::from(KeyCode::Left));
+        editor.handle_key(KeyEvent::from(KeyCode::Left));
+        assert!(editor.operators[0].is_list());
+        assert_eq!(editor.rows().len(), 8);
+
+        editor.handle_key(ctrl('n'));
+        assert_eq!(editor.rows()[editor.cursor], Row::Operator(1, 0));
+        select(&mut editor, Row::Operator(1, 3));
+        type_text(&mut editor, "/usr/bin/firefox");
+
+        // A nested list, with an env var match in it.
+        editor.handle_key(ctrl('a'));
+        editor.handle_key(KeyEvent::from(KeyCode::Left));
+        editor.handle_key(KeyEvent::from(KeyCode::Left));
+        assert!(editor.operators[2].is_list());
+        editor.handle_key(ctrl('n'));
+        select(&mut editor, Row::Operator(3, 1));
+        while editor.field(Row::Operator(3, 1)).value != "process.env." {
+            editor.handle_key(KeyEvent::from(KeyCode::Right));
+        }
+        select(&mut editor, Row::Operator(3, 2));
+        type_text(&mut editor, "DISPLAY");
+        select(&mut editor, Row::Operator(3, 3));
+        type_text(&mut editor, ":0");
+
+        let rule = editor.build(&["other"]).expect("build failed");
+        assert_eq!(rule.name, "web");
+        let root = rule.operator.unwrap();
+        assert_eq!(root.list.len(), 2);
+        assert_eq!(root.list[0].data, "/usr/bin/firefox");
+        assert_eq!(root.list[1].list[0].operand, "process.env.DISPLAY");
+        assert!(root.data.contains("process.env.DISPLAY"));
+
+        // Removing the nested list takes its member with it.
+        select(&mut editor, Row::Operator(2, 0));
+        editor.handle_key(ctrl('d'));
+        assert_eq!(editor.operators.len(), 2);
+        select(&mut editor, Row::Operator(0, 0));
+        editor.handle_key(ctrl('d'));
+        assert_eq!(editor.operators.len(), 2);
+        assert!(editor.build(&["web"]).is_err());
+    }
+
+    /// Test that operator data is checked against its type and operand.
+    #[test]
+    fn test_validate_operator() {
+        assert!(validate_operator("simple", "dest.port", "443").is_ok());
+        assert!(validate_operator("simple", "dest.port", "70000").is_err());
+        assert!(validate_operator("simple", "user.id", "root").is_err());
+        assert!(validate_operator("simple", "dest.ip", "10.0.0.300").is_err());
+        assert!(validate_operator("network", "dest.network", "10.0.0.0/8").is_ok());
+        assert!(validate_operator("network", "dest.network", "fd00::/129").is_err());
+        assert!(validate_operator("simple", "dest.network", "10.0.0.0/8").is_err());
+        assert!(validate_operator("lists", "lists.domains", "/etc/lists").is_ok());
+        assert!(validate_operator("regexp", "lists.domains", "/etc/lists").is_err());
+        assert!(validate_operator("regexp", "dest.host", r".*\.example\.com").is_ok());
+    }
+}
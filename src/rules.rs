@@ -0,0 +1,249 @@
+//! Daemon rules as shown in the rules browser: ordering, and operator descriptions.
+
+use std::cmp::Ordering;
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+
+use crate::constants;
+use crate::opensnitch_proto::pb;
+use crate::rule_editor::RuleEditor;
+
+/// Column the rules table is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RuleSort {
+    Name,
+    Action,
+    Duration,
+    Enabled,
+    Precedence,
+}
+
+impl RuleSort {
+    /// Every column, in the order `s` cycles through them.
+    pub const ALL: [RuleSort; 5] = [
+        RuleSort::Name,
+        RuleSort::Action,
+        RuleSort::Duration,
+        RuleSort::Enabled,
+        RuleSort::Precedence,
+    ];
+
+    /// Column header.
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            RuleSort::Name => "Name",
+            RuleSort::Action => "Action",
+            RuleSort::Duration => "Duration",
+            RuleSort::Enabled => "On",
+            RuleSort::Precedence => "Prec",
+        }
+    }
+
+    /// The next column to sort by, wrapping around.
+    #[must_use]
+    pub fn next(self) -> RuleSort {
+        let idx = RuleSort::ALL
+            .iter()
+            .position(|sort| *sort == self)
+            .unwrap_or(0);
+        RuleSort::ALL[(idx + 1) % RuleSort::ALL.len()]
+    }
+
+    /// Order two rules by this column, then by name so the order is stable.
+    fn compare(self, a: &pb::Rule, b: &pb::Rule) -> Ordering {
+        let by_column = match self {
+            RuleSort::Name => Ordering::Equal,
+            RuleSort::Action => a.action.cmp(&b.action),
+            RuleSort::Duration => a.duration.cmp(&b.duration),
+            // Enabled and precedence rules first.
+            RuleSort::Enabled => b.enabled.cmp(&a.enabled),
+            RuleSort::Precedence => b.precedence.cmp(&a.precedence),
+        };
+        by_column.then_with(|| a.name.cmp(&b.name))
+    }
+}
+
+/// State of the rules view.
+#[derive(Clone, Debug)]
+pub struct RulesView {
+    /// Index of the selected rule in the table, in table order.
+    pub cursor: usize,
+    /// Column the table is sorted by.
+    pub sort: RuleSort,
+    /// Whether the table is sorted in reverse.
+    pub descending: bool,
+    /// Rule being created or edited, shown in place of the table.
+    pub editor: Option<RuleEditor>,
+}
+
+impl Default for RulesView {
+    fn default() -> Self {
+        Self {
+            cursor: 0,
+            sort: RuleSort::Name,
+            descending: false,
+            editor: None,
+        }
+    }
+}
+
+impl RulesView {
+    /// A node's rules in table order.
+    #[must_use]
+    pub fn sorted<'a>(&self, rules: &'a [pb::Rule]) -> Vec<&'a pb::Rule> {
+        sorted(rules, self.sort, self.descending)
+    }
+
+    /// Handle a key press: up/down to move through the node's `rules`, `s` to sort by the
+    /// next column, `S` to reverse the order. Returns whether the key was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent, rules: usize) -> bool {
+        match key_event.code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(rules.saturating_sub(1)),
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('S') => self.descending = !self.descending,
+            _ => return false,
+        }
+        true
+    }
+}
+
+/// Rules in table order.
+#[must_use]
+pub fn sorted(rules: &[pb::Rule], sort: RuleSort, descending: bool) -> Vec<&pb::Rule> {
+    let mut sorted: Vec<&pb::Rule> = rules.iter().collect();
+    sorted.sort_by(|a, b| {
+        let ordering = sort.compare(a, b);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    sorted
+}
+
+/// One-line description of what an operator matches, e.g.
+/// `process.path == /usr/bin/curl && dest.port == 443`.
+#[must_use]
+pub fn operator_summary(operator: &pb::Operator) -> String {
+    if operator.r#type == constants::RuleType::List.get_str() {
+        let parts: Vec<String> = operator.list.iter().map(operator_summary).collect();
+        return parts.join(" && ");
+    }
+    format!(
+        "{} {} {}",
+        operator.operand,
+        comparison(&operator.r#type),
+        operator.data
+    )
+}
+
+/// The operator tree, one line per operator, with list members indented under their list.
+#[must_use]
+pub fn operator_tree(operator: &pb::Operator) -> Vec<String> {
+    let mut lines = Vec::new();
+    push_operator_lines(operator, 0, &mut lines);
+    lines
+}
+
+fn push_operator_lines(operator: &pb::Operator, depth: usize, lines: &mut Vec<String>) {
+    let indent = "  ".repeat(depth);
+    let sensitive = if operator.sensitive {
+        " (case sensitive)"
+    } else {
+        ""
+    };
+    if operator.r#type == constants::RuleType::List.get_str() {
+        lines.push(format!("{indent}• all of ({}):", operator.list.len()));
+        for member in &operator.list {
+            push_operator_lines(member, depth + 1, lines);
+        }
+    } else {
+        lines.push(format!(
+            "{indent}• {} [{}] {}{sensitive}",
+            operator.operand, operator.r#type, operator.data
+        ));
+    }
+}
+
+/// How an operator type compares the operand against its data.
+fn comparison(r#type: &str) -> &'static str {
+    match r#type {
+        "simple" => "==",
+        "regexp" => "=~",
+        "network" => "in",
+        "lists" => "in list",
+        _ => "?",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::operator_util;
+
+    fn rule(name: &str, action: &str, enabled: bool) -> pb::Rule {
+        pb::Rule {
+            name: String::from(name),
+            action: String::from(action),
+            enabled,
+            ..Default::default()
+        }
+    }
+
+    /// Test sorting by each column, with name as the tie breaker.
+    #[test]
+    fn test_sorted() {
+        let rules = vec![
+            rule("b", "deny", true),
+            rule("c", "allow", false),
+            rule("a", "deny", false),
+        ];
+        let names = |sort, descending| -> Vec<String> {
+            sorted(&rules, sort, descending)
+                .iter()
+                .map(|rule| rule.name.clone())
+                .collect()
+        };
+        assert_eq!(names(RuleSort::Name, false), vec!["a", "b", "c"]);
+        assert_eq!(names(RuleSort::Name, true), vec!["c", "b", "a"]);
+        assert_eq!(names(RuleSort::Action, false), vec!["c", "a", "b"]);
+        assert_eq!(names(RuleSort::Enabled, false), vec!["b", "a", "c"]);
+        assert_eq!(RuleSort::Precedence.next(), RuleSort::Name);
+    }
+
+    /// Test describing nested operators.
+    #[test]
+    fn test_operator_description() {
+        let operator = pb::Operator {
+            r#type: String::from("list"),
+            operand: String::from("list"),
+            data: String::new(),
+            sensitive: false,
+            list: vec![
+                operator_util::match_proc_path("/usr/bin/curl"),
+                pb::Operator {
+                    r#type: String::from("regexp"),
+                    operand: String::from("dest.host"),
+                    data: String::from(r".*\.example\.com"),
+                    sensitive: true,
+                    list: vec![],
+                },
+            ],
+        };
+        assert_eq!(
+            operator_summary(&operator),
+            r"process.path == /usr/bin/curl && dest.host =~ .*\.example\.com"
+        );
+        assert_eq!(
+            operator_tree(&operator),
+            vec![
+                String::from("• all of (2):"),
+                String::from("  • process.path [simple] /usr/bin/curl"),
+                String::from(r"  • dest.host [regexp] .*\.example\.com (case sensitive)"),
+            ]
+        );
+    }
+}
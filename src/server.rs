@@ -1,35 +1,257 @@
 use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
 use std::time::{Duration, SystemTime};
 
+use tokio::net::UnixListener;
+use tokio::task::JoinHandle;
 use tokio::time::timeout;
 use tonic::Streaming;
+use tonic::transport::server::{TcpIncoming, TlsConnectInfo, UdsConnectInfo};
+use tonic::transport::{Certificate, Identity, ServerTlsConfig};
 use tonic::{Request, Response, Status, transport::Server};
 
 use crate::alert;
-use crate::event::{AppEvent, ConnectionEvent, Event, PingEvent};
+use crate::constants;
+use crate::event::{AppEvent, ConnectionEvent, Event, Peer, PingEvent, SubscribeEvent};
+use crate::node::{NodeId, StreamState};
+use crate::opensnitch_json::{self, OpenSnitchDaemonConfig};
 use crate::opensnitch_proto::pb;
 use crate::opensnitch_proto::pb::ui_server::Ui;
 use crate::opensnitch_proto::pb::ui_server::UiServer;
-use crate::{constants, opensnitch_json};
 
+use futures::{Stream, StreamExt};
+use std::collections::HashMap;
 use std::sync::Arc;
-use tokio::sync::{Mutex, mpsc};
-use tokio_stream::wrappers::ReceiverStream;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::{Mutex, mpsc, oneshot};
+use tokio_stream::wrappers::{ReceiverStream, UnixListenerStream};
+
+/// Scheme prefix for Unix domain socket bind strings, as used by the daemon config.
+const UNIX_SCHEME: &str = "unix:";
+
+/// Permissions applied to a freshly bound Unix domain socket.
+/// The daemon runs as root, so owner-only access doesn't keep it out.
+const UNIX_SOCKET_MODE: u32 = 0o600;
+
+/// How long in-flight calls get to finish once the server is told to stop.
+const SHUTDOWN_GRACE: Duration = Duration::from_secs(3);
+
+/// Transport endpoint for the gRPC server to listen on.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindAddress {
+    /// TCP listener, e.g. `127.0.0.1:50051`.
+    Tcp(SocketAddr),
+    /// Unix domain socket listener, e.g. `unix:///tmp/osui.sock`.
+    Unix(PathBuf),
+}
+
+impl BindAddress {
+    /// Parses a bind string in either `A.B.C.D:port`, `[A:B:C::D]:port` or
+    /// `unix:///path/to/socket` form.
+    /// # Errors
+    /// Returns a description of the problem if the string can't be parsed.
+    pub fn parse(s: &str) -> Result<BindAddress, String> {
+        if let Some(rest) = s.strip_prefix(UNIX_SCHEME) {
+            // Accept both "unix:///path" and "unix:/path", just like the daemon does.
+            let path = rest.strip_prefix("//").unwrap_or(rest);
+            if path.is_empty() {
+                return Err(format!("Missing socket path in bind address '{s}'"));
+            }
+            return Ok(BindAddress::Unix(PathBuf::from(path)));
+        }
+        s.parse::<SocketAddr>()
+            .map(BindAddress::Tcp)
+            .map_err(|err| format!("Error parsing bind address '{s}' : {err}"))
+    }
+}
+
+impl BindAddress {
+    /// Whether only processes on this host can reach the listener.
+    #[must_use]
+    pub fn is_local(&self) -> bool {
+        match self {
+            BindAddress::Tcp(addr) => addr.ip().is_loopback(),
+            BindAddress::Unix(_) => true,
+        }
+    }
+}
+
+impl std::fmt::Display for BindAddress {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BindAddress::Tcp(addr) => write!(f, "{addr}"),
+            BindAddress::Unix(path) => write!(f, "{UNIX_SCHEME}//{}", path.display()),
+        }
+    }
+}
+
+/// PEM file locations for terminating TLS on the gRPC listener.
+/// These mirror the daemon's `Server.Authentication.TLSOptions`: the daemon's `CACert`
+/// must have signed `cert`, and `client_ca` must have signed the daemon's `ClientCert`.
+#[derive(Clone, Debug)]
+pub struct TlsOptions {
+    /// Server certificate chain.
+    pub cert: PathBuf,
+    /// Server private key.
+    pub key: PathBuf,
+    /// CA used to verify daemon (client) certificates. Enables mutual TLS.
+    pub client_ca: Option<PathBuf>,
+    /// Accept daemons that present no client certificate at all.
+    /// Matches the daemon's `verify-cert` client auth type rather than `req-and-verify-cert`.
+    pub client_auth_optional: bool,
+}
+
+impl TlsOptions {
+    /// Reads the PEM files and builds a tonic TLS config from them.
+    /// # Errors
+    /// Returns a description of the problem if a file can't be read.
+    pub fn load(&self) -> Result<ServerTlsConfig, String> {
+        let read = |path: &Path| {
+            std::fs::read(path).map_err(|err| format!("Unable to read '{}': {err}", path.display()))
+        };
+        let mut config = ServerTlsConfig::new()
+            .identity(Identity::from_pem(read(&self.cert)?, read(&self.key)?));
+        if let Some(client_ca) = &self.client_ca {
+            config = config
+                .client_ca_root(Certificate::from_pem(read(client_ca)?))
+                .client_auth_optional(self.client_auth_optional);
+        }
+        Ok(config)
+    }
+}
+
+/// Binds a Unix domain socket listener at `path`.
+/// A stale socket file left behind by a previous run is removed first, but a socket that
+/// still accepts connections (another live server) or a non-socket file is left alone.
+fn bind_unix(path: &Path) -> std::io::Result<UnixListener> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    match std::fs::symlink_metadata(path) {
+        Ok(meta) => {
+            if !meta.file_type().is_socket() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AlreadyExists,
+                    format!("{} exists and is not a socket", path.display()),
+                ));
+            }
+            if std::os::unix::net::UnixStream::connect(path).is_ok() {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::AddrInUse,
+                    format!("{} is in use by another server", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+        Err(err) => return Err(err),
+    }
+
+    let listener = UnixListener::bind(path)?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(UNIX_SOCKET_MODE))?;
+    Ok(listener)
+}
+
+/// Describes the daemon on the other end of a request, whatever transport it came in on.
+fn peer_of<T>(request: &Request<T>) -> Option<Peer> {
+    if let Some(addr) = request.remote_addr() {
+        return Some(Peer::Tcp(addr));
+    }
+    let extensions = request.extensions();
+    extensions
+        .get::<UdsConnectInfo>()
+        .or_else(|| {
+            extensions
+                .get::<TlsConnectInfo<UdsConnectInfo>>()
+                .map(TlsConnectInfo::get_ref)
+        })
+        .map(|info| Peer::Unix {
+            pid: info.peer_cred.and_then(|cred| cred.pid()),
+            uid: info.peer_cred.map(|cred| cred.uid()),
+        })
+}
+
+/// Per-node handles for the app to send notifications to daemons over their notifications stream.
+/// A node's sender handle gets replaced whenever that daemon reopens the stream.
+pub type NotificationSenders =
+    Arc<Mutex<HashMap<NodeId, mpsc::Sender<Result<pb::Notification, Status>>>>>;
+
+/// Node each subscribed peer belongs to, from the name it subscribed with.
+pub type SubscribedPeers = Arc<std::sync::Mutex<HashMap<Peer, NodeId>>>;
+
+/// In-flight `AskRule` calls awaiting a rule from the app, keyed by request ID.
+pub type PendingRules = Arc<std::sync::Mutex<HashMap<u64, oneshot::Sender<pb::Rule>>>>;
+
+/// Removes a request's pending rule slot once its `AskRule` call is over, however it ended.
+/// If the app never answered, it's told to drop the prompt, under whichever node the peer
+/// is known as by then, since subscribing while the call was open re-keys its prompts.
+struct PendingRuleGuard<'a> {
+    server: &'a OpenSnitchUIGrpcServer,
+    peer: Option<&'a Peer>,
+    id: u64,
+}
+
+impl Drop for PendingRuleGuard<'_> {
+    fn drop(&mut self) {
+        let unanswered = self
+            .server
+            .pending_rules
+            .lock()
+            .unwrap()
+            .remove(&self.id)
+            .is_some();
+        if unanswered {
+            self.server.send_event(AppEvent::AskRuleClosed {
+                node: self.server.node_of(self.peer),
+                id: self.id,
+            });
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct OpenSnitchUIGrpcServer {
     /// Send events to app.
     server_to_app_event_sender: mpsc::UnboundedSender<Event>,
-    /// Handle for app to send notifications to the daemon connected to this server for notifications streaming.
-    app_to_server_notification_sender: Arc<Mutex<mpsc::Sender<Result<pb::Notification, Status>>>>,
-    /// Receiver for rules generated by app, to be sent to clients.
-    app_to_server_rule_receiver: Mutex<mpsc::Receiver<pb::Rule>>,
+    /// Handles for app to send notifications to the daemons connected to this server for notifications streaming.
+    app_to_server_notification_senders: NotificationSenders,
+    /// Rule reply slots for the `AskRule` calls in flight.
+    pending_rules: PendingRules,
+    /// Nodes of the peers that have subscribed.
+    subscribed_peers: SubscribedPeers,
+    /// ID for the next `AskRule` call, used to route the app's rule back to it.
+    next_request_id: AtomicU64,
     /// Default action to be passed to clients.
     default_action: String,
     /// Duration to wait for app to provide a rule for client that's trapped a connection.
     connection_disposition_timeout: Duration,
-    /// Mutex to ensure only one `AskRule` request is active at a time.
-    askrule_lock: Mutex<()>,
+}
+
+impl OpenSnitchUIGrpcServer {
+    fn send_event(&self, event: AppEvent) {
+        let _ = self
+            .server_to_app_event_sender
+            .send(Event::App(Box::new(event)));
+    }
+
+    /// Node key for a request: the one its peer subscribed as, or else its address.
+    fn node_of(&self, peer: Option<&Peer>) -> NodeId {
+        peer.and_then(|peer| self.subscribed_peers.lock().unwrap().get(peer).cloned())
+            .unwrap_or_else(|| node_of(peer))
+    }
+}
+
+/// Drop a peer's subscription, if it's still the one for `node`.
+fn forget_peer(subscribed_peers: &SubscribedPeers, peer: &Peer, node: &NodeId) {
+    let mut subscribed = subscribed_peers.lock().unwrap();
+    if subscribed.get(peer) == Some(node) {
+        subscribed.remove(peer);
+    }
+}
+
+/// Node key for a request from a peer that hasn't subscribed, falling back to a shared key
+/// if the transport didn't identify the peer.
+fn node_of(peer: Option<&Peer>) -> NodeId {
+    peer.map_or_else(|| String::from("unknown"), Peer::node_id)
 }
 
 #[tonic::async_trait]
@@ -40,13 +262,13 @@ impl Ui for OpenSnitchUIGrpcServer {
         &self,
         request: Request<pb::PingRequest>,
     ) -> Result<Response<pb::PingReply>, Status> {
+        let peer = peer_of(&request);
         let event = PingEvent {
-            peer: request.remote_addr(),
+            node: self.node_of(peer.as_ref()),
+            peer,
             stats: request.get_ref().stats.as_ref().unwrap().clone(),
         };
-        let _ = self
-            .server_to_app_event_sender
-            .send(Event::App(Box::new(AppEvent::Update(event))));
+        self.send_event(AppEvent::Update(event));
 
         let reply = pb::PingReply {
             id: request.get_ref().id,
@@ -59,13 +281,13 @@ impl Ui for OpenSnitchUIGrpcServer {
         &self,
         request: Request<pb::Alert>,
     ) -> Result<Response<pb::MsgResponse>, Status> {
+        let node = self.node_of(peer_of(&request).as_ref());
         let alert = request.get_ref();
-        let _ = self
-            .server_to_app_event_sender
-            .send(Event::App(Box::new(AppEvent::Alert(alert::Alert::new(
-                std::time::SystemTime::now(),
-                alert,
-            )))));
+        self.send_event(AppEvent::Alert(alert::Alert::new(
+            Some(node),
+            std::time::SystemTime::now(),
+            alert,
+        )));
 
         let reply = pb::MsgResponse {
             id: request.get_ref().id,
@@ -78,31 +300,32 @@ impl Ui for OpenSnitchUIGrpcServer {
         &self,
         request: Request<pb::Connection>,
     ) -> Result<Response<pb::Rule>, Status> {
-        // In theory, the current proto spec and OpenSnitch daemon design doesn't seem
-        // to permit opening concurrent `AskRule` requests.
-        // If this was to be supported in the future, we'd want to mix in some UID
-        // for request routing/identification as well.
-        let askrule_permit = self.askrule_lock.try_lock();
-        if askrule_permit.is_err() {
-            return Err(Status::resource_exhausted(
-                "Only one AskRule request permitted at a time",
-            ));
-        }
+        // Several daemons, or one daemon across a reconnect, may have calls open at once.
+        // Each gets an ID so the app can queue them and answer them in any order.
+        let peer = peer_of(&request);
+        let node = self.node_of(peer.as_ref());
+        let id = self.next_request_id.fetch_add(1, Ordering::Relaxed);
+        let (rule_tx, rule_rx) = oneshot::channel();
+        self.pending_rules.lock().unwrap().insert(id, rule_tx);
+        let _guard = PendingRuleGuard {
+            server: self,
+            peer: peer.as_ref(),
+            id,
+        };
 
         let connection = ConnectionEvent {
+            id,
+            node: node.clone(),
             connection: request.get_ref().clone(),
             expiry_ts: SystemTime::now() + self.connection_disposition_timeout,
         };
-        let _ = self
-            .server_to_app_event_sender
-            .send(Event::App(Box::new(AppEvent::AskRule(connection))));
+        self.send_event(AppEvent::AskRule(connection));
 
-        let mut recv_lock = self.app_to_server_rule_receiver.lock().await;
-        let maybe_rule = timeout(self.connection_disposition_timeout, recv_lock.recv()).await;
+        let maybe_rule = timeout(self.connection_disposition_timeout, rule_rx).await;
         match maybe_rule {
             Ok(possibly_rule) => match possibly_rule {
-                Some(rule) => Ok(Response::new(rule)),
-                None => Err(Status::internal("sender somehow closed")),
+                Ok(rule) => Ok(Response::new(rule)),
+                Err(_) => Err(Status::internal("sender somehow closed")),
             },
             Err(err) => Err(Status::internal(format!("No rule created: {err}"))),
         }
@@ -112,78 +335,131 @@ impl Ui for OpenSnitchUIGrpcServer {
         &self,
         request: Request<pb::ClientConfig>,
     ) -> Result<Response<pb::ClientConfig>, Status> {
-        // Relfect back most of the rx'ed config.
-        // Be a little oversmart here and rewrite the config JSON blob with the only k-v
-        // the daemon really cares about - default action.
-        let mut reply = request.get_ref().clone();
-        let config = opensnitch_json::OpenSnitchDaemonConfig {
-            DefaultAction: self.default_action.clone(),
-        };
-        let maybe_config_json = serde_json::to_string(&config);
-        match maybe_config_json {
-            Ok(json) => {
-                reply.config = json;
-                Ok(Response::new(reply))
+        let peer = peer_of(&request);
+        let client_config = request.into_inner();
+        let node = match &peer {
+            Some(peer) => {
+                let node = peer.named_node_id(&client_config.name);
+                let mut subscribed = self.subscribed_peers.lock().unwrap();
+                // A daemon only has one connection at a time, so an older one is gone.
+                subscribed.retain(|_, other| *other != node);
+                subscribed.insert(peer.clone(), node.clone());
+                node
             }
-            Err(err) => Err(Status::internal(err.to_string())),
+            None => node_of(None),
+        };
+
+        // Reflect the rx'ed config back, with the TUI's overrides patched in and every other
+        // key left as sent. Anything we can't make sense of goes back untouched.
+        let mut reply = client_config.clone();
+        let overrides = serde_json::json!({ "DefaultAction": self.default_action });
+        if let Ok(json) = opensnitch_json::merge(&client_config.config, &overrides) {
+            reply.config = json;
         }
+        let config = match OpenSnitchDaemonConfig::parse(&client_config.config) {
+            Ok(config) => Some(config),
+            Err(err) => {
+                self.send_event(AppEvent::Alert(alert::Alert {
+                    node: Some(node.clone()),
+                    timestamp: SystemTime::now(),
+                    priority: alert::Priority::Medium,
+                    r#type: alert::Type::Warning,
+                    what: alert::What::Generic,
+                    msg: err,
+                }));
+                None
+            }
+        };
+
+        self.send_event(AppEvent::Subscribe(Box::new(SubscribeEvent {
+            node,
+            peer,
+            client_config,
+            config,
+        })));
+        Ok(Response::new(reply))
     }
 
     async fn notifications(
         &self,
         request: Request<Streaming<pb::NotificationReply>>,
     ) -> Result<Response<Self::NotificationsStream>, Status> {
+        let peer = peer_of(&request);
+        let node = self.node_of(peer.as_ref());
         let mut in_stream = request.into_inner();
         let (app_to_server_notification_tx, app_to_server_notification_rx) = mpsc::channel(128);
         let tx = self.server_to_app_event_sender.clone();
+        let subscribed_peers = Arc::clone(&self.subscribed_peers);
 
-        // Grab a lock on the app to server notification sender, then swaparoo the new sender in.
+        // Grab a lock on the app to server notification senders, then swaparoo the new sender in.
         // A pre-existing receiver on the old sender should also eventually close since its sender will have closed.
-        let mut sender_chan = self.app_to_server_notification_sender.lock().await;
-        *sender_chan = app_to_server_notification_tx;
+        let notification_senders = Arc::clone(&self.app_to_server_notification_senders);
+        let this_stream_tx = app_to_server_notification_tx.clone();
+        notification_senders
+            .lock()
+            .await
+            .insert(node.clone(), app_to_server_notification_tx);
+        self.send_event(AppEvent::NotificationStream {
+            node: node.clone(),
+            state: StreamState::Open,
+        });
 
         tokio::spawn(async move {
+            let send_alert = |priority, r#type, msg| {
+                let _ = tx.send(Event::App(Box::new(AppEvent::Alert(alert::Alert {
+                    node: Some(node.clone()),
+                    timestamp: std::time::SystemTime::now(),
+                    priority,
+                    r#type,
+                    what: alert::What::Generic,
+                    msg,
+                }))));
+            };
             loop {
                 let stream_grpc_event = in_stream.message().await;
                 if let Ok(nominal_grpc_event) = stream_grpc_event {
-                    if let Some(notification) = nominal_grpc_event {
-                        match notification.code() {
-                            pb::NotificationReplyCode::Error => {
-                                // Redirect error notifications to the alerts channel
-                                let _ =
-                                    tx.send(Event::App(Box::new(AppEvent::Alert(alert::Alert {
-                                        timestamp: std::time::SystemTime::now(),
-                                        priority: alert::Priority::Medium,
-                                        r#type: alert::Type::Error,
-                                        what: alert::What::Generic,
-                                        msg: notification.data,
-                                    }))));
-                            }
-                            pb::NotificationReplyCode::Ok => {}
-                        }
+                    if let Some(reply) = nominal_grpc_event {
+                        // The app matches replies to what it sent, and alerts on errors.
+                        let _ = tx.send(Event::App(Box::new(AppEvent::NotificationReply {
+                            node: node.clone(),
+                            reply,
+                        })));
                     } else {
                         // Stream closed by peer
-                        let _ = tx.send(Event::App(Box::new(AppEvent::Alert(alert::Alert {
-                            timestamp: std::time::SystemTime::now(),
-                            priority: alert::Priority::High,
-                            r#type: alert::Type::Warning,
-                            what: alert::What::Generic,
-                            msg: String::from("gRPC stream closed by daemon"),
-                        }))));
+                        send_alert(
+                            alert::Priority::High,
+                            alert::Type::Warning,
+                            String::from("gRPC stream closed by daemon"),
+                        );
                         break;
                     }
                 } else {
                     // gRPC error from peer on stream
-                    let _ = tx.send(Event::App(Box::new(AppEvent::Alert(alert::Alert {
-                        timestamp: std::time::SystemTime::now(),
-                        priority: alert::Priority::High,
-                        r#type: alert::Type::Warning,
-                        what: alert::What::Generic,
-                        msg: format!("gRPC error from daemon: {}", stream_grpc_event.unwrap_err()),
-                    }))));
+                    send_alert(
+                        alert::Priority::High,
+                        alert::Type::Warning,
+                        format!("gRPC error from daemon: {}", stream_grpc_event.unwrap_err()),
+                    );
                     break;
                 }
             }
+
+            // Only report the node's stream as closed if the daemon hasn't already replaced it.
+            let mut senders = notification_senders.lock().await;
+            if senders
+                .get(&node)
+                .is_some_and(|current| current.same_channel(&this_stream_tx))
+            {
+                senders.remove(&node);
+                // The connection is gone, and whatever comes from its address next is new.
+                if let Some(peer) = &peer {
+                    forget_peer(&subscribed_peers, peer, &node);
+                }
+                let _ = tx.send(Event::App(Box::new(AppEvent::NotificationStream {
+                    node,
+                    state: StreamState::Closed,
+                })));
+            }
         });
 
         // Return a stream wrapper over the app to server notifications Receiver.
@@ -192,39 +468,542 @@ impl Ui for OpenSnitchUIGrpcServer {
     }
 }
 
-#[derive(Debug, Default)]
-pub struct OpenSnitchUIServer {}
+/// What the gRPC server is up to.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ServerState {
+    /// Not serving yet, or stopped on request.
+    Stopped,
+    /// Serving on the bind address.
+    Listening,
+    /// Gave up serving, with the reason why.
+    Failed(String),
+}
+
+/// A listener bound ahead of serving, so that bind failures surface at startup.
+#[derive(Debug)]
+enum Listener {
+    Tcp(TcpIncoming),
+    Unix(UnixListener),
+}
+
+#[derive(Debug)]
+pub struct OpenSnitchUIServer {
+    /// Endpoint to listen on.
+    address: BindAddress,
+    /// TLS settings, if terminating TLS. Consumed when the server is spawned.
+    tls_config: Option<ServerTlsConfig>,
+    /// Listener bound ahead of serving. Consumed when the server is spawned.
+    listener: Option<Listener>,
+    /// Tells the running server to stop accepting and wind down.
+    shutdown_tx: Option<oneshot::Sender<()>>,
+    /// The running server.
+    task: Option<JoinHandle<()>>,
+}
 
 impl OpenSnitchUIServer {
-    /// Note for address: Unix domain sockets unsupported due to upstream "authority" handling bug
+    #[must_use]
+    pub fn new(address: BindAddress, tls_config: Option<ServerTlsConfig>) -> Self {
+        Self {
+            address,
+            tls_config,
+            listener: None,
+            shutdown_tx: None,
+            task: None,
+        }
+    }
+
+    /// Stops the server, giving in-flight calls up to [`SHUTDOWN_GRACE`] to finish.
+    /// A noop if the server isn't running.
+    pub async fn shutdown(&mut self) {
+        if let Some(shutdown_tx) = self.shutdown_tx.take() {
+            let _ = shutdown_tx.send(());
+        }
+        if let Some(task) = self.task.take() {
+            let _ = timeout(SHUTDOWN_GRACE, task).await;
+        }
+    }
+
+    /// Endpoint the server listens on.
+    #[must_use]
+    pub fn address(&self) -> &BindAddress {
+        &self.address
+    }
+
+    /// Validates the TLS settings and binds the listener, without serving on it yet.
+    /// # Errors
+    /// Returns a description of the problem if the TLS settings are unusable or the
+    /// address can't be bound, e.g. because another server already holds it.
+    pub fn bind(&mut self) -> Result<(), String> {
+        if let Some(tls_config) = &self.tls_config {
+            Server::builder()
+                .tls_config(tls_config.clone())
+                .map_err(|err| format!("Invalid TLS configuration: {}", error_chain(&err)))?;
+        }
+        let listener = match &self.address {
+            BindAddress::Tcp(addr) => TcpIncoming::bind(*addr)
+                .map(|incoming| Listener::Tcp(incoming.with_nodelay(Some(true)))),
+            BindAddress::Unix(path) => bind_unix(path).map(Listener::Unix),
+        }
+        .map_err(|err| format!("Unable to listen on {}: {err}", self.address))?;
+        self.listener = Some(listener);
+        Ok(())
+    }
+
+    /// Starts serving in the background, binding first if [`Self::bind`] wasn't called.
+    /// Progress is reported to the app as [`AppEvent::ServerState`] events.
     pub fn spawn_and_run(
-        &self,
-        address: SocketAddr,
+        &mut self,
         server_to_app_event_sender: mpsc::UnboundedSender<Event>,
-        app_to_server_notification_sender: &Arc<
-            Mutex<mpsc::Sender<Result<pb::Notification, Status>>>,
-        >,
-        app_to_server_rule_receiver: mpsc::Receiver<pb::Rule>,
+        app_to_server_notification_senders: &NotificationSenders,
+        pending_rules: &PendingRules,
         default_action: constants::DefaultAction,
         connection_disposition_timeout: Duration,
     ) {
-        let server_to_app_event_sender_handle = server_to_app_event_sender;
-        let notification_sender = Arc::clone(app_to_server_notification_sender);
-        let rule_receiver = Mutex::new(app_to_server_rule_receiver);
-        let default_action_str = String::from(default_action.get_str());
-        tokio::spawn(async move {
-            let grpc_server = OpenSnitchUIGrpcServer {
-                server_to_app_event_sender: server_to_app_event_sender_handle,
-                app_to_server_notification_sender: notification_sender,
-                app_to_server_rule_receiver: rule_receiver,
-                default_action: default_action_str,
-                connection_disposition_timeout,
-                askrule_lock: Mutex::default(),
+        let event_sender = server_to_app_event_sender.clone();
+        let send_state = move |state| {
+            let _ = event_sender.send(Event::App(Box::new(AppEvent::ServerState(state))));
+        };
+        if self.listener.is_none()
+            && let Err(err) = self.bind()
+        {
+            send_state(ServerState::Failed(err));
+            return;
+        }
+        let Some(listener) = self.listener.take() else {
+            return;
+        };
+        send_state(ServerState::Listening);
+
+        let grpc_server = OpenSnitchUIGrpcServer {
+            server_to_app_event_sender,
+            app_to_server_notification_senders: Arc::clone(app_to_server_notification_senders),
+            pending_rules: Arc::clone(pending_rules),
+            subscribed_peers: SubscribedPeers::default(),
+            next_request_id: AtomicU64::new(0),
+            default_action: String::from(default_action.get_str()),
+            connection_disposition_timeout,
+        };
+        let address = self.address.clone();
+        let tls_config = self.tls_config.take();
+        let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
+        self.shutdown_tx = Some(shutdown_tx);
+        self.task = Some(tokio::spawn(async move {
+            let signal = async {
+                let _ = shutdown_rx.await;
+            };
+            let state = match serve(grpc_server, listener, tls_config, signal).await {
+                Ok(()) => ServerState::Stopped,
+                Err(err) => ServerState::Failed(err),
             };
-            let _ = Server::builder()
-                .add_service(UiServer::new(grpc_server))
-                .serve(address)
-                .await;
+            if let BindAddress::Unix(path) = address {
+                // Don't leave a dead socket behind for the next run to trip over.
+                let _ = std::fs::remove_file(path);
+            }
+            send_state(state);
+        }));
+    }
+}
+
+/// Serves the UI service on a bound listener until `signal` resolves.
+/// Connections that can't be accepted are reported as alerts, and don't stop the server.
+async fn serve(
+    grpc_server: OpenSnitchUIGrpcServer,
+    listener: Listener,
+    tls_config: Option<ServerTlsConfig>,
+    signal: impl Future<Output = ()>,
+) -> Result<(), String> {
+    let event_sender = grpc_server.server_to_app_event_sender.clone();
+    let mut builder = Server::builder();
+    if let Some(tls_config) = tls_config {
+        builder = builder
+            .tls_config(tls_config)
+            .map_err(|err| format!("Invalid TLS configuration: {}", error_chain(&err)))?;
+    }
+    let router = builder.add_service(UiServer::new(grpc_server));
+    match listener {
+        Listener::Tcp(incoming) => {
+            let incoming = report_accept_errors(incoming, event_sender);
+            router.serve_with_incoming_shutdown(incoming, signal).await
+        }
+        Listener::Unix(listener) => {
+            let incoming = report_accept_errors(UnixListenerStream::new(listener), event_sender);
+            router.serve_with_incoming_shutdown(incoming, signal).await
+        }
+    }
+    .map_err(|err| format!("gRPC server failed: {}", error_chain(&err)))
+}
+
+/// Passes accepted connections through, raising an alert for each one that failed.
+fn report_accept_errors<IO>(
+    incoming: impl Stream<Item = std::io::Result<IO>>,
+    event_sender: mpsc::UnboundedSender<Event>,
+) -> impl Stream<Item = std::io::Result<IO>> {
+    incoming.inspect(move |accepted| {
+        if let Err(err) = accepted {
+            let _ = event_sender.send(Event::App(Box::new(AppEvent::Alert(alert::Alert {
+                node: None,
+                timestamp: SystemTime::now(),
+                priority: alert::Priority::Medium,
+                r#type: alert::Type::Warning,
+                what: alert::What::Generic,
+                msg: format!("Failed to accept connection: {err}"),
+            }))));
+        }
+    })
+}
+
+/// Formats an error along with its sources, since transport errors tend to be terse.
+fn error_chain(err: &dyn std::error::Error) -> String {
+    let mut msg = err.to_string();
+    let mut source = err.source();
+    while let Some(err) = source {
+        msg = format!("{msg}: {err}");
+        source = err.source();
+    }
+    msg
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tonic::transport::server::TcpConnectInfo;
+
+    /// Helper to get a socket path unique to this test process.
+    fn temp_socket_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("osui-test-{}-{name}.sock", std::process::id()))
+    }
+
+    /// Test parsing of the bind string forms we accept.
+    #[test]
+    fn test_bind_address_parse() {
+        assert_eq!(
+            BindAddress::parse("127.0.0.1:50051"),
+            Ok(BindAddress::Tcp("127.0.0.1:50051".parse().unwrap()))
+        );
+        assert_eq!(
+            BindAddress::parse("[::1]:50051"),
+            Ok(BindAddress::Tcp("[::1]:50051".parse().unwrap()))
+        );
+        assert_eq!(
+            BindAddress::parse("unix:///tmp/osui.sock"),
+            Ok(BindAddress::Unix(PathBuf::from("/tmp/osui.sock")))
+        );
+        assert_eq!(
+            BindAddress::parse("unix:/tmp/osui.sock"),
+            Ok(BindAddress::Unix(PathBuf::from("/tmp/osui.sock")))
+        );
+        assert!(BindAddress::parse("unix://").is_err());
+        assert!(BindAddress::parse("localhost").is_err());
+    }
+
+    /// Test which bind addresses count as reachable from this host only.
+    #[test]
+    fn test_bind_address_is_local() {
+        assert!(BindAddress::parse("127.0.0.1:50051").unwrap().is_local());
+        assert!(BindAddress::parse("[::1]:50051").unwrap().is_local());
+        assert!(
+            BindAddress::parse("unix:///tmp/osui.sock")
+                .unwrap()
+                .is_local()
+        );
+        assert!(!BindAddress::parse("0.0.0.0:50051").unwrap().is_local());
+        assert!(!BindAddress::parse("192.168.1.2:50051").unwrap().is_local());
+    }
+
+    /// Test that unreadable PEM files are reported up front.
+    #[test]
+    fn test_tls_options_missing_files() {
+        let options = TlsOptions {
+            cert: PathBuf::from("/nonexistent/cert.pem"),
+            key: PathBuf::from("/nonexistent/key.pem"),
+            client_ca: None,
+            client_auth_optional: false,
+        };
+        let err = options.load().expect_err("load should fail");
+        assert!(err.contains("/nonexistent/cert.pem"));
+    }
+
+    /// Test that a leftover socket file from a dead server gets replaced.
+    #[tokio::test]
+    async fn test_bind_unix_stale_socket() {
+        let path = temp_socket_path("stale");
+        let _ = std::fs::remove_file(&path);
+        // Binding and dropping a std listener leaves the socket file behind.
+        drop(std::os::unix::net::UnixListener::bind(&path).expect("bind failed"));
+        assert!(path.exists());
+
+        let listener = bind_unix(&path).expect("stale socket not replaced");
+        let mode = std::os::unix::fs::PermissionsExt::mode(
+            &std::fs::metadata(&path).unwrap().permissions(),
+        );
+        assert_eq!(mode & 0o777, UNIX_SOCKET_MODE);
+
+        // A live socket must not be stolen from under its owner.
+        assert!(bind_unix(&path).is_err());
+        drop(listener);
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Test that we refuse to clobber a regular file.
+    #[tokio::test]
+    async fn test_bind_unix_not_a_socket() {
+        let path = temp_socket_path("regular");
+        std::fs::write(&path, "not a socket").unwrap();
+        assert!(bind_unix(&path).is_err());
+        assert!(path.exists());
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Test that shutdown stops the server and cleans up its socket.
+    #[tokio::test]
+    async fn test_shutdown() {
+        let path = temp_socket_path("shutdown");
+        let _ = std::fs::remove_file(&path);
+        let mut server = OpenSnitchUIServer::new(BindAddress::Unix(path.clone()), None);
+        let (event_tx, _event_rx) = mpsc::unbounded_channel();
+        server.spawn_and_run(
+            event_tx,
+            &NotificationSenders::default(),
+            &PendingRules::default(),
+            constants::DefaultAction::Deny,
+            Duration::from_secs(1),
+        );
+        for _ in 0..50 {
+            if path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert!(path.exists());
+
+        server.shutdown().await;
+        assert!(server.task.is_none());
+        assert!(!path.exists());
+    }
+
+    /// Test that an address already in use is reported at bind time, and that a failed
+    /// bind is reported to the app when serving.
+    #[tokio::test]
+    async fn test_bind_in_use() {
+        let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = taken.local_addr().unwrap();
+        let mut server = OpenSnitchUIServer::new(BindAddress::Tcp(addr), None);
+        let err = server.bind().expect_err("bind should fail");
+        assert!(err.contains(&addr.to_string()));
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        server.spawn_and_run(
+            event_tx,
+            &NotificationSenders::default(),
+            &PendingRules::default(),
+            constants::DefaultAction::Deny,
+            Duration::from_secs(1),
+        );
+        match event_rx.try_recv() {
+            Ok(Event::App(event)) => {
+                assert!(matches!(
+                    *event,
+                    AppEvent::ServerState(ServerState::Failed(_))
+                ));
+            }
+            _ => panic!("bind failure not reported"),
+        }
+        assert!(server.task.is_none());
+    }
+
+    /// Test that daemons behind one address are told apart by the name they subscribe
+    /// with, and their later calls are filed under it.
+    #[tokio::test]
+    async fn test_subscribe_names_node() {
+        fn from<T>(port: u16, message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(SocketAddr::from(([10, 0, 0, 1], port))),
+            });
+            request
+        }
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let grpc_server = OpenSnitchUIGrpcServer {
+            server_to_app_event_sender: event_tx,
+            app_to_server_notification_senders: NotificationSenders::default(),
+            pending_rules: PendingRules::default(),
+            subscribed_peers: SubscribedPeers::default(),
+            next_request_id: AtomicU64::new(0),
+            default_action: String::from("deny"),
+            connection_disposition_timeout: Duration::from_secs(1),
+        };
+        // The bare configs don't parse, so each subscribe also alerts; skip those.
+        let mut next_node = || loop {
+            match event_rx.try_recv() {
+                Ok(Event::App(event)) => match *event {
+                    AppEvent::Subscribe(evt) => return evt.node,
+                    AppEvent::Update(evt) => return evt.node,
+                    AppEvent::Alert(_) => {}
+                    _ => panic!("unexpected event"),
+                },
+                _ => panic!("nothing reported"),
+            }
+        };
+        let ping = || pb::PingRequest {
+            id: 1,
+            stats: Some(pb::Statistics::default()),
+        };
+
+        grpc_server.ping(from(4000, ping())).await.unwrap();
+        assert_eq!(next_node(), "10.0.0.1");
+        for (port, name) in [(4000, "laptop"), (5000, "desktop")] {
+            let config = pb::ClientConfig {
+                name: String::from(name),
+                ..Default::default()
+            };
+            grpc_server.subscribe(from(port, config)).await.unwrap();
+            assert_eq!(next_node(), format!("{name}@10.0.0.1"));
+        }
+        grpc_server.ping(from(5000, ping())).await.unwrap();
+        assert_eq!(next_node(), "desktop@10.0.0.1");
+
+        // Reconnecting moves the name over to the new connection.
+        let config = pb::ClientConfig {
+            name: String::from("laptop"),
+            ..Default::default()
+        };
+        grpc_server.subscribe(from(4001, config)).await.unwrap();
+        next_node();
+        grpc_server.ping(from(4000, ping())).await.unwrap();
+        assert_eq!(next_node(), "10.0.0.1");
+        assert_eq!(grpc_server.subscribed_peers.lock().unwrap().len(), 2);
+    }
+
+    /// Test that a closed stream only forgets its peer while the peer still maps to the
+    /// stream's node.
+    #[test]
+    fn test_forget_peer() {
+        let subscribed = SubscribedPeers::default();
+        let peer = Peer::Tcp(SocketAddr::from(([10, 0, 0, 1], 4000)));
+        let node = String::from("laptop@10.0.0.1");
+        subscribed
+            .lock()
+            .unwrap()
+            .insert(peer.clone(), String::from("desktop@10.0.0.1"));
+        forget_peer(&subscribed, &peer, &node);
+        assert_eq!(subscribed.lock().unwrap().len(), 1);
+
+        subscribed
+            .lock()
+            .unwrap()
+            .insert(peer.clone(), node.clone());
+        forget_peer(&subscribed, &peer, &node);
+        assert!(subscribed.lock().unwrap().is_empty());
+    }
+
+    /// Test that a prompt left unanswered is closed under the node its daemon subscribed
+    /// as while the call was open, where the app has moved it.
+    #[tokio::test]
+    async fn test_ask_rule_closed_after_subscribe() {
+        fn from<T>(message: T) -> Request<T> {
+            let mut request = Request::new(message);
+            request.extensions_mut().insert(TcpConnectInfo {
+                local_addr: None,
+                remote_addr: Some(SocketAddr::from(([10, 0, 0, 1], 4000))),
+            });
+            request
+        }
+
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let grpc_server = OpenSnitchUIGrpcServer {
+            server_to_app_event_sender: event_tx,
+            app_to_server_notification_senders: NotificationSenders::default(),
+            pending_rules: PendingRules::default(),
+            subscribed_peers: SubscribedPeers::default(),
+            next_request_id: AtomicU64::new(0),
+            default_action: String::from("deny"),
+            connection_disposition_timeout: Duration::from_millis(50),
+        };
+        let config = pb::ClientConfig {
+            name: String::from("laptop"),
+            ..Default::default()
+        };
+        let (asked, _) = tokio::join!(
+            grpc_server.ask_rule(from(pb::Connection::default())),
+            grpc_server.subscribe(from(config)),
+        );
+        assert!(asked.is_err());
+
+        let mut closed = None;
+        while let Ok(Event::App(event)) = event_rx.try_recv() {
+            match *event {
+                AppEvent::AskRule(evt) => assert_eq!(evt.node, "10.0.0.1"),
+                AppEvent::AskRuleClosed { node, .. } => closed = Some(node),
+                _ => {}
+            }
+        }
+        assert_eq!(closed.as_deref(), Some("laptop@10.0.0.1"));
+    }
+
+    /// Test that subscribe reflects the daemon's config back with only the default action
+    /// overridden, and passes the parsed config on to the app.
+    #[tokio::test]
+    async fn test_subscribe_preserves_config() {
+        let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+        let grpc_server = OpenSnitchUIGrpcServer {
+            server_to_app_event_sender: event_tx,
+            app_to_server_notification_senders: NotificationSenders::default(),
+            pending_rules: PendingRules::default(),
+            subscribed_peers: SubscribedPeers::default(),
+            next_request_id: AtomicU64::new(0),
+            default_action: String::from("deny"),
+            connection_disposition_timeout: Duration::from_secs(1),
+        };
+        let daemon_config = r#"{"DefaultAction":"allow","LogLevel":2,"Tasks":{"ConfigPath":"x"}}"#;
+        let request = Request::new(pb::ClientConfig {
+            config: String::from(daemon_config),
+            ..Default::default()
+        });
+        let reply = grpc_server.subscribe(request).await.unwrap().into_inner();
+        let reply: serde_json::Value = serde_json::from_str(&reply.config).unwrap();
+        assert_eq!(
+            reply,
+            serde_json::json!({"DefaultAction": "deny", "LogLevel": 2, "Tasks": {"ConfigPath": "x"}})
+        );
+        match event_rx.try_recv() {
+            Ok(Event::App(event)) => match *event {
+                AppEvent::Subscribe(evt) => {
+                    let config = evt.config.expect("config not parsed");
+                    assert_eq!(config.DefaultAction.as_deref(), Some("allow"));
+                }
+                _ => panic!("expected a subscribe event"),
+            },
+            _ => panic!("subscribe not reported"),
+        }
+
+        // A field the app can't type doesn't stop the override, though it does alert.
+        let request = Request::new(pb::ClientConfig {
+            config: String::from(r#"{"DefaultAction":"allow","LogLevel":"debug","Audit":null}"#),
+            ..Default::default()
+        });
+        let reply = grpc_server.subscribe(request).await.unwrap().into_inner();
+        assert_eq!(
+            reply.config,
+            r#"{"Audit":null,"DefaultAction":"deny","LogLevel":"debug"}"#
+        );
+        assert!(matches!(
+            event_rx.try_recv(),
+            Ok(Event::App(event)) if matches!(*event, AppEvent::Alert(_))
+        ));
+        event_rx.try_recv().unwrap();
+
+        // Garbage goes back as it came, with an alert.
+        let request = Request::new(pb::ClientConfig {
+            config: String::from("not json"),
+            ..Default::default()
         });
+        let reply = grpc_server.subscribe(request).await.unwrap().into_inner();
+        assert_eq!(reply.config, "not json");
+        assert!(matches!(
+            event_rx.try_recv(),
+            Ok(Event::App(event)) if matches!(*event, AppEvent::Alert(_))
+        ));
     }
 }
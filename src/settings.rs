@@ -0,0 +1,196 @@
+//! Daemon settings editor. Edits are sent to the daemon as a `CHANGE_CONFIG` notification
+//! carrying its whole config, with only the edited fields changed.
+
+use crate::constants;
+use crate::form::{Field, Form};
+use crate::node::NodeId;
+use crate::opensnitch_json::{OpenSnitchDaemonConfig, StatsConfig};
+
+const DEFAULT_ACTION: &str = "Default action";
+const DEFAULT_DURATION: &str = "Default duration";
+const LOG_LEVEL: &str = "Log level";
+const PROC_MONITOR: &str = "Proc monitor method";
+const INTERCEPT_UNKNOWN: &str = "Intercept unknown";
+const MAX_EVENTS: &str = "Stats max events";
+const MAX_STATS: &str = "Stats max stats";
+
+/// Log level names, indexed by the daemon's numeric level (opensnitch/daemon/log/log.go).
+pub const LOG_LEVELS: [&str; 5] = ["debug", "info", "important", "warning", "error"];
+const PROC_MONITOR_METHODS: [&str; 3] = ["ebpf", "proc", "audit"];
+/// Upper bound for the stats limits. The daemon holds this many entries in memory
+/// between pings, and sends them all in each one.
+const MAX_STATS_LIMIT: u64 = 10_000;
+
+/// Name of a daemon log level, or the number itself if it's not one the TUI knows.
+#[must_use]
+pub fn log_level_name(level: i64) -> String {
+    usize::try_from(level)
+        .ok()
+        .and_then(|idx| LOG_LEVELS.get(idx))
+        .map_or_else(|| level.to_string(), |name| String::from(*name))
+}
+
+/// Settings form for one node, and what became of the last attempt to apply it.
+#[derive(Clone, Debug)]
+pub struct SettingsEditor {
+    /// Node whose config is being edited.
+    pub node: NodeId,
+    pub form: Form,
+    /// ID of the last `CHANGE_CONFIG` notification sent from this editor.
+    pub sent: Option<u64>,
+    /// Why the form couldn't be sent, if it couldn't.
+    pub error: Option<String>,
+}
+
+impl SettingsEditor {
+    /// Loads a form with a node's current config.
+    #[must_use]
+    pub fn new(node: &NodeId, config: &OpenSnitchDaemonConfig) -> Self {
+        let log_level = config.LogLevel.map(|level| log_level_name(level.into()));
+        let stats = config.Stats.as_ref();
+        let form = Form::new(vec![
+            Field::choice(
+                DEFAULT_ACTION,
+                &constants::DefaultAction::ALL.map(|action| action.get_str()),
+                config.DefaultAction.as_deref(),
+            ),
+            Field::choice(
+                DEFAULT_DURATION,
+                &constants::Duration::ALL.map(|duration| duration.get_str()),
+                config.DefaultDuration.as_deref(),
+            ),
+            Field::choice(LOG_LEVEL, &LOG_LEVELS, log_level.as_deref()),
+            Field::choice(
+                PROC_MONITOR,
+                &PROC_MONITOR_METHODS,
+                config.ProcMonitorMethod.as_deref(),
+            ),
+            Field::toggle(INTERCEPT_UNKNOWN, config.InterceptUnknown),
+            Field::number(
+                MAX_EVENTS,
+                1,
+                MAX_STATS_LIMIT,
+                stats.and_then(|stats| stats.MaxEvents).map(u64::from),
+            ),
+            Field::number(
+                MAX_STATS,
+                1,
+                MAX_STATS_LIMIT,
+                stats.and_then(|stats| stats.MaxStats).map(u64::from),
+            ),
+        ]);
+        Self {
+            node: node.clone(),
+            form,
+            sent: None,
+            error: None,
+        }
+    }
+
+    /// Applies the edited fields to `config`, leaving everything else as it is.
+    /// # Errors
+    /// Returns a description of the invalid fields.
+    pub fn apply(&self, config: &OpenSnitchDaemonConfig) -> Result<OpenSnitchDaemonConfig, String> {
+        self.form.validate()?;
+        let mut config = config.clone();
+        let edited = |label| Some(self.form.field(label)).filter(|field| field.changed());
+
+        if let Some(field) = edited(DEFAULT_ACTION) {
+            config.DefaultAction = Some(field.value.clone());
+        }
+        if let Some(field) = edited(DEFAULT_DURATION) {
+            config.DefaultDuration = Some(field.value.clone());
+        }
+        if let Some(field) = edited(LOG_LEVEL) {
+            let level = LOG_LEVELS
+                .iter()
+                .position(|name| *name == field.value)
+                .and_then(|idx| i32::try_from(idx).ok());
+            config.LogLevel = level.or(config.LogLevel);
+        }
+        if let Some(field) = edited(PROC_MONITOR) {
+            config.ProcMonitorMethod = Some(field.value.clone());
+        }
+        if let Some(field) = edited(INTERCEPT_UNKNOWN) {
+            config.InterceptUnknown = field.as_bool();
+        }
+        // Validated to be in range for the daemon's u32s.
+        let limit = |label| {
+            edited(label)
+                .and_then(Field::as_number)
+                .and_then(|limit| u32::try_from(limit).ok())
+        };
+        if let Some(limit) = limit(MAX_EVENTS) {
+            config
+                .Stats
+                .get_or_insert_with(StatsConfig::default)
+                .MaxEvents = Some(limit);
+        }
+        if let Some(limit) = limit(MAX_STATS) {
+            config
+                .Stats
+                .get_or_insert_with(StatsConfig::default)
+                .MaxStats = Some(limit);
+        }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> OpenSnitchDaemonConfig {
+        OpenSnitchDaemonConfig::parse(
+            r#"{"DefaultAction": "allow", "LogLevel": 2, "ProcMonitorMethod": "ebpf",
+                "Stats": {"MaxEvents": 150, "Workers": 6}, "Tasks": {}}"#,
+        )
+        .unwrap()
+    }
+
+    fn set(editor: &mut SettingsEditor, label: &str, value: &str) {
+        let field = editor
+            .form
+            .fields
+            .iter_mut()
+            .find(|field| field.label == label)
+            .unwrap();
+        field.value = String::from(value);
+    }
+
+    /// Test that the form loads the config and applying it untouched changes nothing.
+    #[test]
+    fn test_load() {
+        let editor = SettingsEditor::new(&String::from("10.0.0.1"), &config());
+        assert_eq!(editor.form.field(LOG_LEVEL).value, "important");
+        assert_eq!(editor.form.field(MAX_EVENTS).as_number(), Some(150));
+        assert_eq!(editor.form.field(MAX_STATS).display(), "-");
+        assert_eq!(editor.form.field(INTERCEPT_UNKNOWN).display(), "-");
+        assert_eq!(editor.apply(&config()), Ok(config()));
+    }
+
+    /// Test that only edited fields change, and invalid edits are refused.
+    #[test]
+    fn test_apply() {
+        let mut editor = SettingsEditor::new(&String::from("10.0.0.1"), &config());
+        set(&mut editor, DEFAULT_ACTION, "deny");
+        set(&mut editor, LOG_LEVEL, "debug");
+        set(&mut editor, INTERCEPT_UNKNOWN, "true");
+        set(&mut editor, MAX_STATS, "50");
+
+        let applied = editor.apply(&config()).unwrap();
+        assert_eq!(applied.DefaultAction.as_deref(), Some("deny"));
+        assert_eq!(applied.LogLevel, Some(0));
+        assert_eq!(applied.InterceptUnknown, Some(true));
+        assert_eq!(applied.ProcMonitorMethod.as_deref(), Some("ebpf"));
+        let stats = applied.Stats.as_ref().unwrap();
+        assert_eq!(
+            (stats.MaxEvents, stats.MaxStats, stats.Workers),
+            (Some(150), Some(50), Some(6))
+        );
+        assert!(applied.extra.contains_key("Tasks"));
+
+        set(&mut editor, MAX_EVENTS, "0");
+        assert!(editor.apply(&config()).is_err());
+    }
+}
@@ -0,0 +1,505 @@
+//! Sockets reported by a daemon's sockets monitor task, as shown in the sockets view:
+//! parsing the task's reports, and ordering and filtering the table.
+
+use std::cmp::Ordering;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime};
+
+use ratatui::crossterm::event::{KeyCode, KeyEvent};
+use serde_json::Value;
+
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+use crate::task;
+
+/// Daemon's name for the task listing sockets.
+pub const SOCKETS_MONITOR: &str = "sockets-monitor";
+
+/// How often the sockets monitor reports.
+pub const SOCKETS_MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+
+/// TCP state names, numbered by the kernel from 1 (`include/net/tcp_states.h`).
+pub const TCP_STATES: [&str; 12] = [
+    "ESTABLISHED",
+    "SYN_SENT",
+    "SYN_RECV",
+    "FIN_WAIT1",
+    "FIN_WAIT2",
+    "TIME_WAIT",
+    "CLOSE",
+    "CLOSE_WAIT",
+    "LAST_ACK",
+    "LISTEN",
+    "CLOSING",
+    "NEW_SYN_RECV",
+];
+
+/// Name of a TCP state, or its number if the TUI doesn't know it.
+#[must_use]
+pub fn state_name(state: u8) -> String {
+    usize::from(state)
+        .checked_sub(1)
+        .and_then(|idx| TCP_STATES.get(idx))
+        .map_or_else(|| state.to_string(), |name| String::from(*name))
+}
+
+/// The TCP state after `state` when cycling the state filter, with none for all states.
+#[must_use]
+pub fn next_state(state: Option<u8>, forward: bool) -> Option<u8> {
+    let last = u8::try_from(TCP_STATES.len()).unwrap_or(u8::MAX);
+    match (state, forward) {
+        (None, true) => Some(1),
+        (None, false) => Some(last),
+        (Some(state), true) if state < last => Some(state + 1),
+        (Some(state), false) if state > 1 => Some(state - 1),
+        (Some(_), _) => None,
+    }
+}
+
+/// One socket on the daemon's host.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Socket {
+    /// Protocol name, with a `6` for IPv6, e.g. `tcp6`.
+    pub proto: String,
+    pub local: Option<IpAddr>,
+    pub local_port: u16,
+    pub remote: Option<IpAddr>,
+    pub remote_port: u16,
+    /// TCP state as the kernel numbers it. Other protocols reuse some of the numbers.
+    pub state: u8,
+    pub uid: u32,
+    pub inode: u32,
+    /// Owning process, if the daemon could tell.
+    pub pid: Option<u32>,
+    /// Owning process's path, if the daemon sent it.
+    pub process: Option<String>,
+}
+
+impl Socket {
+    /// Local address and port, e.g. `[::1]:53`.
+    #[must_use]
+    pub fn local_endpoint(&self) -> String {
+        endpoint(self.local, self.local_port)
+    }
+
+    /// Remote address and port.
+    #[must_use]
+    pub fn remote_endpoint(&self) -> String {
+        endpoint(self.remote, self.remote_port)
+    }
+}
+
+fn endpoint(ip: Option<IpAddr>, port: u16) -> String {
+    match ip {
+        Some(ip) => SocketAddr::new(ip, port).to_string(),
+        None => format!("*:{port}"),
+    }
+}
+
+/// Protocol name for an IP protocol number and address family.
+fn proto_name(proto: u64, family: u64) -> String {
+    let name = match proto {
+        // ICMP, and ICMP for IPv6.
+        1 | 58 => String::from("icmp"),
+        6 => String::from("tcp"),
+        17 => String::from("udp"),
+        132 => String::from("sctp"),
+        136 => String::from("udplite"),
+        _ => proto.to_string(),
+    };
+    // AF_INET6
+    if family == 10 {
+        format!("{name}6")
+    } else {
+        name
+    }
+}
+
+/// Reads a sockets monitor report: `{"Table": [...], "Processes": {"<pid>": {...}}}`,
+/// each socket being `{"Socket": {...}, "PID": 1234, "Proto": 6, ...}` with the
+/// socket as the kernel's `inet_diag` describes it.
+/// # Errors
+/// Returns a description of what's wrong, if the report isn't laid out like that.
+pub fn parse(data: &str) -> Result<Vec<Socket>, String> {
+    let value: Value = serde_json::from_str(data).map_err(|err| err.to_string())?;
+    let table = match value.get("Table") {
+        Some(Value::Array(table)) => table.as_slice(),
+        // Go marshals an empty slice as null.
+        Some(Value::Null) => &[],
+        _ => return Err(String::from("sockets report has no socket table")),
+    };
+    let processes = value.get("Processes");
+    table
+        .iter()
+        .map(|entry| {
+            let socket = entry
+                .get("Socket")
+                .ok_or_else(|| String::from("socket entry without a socket"))?;
+            let id = socket.get("ID");
+            let number = |value: Option<&Value>, key: &str| {
+                value
+                    .and_then(|value| value.get(key))
+                    .and_then(Value::as_u64)
+            };
+            let ip = |key: &str| {
+                id.and_then(|id| id.get(key))
+                    .and_then(Value::as_str)
+                    .and_then(|ip| ip.parse().ok())
+            };
+            let port = |key: &str| {
+                number(id, key)
+                    .and_then(|port| u16::try_from(port).ok())
+                    .unwrap_or_default()
+            };
+            let pid = entry
+                .get("PID")
+                .and_then(Value::as_i64)
+                .and_then(|pid| u32::try_from(pid).ok());
+            let process = pid.and_then(|pid| {
+                processes?
+                    .get(pid.to_string())?
+                    .get("Path")?
+                    .as_str()
+                    .map(String::from)
+            });
+            Ok(Socket {
+                proto: proto_name(
+                    number(Some(entry), "Proto").unwrap_or_default(),
+                    number(Some(socket), "Family").unwrap_or_default(),
+                ),
+                local: ip("Source"),
+                local_port: port("SourcePort"),
+                remote: ip("Destination"),
+                remote_port: port("DestinationPort"),
+                state: number(Some(socket), "State")
+                    .and_then(|state| u8::try_from(state).ok())
+                    .unwrap_or_default(),
+                uid: number(Some(socket), "UID")
+                    .and_then(|uid| u32::try_from(uid).ok())
+                    .unwrap_or_default(),
+                inode: number(Some(socket), "INode")
+                    .and_then(|inode| u32::try_from(inode).ok())
+                    .unwrap_or_default(),
+                pid,
+                process,
+            })
+        })
+        .collect()
+}
+
+/// Column the sockets table is sorted by.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SocketSort {
+    Local,
+    Remote,
+    State,
+    Uid,
+    Inode,
+    Process,
+}
+
+impl SocketSort {
+    /// Every column, in the order `s` cycles through them.
+    pub const ALL: [SocketSort; 6] = [
+        SocketSort::Local,
+        SocketSort::Remote,
+        SocketSort::State,
+        SocketSort::Uid,
+        SocketSort::Inode,
+        SocketSort::Process,
+    ];
+
+    /// Column header.
+    #[must_use]
+    pub fn title(self) -> &'static str {
+        match self {
+            SocketSort::Local => "Local",
+            SocketSort::Remote => "Remote",
+            SocketSort::State => "State",
+            SocketSort::Uid => "UID",
+            SocketSort::Inode => "Inode",
+            SocketSort::Process => "Process",
+        }
+    }
+
+    /// The next column to sort by, wrapping around.
+    #[must_use]
+    pub fn next(self) -> SocketSort {
+        let idx = SocketSort::ALL
+            .iter()
+            .position(|sort| *sort == self)
+            .unwrap_or(0);
+        SocketSort::ALL[(idx + 1) % SocketSort::ALL.len()]
+    }
+
+    /// Order two sockets by this column, then by local endpoint and inode so the order
+    /// is stable.
+    fn compare(self, a: &Socket, b: &Socket) -> Ordering {
+        let by_column = match self {
+            SocketSort::Local => Ordering::Equal,
+            SocketSort::Remote => (a.remote, a.remote_port).cmp(&(b.remote, b.remote_port)),
+            SocketSort::State => a.state.cmp(&b.state),
+            SocketSort::Uid => a.uid.cmp(&b.uid),
+            SocketSort::Inode => a.inode.cmp(&b.inode),
+            SocketSort::Process => (&a.process, a.pid).cmp(&(&b.process, b.pid)),
+        };
+        by_column
+            .then_with(|| (a.local, a.local_port).cmp(&(b.local, b.local_port)))
+            .then_with(|| a.inode.cmp(&b.inode))
+    }
+}
+
+/// Sockets in `state`, or all of them, in table order.
+#[must_use]
+pub fn sorted(
+    sockets: &[Socket],
+    sort: SocketSort,
+    descending: bool,
+    state: Option<u8>,
+) -> Vec<&Socket> {
+    let mut sorted: Vec<&Socket> = sockets
+        .iter()
+        .filter(|socket| state.is_none_or(|state| socket.state == state))
+        .collect();
+    sorted.sort_by(|a, b| {
+        let ordering = sort.compare(a, b);
+        if descending {
+            ordering.reverse()
+        } else {
+            ordering
+        }
+    });
+    sorted
+}
+
+/// A sockets monitor running on a daemon, and what it last reported.
+#[derive(Clone, Debug)]
+pub struct SocketsMonitor {
+    /// Node the task runs on.
+    pub node: NodeId,
+    /// ID of the `TASK_START` notification, which the daemon's reports reply to.
+    pub task_id: u64,
+    /// Sockets as of the latest report.
+    pub sockets: Vec<Socket>,
+    /// When the latest report arrived.
+    pub updated_at: Option<SystemTime>,
+    /// What the daemon last reported instead of a socket list, if it reported a problem.
+    pub error: Option<String>,
+}
+
+impl SocketsMonitor {
+    #[must_use]
+    pub fn new(node: &NodeId, task_id: u64) -> Self {
+        Self {
+            node: node.clone(),
+            task_id,
+            sockets: Vec::new(),
+            updated_at: None,
+            error: None,
+        }
+    }
+
+    /// Notification data to start or stop the task. Sockets in every state are asked
+    /// for, the view filters them itself.
+    #[must_use]
+    pub fn task_data() -> String {
+        let states: Vec<String> = (1..=TCP_STATES.len())
+            .map(|state| state.to_string())
+            .collect();
+        task::task_data(
+            SOCKETS_MONITOR,
+            &[
+                (
+                    "interval",
+                    format!("{}s", SOCKETS_MONITOR_INTERVAL.as_secs()),
+                ),
+                ("states", states.join(",")),
+            ],
+        )
+    }
+
+    /// Take in a reply to the task's notification, received at `at`.
+    /// A report that can't be read leaves the last good one in place.
+    pub fn update(&mut self, reply: &pb::NotificationReply, at: SystemTime) {
+        if reply.code() == pb::NotificationReplyCode::Error {
+            self.error = Some(reply.data.clone());
+            return;
+        }
+        match parse(&reply.data) {
+            Ok(sockets) => {
+                self.sockets = sockets;
+                self.updated_at = Some(at);
+                self.error = None;
+            }
+            Err(err) => self.error = Some(format!("Unreadable report: {err}")),
+        }
+    }
+}
+
+/// State of the sockets view.
+#[derive(Clone, Debug)]
+pub struct SocketsView {
+    /// Sockets monitor task running for the view, while it's shown.
+    pub monitor: Option<SocketsMonitor>,
+    /// Index of the selected socket in the table, in table order.
+    pub cursor: usize,
+    /// Column the table is sorted by.
+    pub sort: SocketSort,
+    /// Whether the table is sorted in reverse.
+    pub descending: bool,
+    /// TCP state the table is limited to, if any.
+    pub state: Option<u8>,
+}
+
+impl Default for SocketsView {
+    fn default() -> Self {
+        Self {
+            monitor: None,
+            cursor: 0,
+            sort: SocketSort::Local,
+            descending: false,
+            state: None,
+        }
+    }
+}
+
+impl SocketsView {
+    /// Sockets in table order, limited to the picked state, if the monitor runs on `node`.
+    #[must_use]
+    pub fn visible(&self, node: Option<&NodeId>) -> Vec<&Socket> {
+        self.monitor
+            .as_ref()
+            .filter(|monitor| Some(&monitor.node) == node)
+            .map_or_else(Vec::new, |monitor| {
+                sorted(&monitor.sockets, self.sort, self.descending, self.state)
+            })
+    }
+
+    /// Handle a key press: up/down to move through the `sockets` shown, `s` to sort by
+    /// the next column, `S` to reverse the order, and `f`/`F` to limit the table to the
+    /// next or previous TCP state. Returns whether the key was used.
+    pub fn handle_key(&mut self, key_event: KeyEvent, sockets: usize) -> bool {
+        match key_event.code {
+            KeyCode::Up => self.cursor = self.cursor.saturating_sub(1),
+            KeyCode::Down => self.cursor = (self.cursor + 1).min(sockets.saturating_sub(1)),
+            KeyCode::Char('s') => self.sort = self.sort.next(),
+            KeyCode::Char('S') => self.descending = !self.descending,
+            KeyCode::Char(c @ ('f' | 'F')) => {
+                self.state = next_state(self.state, c == 'f');
+                self.cursor = 0;
+            }
+            _ => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    /// A sockets monitor report as the daemon sends it, trimmed down.
+    fn report() -> String {
+        let socket = |family, state, src: &str, sport, dst: &str, dport, inode| {
+            json!({
+                "Family": family,
+                "State": state,
+                "ID": {
+                    "SourcePort": sport,
+                    "DestinationPort": dport,
+                    "Source": src,
+                    "Destination": dst,
+                },
+                "UID": 1000,
+                "INode": inode,
+            })
+        };
+        json!({
+            "Table": [
+                {"Socket": socket(2, 1, "10.0.0.1", 40000, "1.1.1.1", 443, 30), "PID": 1234, "Proto": 6},
+                {"Socket": socket(10, 10, "::", 22, "::", 0, 10), "PID": -1, "Proto": 6},
+                {"Socket": socket(2, 7, "0.0.0.0", 53, "0.0.0.0", 0, 20), "PID": 1234, "Proto": 17},
+            ],
+            "Processes": {"1234": {"Path": "/usr/bin/curl"}}
+        })
+        .to_string()
+    }
+
+    /// Test that reports are read into sockets, with their processes looked up.
+    #[test]
+    fn test_parse() {
+        let sockets = parse(&report()).unwrap();
+        assert_eq!(sockets.len(), 3);
+        assert_eq!(sockets[0].proto, "tcp");
+        assert_eq!(sockets[0].local_endpoint(), "10.0.0.1:40000");
+        assert_eq!(sockets[0].remote_endpoint(), "1.1.1.1:443");
+        assert_eq!(sockets[0].process.as_deref(), Some("/usr/bin/curl"));
+        assert_eq!((sockets[0].uid, sockets[0].inode), (1000, 30));
+        assert_eq!(sockets[1].proto, "tcp6");
+        assert_eq!(sockets[1].local_endpoint(), "[::]:22");
+        assert_eq!((sockets[1].pid, &sockets[1].process), (None, &None));
+        assert_eq!(state_name(sockets[1].state), "LISTEN");
+        assert_eq!(sockets[2].proto, "udp");
+
+        assert_eq!(parse(r#"{"Table": null}"#), Ok(Vec::new()));
+        assert!(parse(r#"{"Table": [{}]}"#).is_err());
+        assert!(parse("{}").is_err());
+    }
+
+    /// Test that the table sorts by any column and filters by state.
+    #[test]
+    fn test_sorted() {
+        let sockets = parse(&report()).unwrap();
+        let inodes = |sort, descending, state| -> Vec<u32> {
+            sorted(&sockets, sort, descending, state)
+                .iter()
+                .map(|socket| socket.inode)
+                .collect()
+        };
+        assert_eq!(inodes(SocketSort::Local, false, None), [20, 30, 10]);
+        assert_eq!(inodes(SocketSort::Inode, true, None), [30, 20, 10]);
+        assert_eq!(inodes(SocketSort::State, false, None), [30, 20, 10]);
+        assert_eq!(inodes(SocketSort::Process, false, None), [10, 20, 30]);
+        assert_eq!(inodes(SocketSort::Local, false, Some(10)), [10]);
+
+        assert_eq!(next_state(None, true), Some(1));
+        assert_eq!(next_state(Some(12), true), None);
+        assert_eq!(next_state(None, false), Some(12));
+        assert_eq!(next_state(Some(1), false), None);
+        assert_eq!(state_name(42), "42");
+    }
+
+    /// Test that errors are kept until the next good report, which replaces the table.
+    #[test]
+    fn test_update() {
+        let node = String::from("10.0.0.1");
+        let mut monitor = SocketsMonitor::new(&node, 3);
+        let reply = |code: pb::NotificationReplyCode, data: String| pb::NotificationReply {
+            id: 3,
+            code: code.into(),
+            data,
+        };
+        monitor.update(
+            &reply(pb::NotificationReplyCode::Ok, report()),
+            SystemTime::UNIX_EPOCH,
+        );
+        monitor.update(
+            &reply(pb::NotificationReplyCode::Ok, String::from("[]")),
+            SystemTime::UNIX_EPOCH,
+        );
+        assert_eq!(monitor.sockets.len(), 3);
+        assert!(monitor.error.is_some());
+        monitor.update(
+            &reply(
+                pb::NotificationReplyCode::Ok,
+                String::from(r#"{"Table": []}"#),
+            ),
+            SystemTime::UNIX_EPOCH,
+        );
+        assert!(monitor.sockets.is_empty());
+        assert_eq!(monitor.error, None);
+        assert!(SocketsMonitor::task_data().contains(r#""states":"1,2,3,4,5,6,7,8,9,10,11,12""#));
+    }
+}
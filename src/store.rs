@@ -0,0 +1,749 @@
+//! On-disk store of what the TUI has seen, so it outlives a restart: connection events,
+//! alerts, decisions on prompted connections, stats snapshots, and the audit trail of
+//! commands sent to daemons. Kept in `SQLite`,
+//! by default under `$XDG_DATA_HOME/opensnitch-tui`, and written from a thread of its own.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime};
+
+use prost::Message;
+use rusqlite::{Connection, params};
+
+use crate::alert::{self, Alert};
+use crate::audit;
+use crate::history::HistoryEvent;
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+
+/// Name of the database file in the data directory.
+pub const STORE_FILE: &str = "store.db";
+
+/// How often a node's stats are snapshotted, however often it pings.
+pub const STATS_INTERVAL: Duration = Duration::from_mins(1);
+
+const SCHEMA: &str = "
+    CREATE TABLE IF NOT EXISTS events (
+        id INTEGER PRIMARY KEY,
+        node TEXT NOT NULL,
+        unixnano INTEGER NOT NULL,
+        -- The event's time, only to tell apart events without unixnano.
+        time_key TEXT NOT NULL,
+        event BLOB NOT NULL,
+        UNIQUE (node, unixnano, time_key)
+    );
+    CREATE TABLE IF NOT EXISTS alerts (
+        id INTEGER PRIMARY KEY,
+        node TEXT,
+        timestamp INTEGER NOT NULL,
+        priority INTEGER NOT NULL,
+        type INTEGER NOT NULL,
+        what INTEGER NOT NULL,
+        msg TEXT NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS decisions (
+        id INTEGER PRIMARY KEY,
+        node TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        duration TEXT NOT NULL,
+        connection BLOB NOT NULL,
+        rule BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS stats (
+        id INTEGER PRIMARY KEY,
+        node TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        stats BLOB NOT NULL
+    );
+    CREATE TABLE IF NOT EXISTS audit (
+        id INTEGER PRIMARY KEY,
+        node TEXT NOT NULL,
+        timestamp INTEGER NOT NULL,
+        action TEXT NOT NULL,
+        data TEXT NOT NULL,
+        notification INTEGER NOT NULL,
+        outcome TEXT NOT NULL
+    );
+";
+
+/// Max number of rows kept in each table, oldest going first.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Retention {
+    pub events: usize,
+    pub alerts: usize,
+    pub decisions: usize,
+    /// Stats snapshots, one per node every [`STATS_INTERVAL`], across all nodes.
+    pub stats: usize,
+    pub audit: usize,
+    /// Rows added to a table between prunes, so how far past its limit it can get.
+    pub prune_every: usize,
+}
+
+impl Default for Retention {
+    fn default() -> Self {
+        Self {
+            events: 10_000,
+            alerts: 5_000,
+            decisions: 5_000,
+            stats: 20_000,
+            audit: 5_000,
+            prune_every: 100,
+        }
+    }
+}
+
+/// Data directory the store goes in when none is given: `$XDG_DATA_HOME/opensnitch-tui`,
+/// or `~/.local/share/opensnitch-tui`. None if neither variable is set.
+#[must_use]
+pub fn default_dir() -> Option<PathBuf> {
+    let non_empty = |var| std::env::var_os(var).filter(|value| !value.is_empty());
+    let data_home = non_empty("XDG_DATA_HOME")
+        .map(PathBuf::from)
+        .or_else(|| non_empty("HOME").map(|home| Path::new(&home).join(".local/share")))?;
+    Some(data_home.join("opensnitch-tui"))
+}
+
+/// The store, open on a database file.
+#[derive(Debug)]
+pub struct Store {
+    conn: Connection,
+    retention: Retention,
+    /// Rows added to each table since it was last pruned.
+    unpruned: HashMap<&'static str, usize>,
+}
+
+impl Store {
+    /// Opens the store at `path`, creating the file and its directory if needed.
+    /// # Errors
+    /// Returns a description of what went wrong, if the database can't be opened or set up.
+    pub fn open(path: &Path, retention: Retention) -> Result<Self, String> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| format!("{}: {err}", dir.display()))?;
+        }
+        let conn = Connection::open(path).map_err(|err| format!("{}: {err}", path.display()))?;
+        // Other runs of the TUI may read the store while this one writes.
+        conn.pragma_update(None, "journal_mode", "WAL")
+            .map_err(|err| err.to_string())?;
+        conn.pragma_update(None, "synchronous", "NORMAL")
+            .map_err(|err| err.to_string())?;
+        Self::with_connection(conn, retention)
+    }
+
+    /// Opens a store that's gone once dropped.
+    /// # Errors
+    /// Returns a description of what went wrong, if the database can't be set up.
+    pub fn open_in_memory(retention: Retention) -> Result<Self, String> {
+        let conn = Connection::open_in_memory().map_err(|err| err.to_string())?;
+        Self::with_connection(conn, retention)
+    }
+
+    fn with_connection(conn: Connection, retention: Retention) -> Result<Self, String> {
+        conn.execute_batch(SCHEMA).map_err(|err| err.to_string())?;
+        Ok(Self {
+            conn,
+            retention,
+            unpruned: HashMap::new(),
+        })
+    }
+
+    /// Saves a node's events that haven't been saved yet. Returns how many were new.
+    /// # Errors
+    /// Returns the database error, if the write failed. Nothing is saved then.
+    pub fn save_events(&mut self, node: &NodeId, events: &[pb::Event]) -> Result<usize, String> {
+        let tx = self.conn.transaction().map_err(|err| err.to_string())?;
+        let mut added = 0;
+        {
+            let mut insert = tx
+                .prepare_cached(
+                    "INSERT OR IGNORE INTO events (node, unixnano, time_key, event)
+                     VALUES (?1, ?2, ?3, ?4)",
+                )
+                .map_err(|err| err.to_string())?;
+            for event in events {
+                let time_key = if event.unixnano == 0 {
+                    event.time.as_str()
+                } else {
+                    ""
+                };
+                added += insert
+                    .execute(params![
+                        node,
+                        event.unixnano,
+                        time_key,
+                        event.encode_to_vec()
+                    ])
+                    .map_err(|err| err.to_string())?;
+            }
+        }
+        tx.commit().map_err(|err| err.to_string())?;
+        self.added("events", self.retention.events, added)?;
+        Ok(added)
+    }
+
+    /// Saves a snapshot of a node's counters. The breakdowns and events its stats carry
+    /// are left out, see [`counters`].
+    /// # Errors
+    /// Returns the database error, if the write failed.
+    pub fn save_stats(
+        &mut self,
+        node: &NodeId,
+        stats: &pb::Statistics,
+        at: SystemTime,
+    ) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO stats (node, timestamp, stats) VALUES (?1, ?2, ?3)",
+                params![node, millis(at), counters(stats).encode_to_vec()],
+            )
+            .map_err(|err| err.to_string())?;
+        self.added("stats", self.retention.stats, 1)
+    }
+
+    /// Saves an alert.
+    /// # Errors
+    /// Returns the database error, if the write failed.
+    pub fn save_alert(&mut self, alert: &Alert) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO alerts (node, timestamp, priority, type, what, msg)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    alert.node,
+                    millis(alert.timestamp),
+                    alert.priority.code(),
+                    alert.r#type.code(),
+                    alert.what.code(),
+                    alert.msg,
+                ],
+            )
+            .map_err(|err| err.to_string())?;
+        self.added("alerts", self.retention.alerts, 1)
+    }
+
+    /// Saves the rule a prompted connection was answered with.
+    /// # Errors
+    /// Returns the database error, if the write failed.
+    pub fn save_decision(
+        &mut self,
+        node: &NodeId,
+        connection: &pb::Connection,
+        rule: &pb::Rule,
+        at: SystemTime,
+    ) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO decisions (node, timestamp, action, duration, connection, rule)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    node,
+                    millis(at),
+                    rule.action,
+                    rule.duration,
+                    connection.encode_to_vec(),
+                    rule.encode_to_vec(),
+                ],
+            )
+            .map_err(|err| err.to_string())?;
+        self.added("decisions", self.retention.decisions, 1)
+    }
+
+    /// Saves an entry of the audit trail.
+    /// # Errors
+    /// Returns the database error, if the write failed.
+    pub fn save_audit(&mut self, entry: &audit::Entry) -> Result<(), String> {
+        self.conn
+            .execute(
+                "INSERT INTO audit (node, timestamp, action, data, notification, outcome)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![
+                    entry.node,
+                    millis(entry.at),
+                    entry.action.as_str_name(),
+                    entry.data,
+                    i64::try_from(entry.notification).unwrap_or(i64::MAX),
+                    entry.outcome.to_string(),
+                ],
+            )
+            .map_err(|err| err.to_string())?;
+        self.added("audit", self.retention.audit, 1)
+    }
+
+    /// Makes a write handed over by a [`Writer`].
+    fn write(&mut self, write: Write) -> Result<(), String> {
+        match write {
+            Write::Events { node, events } => self.save_events(&node, &events).map(|_| ()),
+            Write::Stats { node, stats, at } => self.save_stats(&node, &stats, at),
+            Write::Alert(alert) => self.save_alert(&alert),
+            Write::Decision {
+                node,
+                connection,
+                rule,
+                at,
+            } => self.save_decision(&node, &connection, &rule, at),
+            Write::Audit(entry) => self.save_audit(&entry),
+        }
+    }
+
+    /// Notes `rows` added to `table`, pruning it down to `keep` rows once enough have
+    /// been added since it was last pruned.
+    fn added(&mut self, table: &'static str, keep: usize, rows: usize) -> Result<(), String> {
+        let unpruned = self.unpruned.entry(table).or_default();
+        *unpruned += rows;
+        if *unpruned < self.retention.prune_every {
+            return Ok(());
+        }
+        *unpruned = 0;
+        prune(&self.conn, table, keep)
+    }
+
+    /// The latest `limit` events, oldest first, and how many couldn't be read back and
+    /// were skipped.
+    /// # Errors
+    /// Returns the database error, if the read failed.
+    pub fn events(&self, limit: usize) -> Result<(Vec<HistoryEvent>, usize), String> {
+        let mut select = self
+            .conn
+            .prepare(
+                "SELECT node, event FROM
+                 (SELECT id, node, event FROM events ORDER BY id DESC LIMIT ?1)
+                 ORDER BY id",
+            )
+            .map_err(|err| err.to_string())?;
+        let rows = select
+            .query_map([limit_param(limit)], |row| {
+                Ok((row.get::<_, String>(0)?, row.get::<_, Vec<u8>>(1)?))
+            })
+            .map_err(|err| err.to_string())?;
+        let mut events = Vec::new();
+        let mut skipped = 0;
+        for row in rows {
+            let (node, event) = row.map_err(|err| err.to_string())?;
+            match pb::Event::decode(event.as_slice()) {
+                Ok(event) => events.push(HistoryEvent { node, event }),
+                Err(_) => skipped += 1,
+            }
+        }
+        Ok((events, skipped))
+    }
+
+    /// Alerts raised since `since`, oldest first.
+    /// # Errors
+    /// Returns the database error, if the read failed.
+    pub fn alerts_since(&self, since: SystemTime) -> Result<Vec<Alert>, String> {
+        let mut select = self
+            .conn
+            .prepare(
+                "SELECT node, timestamp, priority, type, what, msg FROM alerts
+                 WHERE timestamp >= ?1 ORDER BY id",
+            )
+            .map_err(|err| err.to_string())?;
+        let rows = select
+            .query_map([millis(since)], |row| {
+                Ok(Alert {
+                    node: row.get(0)?,
+                    timestamp: from_millis(row.get(1)?),
+                    priority: alert::Priority::new(row.get(2)?),
+                    r#type: alert::Type::new(row.get(3)?),
+                    what: alert::What::new(row.get(4)?),
+                    msg: row.get(5)?,
+                })
+            })
+            .map_err(|err| err.to_string())?;
+        rows.collect::<Result<_, _>>()
+            .map_err(|err| err.to_string())
+    }
+}
+
+/// A write for a [`Writer`]'s thread to make.
+#[derive(Debug)]
+enum Write {
+    Events {
+        node: NodeId,
+        events: Vec<pb::Event>,
+    },
+    Stats {
+        node: NodeId,
+        stats: pb::Statistics,
+        at: SystemTime,
+    },
+    Alert(Alert),
+    Decision {
+        node: NodeId,
+        connection: pb::Connection,
+        rule: pb::Rule,
+        at: SystemTime,
+    },
+    Audit(audit::Entry),
+}
+
+/// Hands writes over to a thread that makes them, so nothing waits on the disk.
+/// Dropping it waits for the writes already handed over to be made.
+#[derive(Debug)]
+pub struct Writer {
+    tx: Option<mpsc::Sender<Write>>,
+    thread: Option<JoinHandle<()>>,
+    /// When each node's stats were last snapshotted.
+    last_stats: HashMap<NodeId, SystemTime>,
+}
+
+impl Writer {
+    /// Starts a thread writing to `store`. The first write to fail stops the thread,
+    /// which passes the error to `on_error`, and every write after that fails.
+    pub fn spawn(mut store: Store, on_error: impl FnOnce(String) + Send + 'static) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let thread = std::thread::spawn(move || {
+            while let Ok(write) = rx.recv() {
+                if let Err(err) = store.write(write) {
+                    drop(rx);
+                    on_error(err);
+                    return;
+                }
+            }
+        });
+        Self {
+            tx: Some(tx),
+            thread: Some(thread),
+            last_stats: HashMap::new(),
+        }
+    }
+
+    /// Saves a node's events that are new in its ping, and a snapshot of its counters
+    /// if one is due.
+    /// # Errors
+    /// Fails if the writer has stopped.
+    pub fn save_ping(
+        &mut self,
+        node: &NodeId,
+        events: Vec<pb::Event>,
+        stats: &pb::Statistics,
+        at: SystemTime,
+    ) -> Result<(), String> {
+        if !events.is_empty() {
+            self.send(Write::Events {
+                node: node.clone(),
+                events,
+            })?;
+        }
+        let due = self
+            .last_stats
+            .get(node)
+            .is_none_or(|last| at.duration_since(*last).unwrap_or_default() >= STATS_INTERVAL);
+        if due {
+            self.last_stats.insert(node.clone(), at);
+            self.send(Write::Stats {
+                node: node.clone(),
+                stats: counters(stats),
+                at,
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Saves an alert.
+    /// # Errors
+    /// Fails if the writer has stopped.
+    pub fn save_alert(&self, alert: &Alert) -> Result<(), String> {
+        self.send(Write::Alert(alert.clone()))
+    }
+
+    /// Saves the rule a prompted connection was answered with.
+    /// # Errors
+    /// Fails if the writer has stopped.
+    pub fn save_decision(
+        &self,
+        node: &NodeId,
+        connection: &pb::Connection,
+        rule: &pb::Rule,
+        at: SystemTime,
+    ) -> Result<(), String> {
+        self.send(Write::Decision {
+            node: node.clone(),
+            connection: connection.clone(),
+            rule: rule.clone(),
+            at,
+        })
+    }
+
+    /// Saves an entry of the audit trail.
+    /// # Errors
+    /// Fails if the writer has stopped.
+    pub fn save_audit(&self, entry: &audit::Entry) -> Result<(), String> {
+        self.send(Write::Audit(entry.clone()))
+    }
+
+    fn send(&self, write: Write) -> Result<(), String> {
+        self.tx
+            .as_ref()
+            .and_then(|tx| tx.send(write).ok())
+            .ok_or_else(|| String::from("The store has stopped"))
+    }
+}
+
+impl Drop for Writer {
+    fn drop(&mut self) {
+        self.tx = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// A node's counters, without the breakdowns and events its stats also carry.
+fn counters(stats: &pb::Statistics) -> pb::Statistics {
+    pb::Statistics {
+        daemon_version: stats.daemon_version.clone(),
+        rules: stats.rules,
+        uptime: stats.uptime,
+        dns_responses: stats.dns_responses,
+        connections: stats.connections,
+        ignored: stats.ignored,
+        accepted: stats.accepted,
+        dropped: stats.dropped,
+        rule_hits: stats.rule_hits,
+        rule_misses: stats.rule_misses,
+        ..Default::default()
+    }
+}
+
+/// Drops the oldest rows of a table beyond the latest `keep`. Ids only grow, and rows
+/// only go from the oldest end, so that's every id `keep` or more below the newest.
+fn prune(conn: &Connection, table: &str, keep: usize) -> Result<(), String> {
+    conn.execute(
+        &format!("DELETE FROM {table} WHERE id <= (SELECT MAX(id) FROM {table}) - ?1"),
+        [limit_param(keep)],
+    )
+    .map(|_| ())
+    .map_err(|err| err.to_string())
+}
+
+/// A row count as `SQLite` takes it.
+fn limit_param(limit: usize) -> i64 {
+    i64::try_from(limit).unwrap_or(i64::MAX)
+}
+
+/// Milliseconds since the epoch, as timestamps are stored.
+fn millis(at: SystemTime) -> i64 {
+    at.duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |since| {
+            i64::try_from(since.as_millis()).unwrap_or(i64::MAX)
+        })
+}
+
+fn from_millis(millis: i64) -> SystemTime {
+    SystemTime::UNIX_EPOCH + Duration::from_millis(u64::try_from(millis).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(unixnano: i64, host: &str) -> pb::Event {
+        pb::Event {
+            time: String::from("2025-01-01 00:00:00"),
+            unixnano,
+            connection: Some(pb::Connection {
+                dst_host: String::from(host),
+                ..Default::default()
+            }),
+            rule: None,
+        }
+    }
+
+    fn ping(events: Vec<pb::Event>) -> pb::Statistics {
+        pb::Statistics {
+            uptime: 60,
+            connections: 3,
+            events,
+            ..Default::default()
+        }
+    }
+
+    fn count(store: &Store, table: &str) -> i64 {
+        store
+            .conn
+            .query_row(&format!("SELECT COUNT(*) FROM {table}"), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    }
+
+    /// Test that events are saved once however many pings carry them, and read back
+    /// newest last, with the oldest dropped once past the retention limit.
+    #[test]
+    fn test_events() {
+        let retention = Retention {
+            events: 3,
+            prune_every: 3,
+            ..Retention::default()
+        };
+        let mut store = Store::open_in_memory(retention).unwrap();
+        let node = String::from("10.0.0.1");
+        let first = [event(1, "a.com"), event(2, "b.com")];
+        assert_eq!(store.save_events(&node, &first), Ok(2));
+        let second = [event(2, "b.com"), event(0, "c.com"), event(0, "c.com")];
+        assert_eq!(store.save_events(&node, &second), Ok(1));
+        assert_eq!(store.save_events(&node, &[event(3, "d.com")]), Ok(1));
+        // Not pruned yet: only one row added since the last prune.
+        assert_eq!(count(&store, "events"), 4);
+        let third = [event(4, "e.com"), event(5, "f.com")];
+        assert_eq!(store.save_events(&node, &third), Ok(2));
+
+        let (events, skipped) = store.events(10).unwrap();
+        let hosts: Vec<String> = events
+            .into_iter()
+            .map(|entry| entry.event.connection.unwrap().dst_host)
+            .collect();
+        assert_eq!(hosts, ["d.com", "e.com", "f.com"]);
+        assert_eq!(skipped, 0);
+        assert_eq!(store.events(1).unwrap().0[0].event.unixnano, 5);
+
+        // Anything that can't be read back is skipped.
+        store
+            .conn
+            .execute("UPDATE events SET event = x'ff' WHERE unixnano = 4", [])
+            .unwrap();
+        let (events, skipped) = store.events(10).unwrap();
+        assert_eq!((events.len(), skipped), (2, 1));
+    }
+
+    /// Test that the writer saves new events, snapshots only counters at most once a
+    /// minute per node, and has made every write by the time it's dropped.
+    #[test]
+    fn test_writer() {
+        let path = std::env::temp_dir().join(format!("osui-test-{}-writer.db", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let store = Store::open(&path, Retention::default()).unwrap();
+        let mut writer = Writer::spawn(store, |err| panic!("write failed: {err}"));
+        let node = String::from("10.0.0.1");
+        let mut stats = ping(vec![event(1, "a.com")]);
+        stats.by_host.insert(String::from("a.com"), 1);
+        let start = SystemTime::now();
+        for secs in [0, 30, 60] {
+            let at = start + Duration::from_secs(secs);
+            writer
+                .save_ping(&node, stats.events.clone(), &stats, at)
+                .unwrap();
+        }
+        writer
+            .save_ping(&String::from("10.0.0.2"), Vec::new(), &stats, start)
+            .unwrap();
+        drop(writer);
+
+        let store = Store::open(&path, Retention::default()).unwrap();
+        assert_eq!(count(&store, "events"), 1);
+        assert_eq!(count(&store, "stats"), 3);
+        let snapshot: Vec<u8> = store
+            .conn
+            .query_row("SELECT stats FROM stats LIMIT 1", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(
+            pb::Statistics::decode(snapshot.as_slice()),
+            Ok(pb::Statistics {
+                events: Vec::new(),
+                by_host: HashMap::new(),
+                ..stats
+            })
+        );
+        drop(store);
+        std::fs::remove_file(&path).expect("remove failed");
+    }
+
+    /// Test that alerts are read back as they were saved, and only from the given time.
+    #[test]
+    fn test_alerts() {
+        let mut store = Store::open_in_memory(Retention::default()).unwrap();
+        let then = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let alert = |node: Option<&str>, timestamp, msg: &str| Alert {
+            node: node.map(String::from),
+            timestamp,
+            priority: alert::Priority::Medium,
+            r#type: alert::Type::Warning,
+            what: alert::What::Firewall,
+            msg: String::from(msg),
+        };
+        store.save_alert(&alert(None, then, "old")).unwrap();
+        let later = then + Duration::from_mins(1);
+        store
+            .save_alert(&alert(Some("10.0.0.1"), later, "new"))
+            .unwrap();
+
+        let alerts = store.alerts_since(later).unwrap();
+        assert_eq!(alerts.len(), 1);
+        let saved = &alerts[0];
+        assert_eq!(saved.node.as_deref(), Some("10.0.0.1"));
+        assert_eq!(saved.timestamp, later);
+        assert_eq!(saved.msg, "new");
+        assert!(matches!(
+            (&saved.priority, &saved.r#type, &saved.what),
+            (
+                alert::Priority::Medium,
+                alert::Type::Warning,
+                alert::What::Firewall
+            )
+        ));
+        assert_eq!(store.alerts_since(then).unwrap().len(), 2);
+    }
+
+    /// Test that decisions are saved, and pruned past the retention limit.
+    #[test]
+    fn test_decisions() {
+        let retention = Retention {
+            decisions: 1,
+            prune_every: 1,
+            ..Retention::default()
+        };
+        let mut store = Store::open_in_memory(retention).unwrap();
+        let node = String::from("10.0.0.1");
+        let rule = pb::Rule {
+            action: String::from("deny"),
+            duration: String::from("once"),
+            ..Default::default()
+        };
+        for _ in 0..2 {
+            store
+                .save_decision(&node, &pb::Connection::default(), &rule, SystemTime::now())
+                .unwrap();
+        }
+        assert_eq!(count(&store, "decisions"), 1);
+        let action: String = store
+            .conn
+            .query_row("SELECT action FROM decisions", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(action, "deny");
+    }
+
+    /// Test that audit entries are saved with their action and outcome spelled out.
+    #[test]
+    fn test_audit() {
+        let mut store = Store::open_in_memory(Retention::default()).unwrap();
+        let entry = audit::Entry {
+            node: String::from("10.0.0.1"),
+            at: SystemTime::now(),
+            action: pb::Action::LogLevel,
+            data: String::from("2"),
+            notification: 7,
+            outcome: crate::notification::Outcome::Error(String::from("nope")),
+        };
+        store.save_audit(&entry).unwrap();
+        let saved: (String, String, String, i64) = store
+            .conn
+            .query_row(
+                "SELECT action, data, outcome, notification FROM audit",
+                [],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?)),
+            )
+            .unwrap();
+        assert_eq!(
+            saved,
+            (
+                String::from("LOG_LEVEL"),
+                String::from("2"),
+                String::from("error: nope"),
+                7
+            )
+        );
+    }
+}
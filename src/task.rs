@@ -0,0 +1,257 @@
+//! Daemon tasks, started with a `TASK_START` notification and stopped with `TASK_STOP`.
+//! A running task reports back by replying to the notification that started it, again
+//! and again, with a JSON document in `NotificationReply.data`.
+
+use std::time::{Duration, SystemTime};
+
+use serde_json::{Value, json};
+
+use crate::node::NodeId;
+use crate::opensnitch_proto::pb;
+
+/// Daemon's name for the task reporting on one process.
+pub const PID_MONITOR: &str = "pid-monitor";
+
+/// How often a pid monitor reports, kept under the notification reply timeout
+/// so the first report settles the `TASK_START`.
+pub const PID_MONITOR_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Clock ticks per second the kernel counts CPU time in (`USER_HZ`), which is 100 on
+/// every architecture the daemon runs on.
+const CLOCK_TICKS: f64 = 100.0;
+
+/// Page size the daemon's memory counts are in.
+const PAGE_SIZE: u64 = 4096;
+
+/// Notification data for a task: its name, and its config as a map of strings,
+/// e.g. `{"name": "pid-monitor", "data": {"interval": "5s", "pid": "1234"}}`.
+#[must_use]
+pub fn task_data(name: &str, config: &[(&str, String)]) -> String {
+    let config: serde_json::Map<String, Value> = config
+        .iter()
+        .map(|(key, value)| (String::from(*key), Value::from(value.as_str())))
+        .collect();
+    json!({ "name": name, "data": config }).to_string()
+}
+
+/// One report from a pid monitor. The daemon sends what it read from `/proc/<pid>`,
+/// and any counter it couldn't read is left out.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ProcessSample {
+    pub comm: Option<String>,
+    pub path: Option<String>,
+    /// CPU time used so far, user and system, in clock ticks.
+    pub cpu_ticks: Option<u64>,
+    /// Resident memory, in bytes.
+    pub resident: Option<u64>,
+    /// Virtual memory, in bytes.
+    pub size: Option<u64>,
+    /// Bytes read from and written to storage.
+    pub read_bytes: Option<u64>,
+    pub write_bytes: Option<u64>,
+    /// Bytes received and sent over the network.
+    pub net_read_bytes: Option<u64>,
+    pub net_write_bytes: Option<u64>,
+    /// Open file descriptors, and how many of them are sockets.
+    pub descriptors: Option<usize>,
+    pub sockets: Option<usize>,
+}
+
+impl ProcessSample {
+    /// Reads a pid monitor report.
+    /// # Errors
+    /// Returns a description of what's wrong, if the report isn't a JSON object.
+    pub fn parse(data: &str) -> Result<Self, String> {
+        let value: Value = serde_json::from_str(data).map_err(|err| err.to_string())?;
+        if !value.is_object() {
+            return Err(String::from("process report isn't a JSON object"));
+        }
+        let number = |group: &str, key: &str| value.get(group)?.get(key)?.as_u64();
+        let text = |key: &str| value.get(key)?.as_str().map(String::from);
+        let descriptors = value.get("Descriptors").and_then(Value::as_array);
+        Ok(Self {
+            comm: text("Comm"),
+            path: text("Path"),
+            cpu_ticks: value
+                .get("Stat")
+                .and_then(Value::as_str)
+                .and_then(stat_cpu_ticks),
+            resident: number("Statm", "Resident").map(|pages| pages * PAGE_SIZE),
+            size: number("Statm", "Size").map(|pages| pages * PAGE_SIZE),
+            read_bytes: number("IOStats", "ReadBytes"),
+            write_bytes: number("IOStats", "WriteBytes"),
+            net_read_bytes: number("NetStats", "ReadBytes"),
+            net_write_bytes: number("NetStats", "WriteBytes"),
+            descriptors: descriptors.map(Vec::len),
+            sockets: descriptors.map(|descriptors| {
+                descriptors
+                    .iter()
+                    .filter(|fd| {
+                        fd.get("SymLink")
+                            .and_then(Value::as_str)
+                            .is_some_and(|link| link.starts_with("socket:"))
+                    })
+                    .count()
+            }),
+        })
+    }
+}
+
+/// `utime + stime` from the contents of `/proc/<pid>/stat`.
+fn stat_cpu_ticks(stat: &str) -> Option<u64> {
+    // The command name is in parentheses and may hold spaces, so count fields after it.
+    let (_, rest) = stat.rsplit_once(')')?;
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let utime: u64 = fields.get(11)?.parse().ok()?;
+    let stime: u64 = fields.get(12)?.parse().ok()?;
+    Some(utime + stime)
+}
+
+/// A pid monitor running on a daemon, and what it last reported.
+#[derive(Clone, Debug)]
+pub struct PidMonitor {
+    /// Node the task runs on.
+    pub node: NodeId,
+    pub pid: u32,
+    /// ID of the `TASK_START` notification, which the daemon's reports reply to.
+    pub task_id: u64,
+    /// Latest report, and when it arrived.
+    pub latest: Option<(SystemTime, ProcessSample)>,
+    /// CPU use between the last two reports, in percent of one core.
+    pub cpu_percent: Option<f64>,
+    /// What the daemon last reported instead of a sample, if it reported a problem.
+    pub error: Option<String>,
+}
+
+impl PidMonitor {
+    #[must_use]
+    pub fn new(node: &NodeId, pid: u32, task_id: u64) -> Self {
+        Self {
+            node: node.clone(),
+            pid,
+            task_id,
+            latest: None,
+            cpu_percent: None,
+            error: None,
+        }
+    }
+
+    /// Notification data to start or stop monitoring `pid`.
+    #[must_use]
+    pub fn task_data(pid: u32) -> String {
+        task_data(
+            PID_MONITOR,
+            &[
+                ("interval", format!("{}s", PID_MONITOR_INTERVAL.as_secs())),
+                ("pid", pid.to_string()),
+            ],
+        )
+    }
+
+    /// Take in a reply to the task's notification, received at `at`.
+    pub fn update(&mut self, reply: &pb::NotificationReply, at: SystemTime) {
+        if reply.code() == pb::NotificationReplyCode::Error {
+            self.error = Some(reply.data.clone());
+            return;
+        }
+        let sample = match ProcessSample::parse(&reply.data) {
+            Ok(sample) => sample,
+            Err(err) => {
+                self.error = Some(format!("Unreadable report: {err}"));
+                return;
+            }
+        };
+        self.error = None;
+        self.cpu_percent = self.latest.as_ref().and_then(|(previous_at, previous)| {
+            let ticks = sample.cpu_ticks?.checked_sub(previous.cpu_ticks?)?;
+            let ticks = f64::from(u32::try_from(ticks).ok()?);
+            let elapsed = at.duration_since(*previous_at).ok()?.as_secs_f64();
+            (elapsed > 0.0).then(|| ticks / CLOCK_TICKS / elapsed * 100.0)
+        });
+        self.latest = Some((at, sample));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A pid monitor report as the daemon sends it, trimmed down.
+    fn report(utime: u64) -> String {
+        json!({
+            "ID": 1234,
+            "Comm": "curl",
+            "Path": "/usr/bin/curl",
+            "Stat": format!("1234 (curl -s) S 1 1234 1234 0 -1 4194304 100 0 0 0 {utime} 10 0 0 20 0 1 0"),
+            "Statm": {"Size": 2048, "Resident": 512},
+            "IOStats": {"RChar": 10, "WChar": 20, "ReadBytes": 4096, "WriteBytes": 0},
+            "NetStats": {"ReadBytes": 1500, "WriteBytes": 300},
+            "Descriptors": [
+                {"Name": "0", "SymLink": "/dev/null"},
+                {"Name": "3", "SymLink": "socket:[4242]"}
+            ]
+        })
+        .to_string()
+    }
+
+    /// Test that reports are read into samples, with missing counters left unknown.
+    #[test]
+    fn test_parse() {
+        let sample = ProcessSample::parse(&report(40)).unwrap();
+        assert_eq!(sample.comm.as_deref(), Some("curl"));
+        assert_eq!(sample.cpu_ticks, Some(50));
+        assert_eq!(sample.resident, Some(512 * 4096));
+        assert_eq!(sample.size, Some(2048 * 4096));
+        assert_eq!(
+            (sample.read_bytes, sample.write_bytes),
+            (Some(4096), Some(0))
+        );
+        assert_eq!(sample.net_read_bytes, Some(1500));
+        assert_eq!((sample.descriptors, sample.sockets), (Some(2), Some(1)));
+
+        assert_eq!(
+            ProcessSample::parse(r#"{"Comm": "sh"}"#),
+            Ok(ProcessSample {
+                comm: Some(String::from("sh")),
+                ..Default::default()
+            })
+        );
+        assert!(ProcessSample::parse("[]").is_err());
+        assert!(ProcessSample::parse("not json").is_err());
+    }
+
+    /// Test that CPU use is worked out between reports, and errors are kept until the
+    /// next good report.
+    #[test]
+    fn test_update() {
+        assert_eq!(
+            PidMonitor::task_data(1234),
+            r#"{"data":{"interval":"5s","pid":"1234"},"name":"pid-monitor"}"#
+        );
+        let node = String::from("10.0.0.1");
+        let mut monitor = PidMonitor::new(&node, 1234, 7);
+        let reply = |code: pb::NotificationReplyCode, data: String| pb::NotificationReply {
+            id: 7,
+            code: code.into(),
+            data,
+        };
+        let start = SystemTime::UNIX_EPOCH;
+        monitor.update(&reply(pb::NotificationReplyCode::Ok, report(40)), start);
+        assert!(monitor.latest.is_some());
+        assert_eq!(monitor.cpu_percent, None);
+
+        monitor.update(
+            &reply(
+                pb::NotificationReplyCode::Error,
+                String::from("no such process"),
+            ),
+            start,
+        );
+        assert_eq!(monitor.error.as_deref(), Some("no such process"));
+
+        let later = start + Duration::from_secs(5);
+        monitor.update(&reply(pb::NotificationReplyCode::Ok, report(290)), later);
+        assert_eq!(monitor.error, None);
+        assert_eq!(monitor.cpu_percent, Some(50.0));
+    }
+}
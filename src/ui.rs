@@ -1,63 +1,224 @@
 use ratatui::{
     buffer::Buffer,
-    layout::{Alignment, Constraint, Layout, Rect},
+    layout::{Alignment, Constraint, Flex, Layout, Rect},
     style::{Color, Style, Stylize},
-    widgets::{Block, BorderType, List, ListItem, Paragraph, Widget},
+    symbols,
+    text::{Line, Span},
+    widgets::{
+        Axis, Block, BorderType, Cell, Chart, Clear, Dataset, GraphType, List, ListItem, Paragraph,
+        Row, Table, TableState, Widget, Wrap,
+    },
 };
 
-use crate::app::App;
+use crate::admin::{self, AdminMenu};
+use crate::app::{ADMIN_ACTIONS, App, CONTROL_ACTIONS, Confirmation, RULE_ACTIONS, View};
+use crate::breakdown::Dimension;
+use crate::firewall::{self, Row as FwRow};
+use crate::firewall_editor::{Expression, FwRuleEditor, Row as FwEditorRow};
+use crate::form::FieldKind;
+use crate::nft;
+use crate::node::{Health, Node, StreamState};
+use crate::notification::Outcome;
+use crate::opensnitch_proto::pb;
+use crate::rates::Counter;
+use crate::rule_editor::{Row as RuleRow, RuleEditor};
+use crate::rules::{self, RuleSort};
+use crate::server::ServerState;
+use crate::settings;
+use crate::sockets::{self, SocketSort};
+use crate::task::PidMonitor;
 
 impl Widget for &App {
     /// Renders the user interface widgets.
     fn render(self, area: Rect, buf: &mut Buffer) {
-        let areas = Layout::vertical([
-            Constraint::Max(6),
-            Constraint::Max(9),
-            Constraint::Max(5),
-            Constraint::Max(2),
+        let [top_area, body_area, controls_area] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(0),
+            Constraint::Length(2),
         ])
-        .split(area);
-        let stats_title = match self.peer {
-            Some(addr) => format!(" OpenSnitch ({addr}) "),
-            _ => String::from(" OpenSnitch "),
+        .areas(area);
+        let server_status = self.server_status();
+        let [bar_area, status_area] = Layout::horizontal([
+            Constraint::Min(0),
+            Constraint::Length(u16::try_from(server_status.width()).unwrap_or(u16::MAX)),
+        ])
+        .areas(top_area);
+        self.top_bar().render(bar_area, buf);
+        Paragraph::new(server_status)
+            .bg(Color::Black)
+            .render(status_area, buf);
+
+        if let Some(editor) = &self.rules_view.editor {
+            self.render_rule_editor(editor, body_area, buf);
+        } else if let Some(editor) = &self.firewall_view.editor {
+            self.render_fw_rule_editor(editor, body_area, buf);
+        } else {
+            self.render_view(body_area, buf);
+        }
+        if let Some(menu) = &self.admin_menu {
+            render_admin_menu(menu, body_area, buf);
+        }
+        if let Some(export) = &self.firewall_view.export {
+            render_fw_export(export, body_area, buf);
+        }
+        if let Some(confirmation) = &self.confirmation {
+            render_confirmation(confirmation, body_area, buf);
+        }
+
+        // Controls footer
+        let controls_paragraph = Paragraph::new(self.controls_text())
+            .bg(Color::DarkGray)
+            .fg(Color::White)
+            .alignment(Alignment::Center);
+
+        controls_paragraph.render(controls_area, buf);
+    }
+}
+
+impl App {
+    /// Body of the current view.
+    fn render_view(&self, area: Rect, buf: &mut Buffer) {
+        match self.view {
+            View::Main => self.render_main(area, buf),
+            View::History => self.render_history(area, buf),
+            View::Stats => self.render_stats(area, buf),
+            View::Rules => self.render_rules(area, buf),
+            View::Firewall => self.render_firewall(area, buf),
+            View::Sockets => self.render_sockets(area, buf),
+            View::Notifications => self.render_notification_log(area, buf),
+            View::Settings => {
+                let [control_area, settings_area] =
+                    Layout::vertical([Constraint::Length(5), Constraint::Min(0)]).areas(area);
+                self.render_daemon_control(control_area, buf);
+                self.render_settings(settings_area, buf);
+            }
+        }
+    }
+
+    /// Key hints for the current view.
+    fn controls_text(&self) -> String {
+        if let Some(confirmation) = &self.confirmation {
+            return match &confirmation.challenge {
+                Some(challenge) => {
+                    format!("type `{challenge}` then `enter` → confirm | `esc` → cancel")
+                }
+                None => String::from("`y` → confirm | any other key → cancel"),
+            };
+        }
+        if let Some(export) = &self.firewall_view.export {
+            return String::from(if export.overwrite {
+                "`y` → replace the file | any other key → back to the path"
+            } else {
+                "type the path | `enter` → write | `esc` → cancel"
+            });
+        }
+        if self.admin_menu.is_some() {
+            return String::from(
+                "`up/down` → select | `left/right` → pick log level | `enter` → send | `esc` → close",
+            );
+        }
+        if self.rules_view.editor.is_some() {
+            return String::from(
+                "\
+            `up/down` → select field | `left/right/space` → change | type to edit text | `enter` → send to daemon | `esc` → close\n\
+            `ctrl+A` → add operator | `ctrl+N` → add operator to list | `ctrl+D` → remove operator",
+            );
+        }
+        if self.firewall_view.editor.is_some() {
+            return String::from(
+                "\
+            `up/down` → select field | `left/right/space` → change | type to edit text | `enter` → send to daemon | `esc` → close\n\
+            `ctrl+A` → add statement | `ctrl+D` → remove statement",
+            );
+        }
+        match self.view {
+            View::Main => format!(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node | `A/D` → (allow/deny) connection {}\n\
+            `J/L` → (allow/deny) connection forever | `[/]` → prev/next prompt | `up/down` → scroll alerts | `m/M` → watch/stop pid",
+                self.temp_rule_lifetime.get_str(),
+            ),
+            View::History => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node\n\
+            `up/down` → select event | `enter` → show/hide details | `m` → watch process",
+            ),
+            View::Stats => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node\n\
+            `left/right` → breakdown by previous/next | `up/down` → select | `w` → chart window",
+            ),
+            View::Rules => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node\n\
+            `up/down` → select rule | `s/S` → sort by next column/reverse | `c` → create | `enter` → edit | `e` → enable/disable | `x/del` → delete",
+            ),
+            View::Firewall if self.firewall_view.nft => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node\n\
+            `up/down` → scroll | `v/esc` → back to chains | `w` → export to file",
+            ),
+            View::Firewall => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node | `space` → show/hide chain rules | `a` → show/hide all | `v` → nft ruleset | `w` → export\n\
+            `up/down` → select | `c` → add rule | `enter` → edit | `e` → enable/disable | `[/]` → move up/down | `x/del` → delete | `p` → chain policy",
+            ),
+            View::Sockets => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node | `r` → restart monitor\n\
+            `up/down` → select socket | `s/S` → sort by next column/reverse | `f/F` → next/prev TCP state filter",
+            ),
+            View::Notifications => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node\n\
+            `up/down` → scroll",
+            ),
+            View::Settings => String::from(
+                "\
+            `ctrl+C` → quit | `tab` → next view | `n/N` → next/prev node | `up/down` → select field\n\
+            `left/right/space` → change | `0-9/backspace` → edit number | `enter` → send to daemon | `esc` → revert",
+            ),
+        }
+    }
+
+    /// Stats, connection prompts and alerts for the selected node.
+    fn render_main(&self, area: Rect, buf: &mut Buffer) {
+        let areas = Layout::vertical([Constraint::Max(7), Constraint::Max(9), Constraint::Min(5)])
+            .split(area);
+
+        let stats_title = match self.selected() {
+            Some(node) => match &node.peer {
+                Some(peer) => format!(" OpenSnitch {} ({peer}) ", node.label()),
+                None => format!(" OpenSnitch {} ", node.label()),
+            },
+            None => String::from(" OpenSnitch "),
         };
-        let stats_block = Block::bordered()
+        let mut stats_block = Block::bordered()
             .title(stats_title)
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Rounded);
+        let mut stats_color = Color::Cyan;
+        if let Some(status) = self.health_status() {
+            stats_block = stats_block.title(status.clone().left_aligned());
+            // Grey out numbers that may no longer reflect reality.
+            if status.style.fg != Some(Color::Green) {
+                stats_color = Color::DarkGray;
+            }
+        }
 
         let stats_text = self.format_stats_panel();
         let stats_paragraph = Paragraph::new(stats_text)
             .block(stats_block)
-            .fg(Color::Cyan)
+            .fg(stats_color)
             .bg(Color::Black);
 
         stats_paragraph.render(areas[0], buf);
 
-        // Connection controls
-        let connection_block = Block::bordered()
-            .title(" New Connections ")
-            .title_alignment(Alignment::Center)
-            .border_type(BorderType::Rounded)
-            .title_style(match &self.current_connection {
-                None => Style::default(),
-                Some(_) => Style::default().bold(),
-            })
-            .style(match &self.current_connection {
-                None => Style::default().fg(Color::Cyan),
-                Some(_) => Style::default().fg(Color::Yellow),
-            });
-
-        let connection_text = self.format_connection_panel();
-        let connection_paragraph = Paragraph::new(connection_text)
-            .block(connection_block)
-            .bg(Color::Black);
-
-        connection_paragraph.render(areas[1], buf);
+        self.render_connections(areas[1], buf);
 
         // Alerts list
         let alerts_block = Block::bordered()
-            .title(format!(" Alerts ({}) ", self.current_alerts.len()))
+            .title(format!(" Alerts ({}) ", self.visible_alerts().count()))
             .title_alignment(Alignment::Center)
             .border_type(BorderType::Rounded);
 
@@ -66,10 +227,7 @@ impl Widget for &App {
 
         // We want to render the alert list from some stateful head index,
         // so get an iterator and skip forward to that head.
-        let items_iter = self
-            .current_alerts
-            .iter()
-            .skip(self.alert_list_render_offset);
+        let items_iter = self.visible_alerts().skip(self.alert_list_render_offset);
 
         let items: Vec<ListItem> = items_iter
             .map(|alert| {
@@ -86,41 +244,1268 @@ impl Widget for &App {
             })
             .collect();
 
+        // Make room for the process being watched, if there is one.
+        let alerts_area = match &self.pid_monitor {
+            Some(monitor) => {
+                let [alerts_area, monitor_area] =
+                    Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                        .areas(areas[2]);
+                self.render_pid_monitor(monitor, monitor_area, buf);
+                alerts_area
+            }
+            None => areas[2],
+        };
+
         // Create a List from all list items
         let list = List::new(items)
             .block(alerts_block)
             .fg(Color::Cyan)
             .bg(Color::Black);
-        list.render(areas[2], buf);
+        list.render(alerts_area, buf);
+    }
 
-        // Controls footer
-        let controls_text = format!(
-            "\
-        `ctrl+C` → quit | `A/D` → (allow/deny) connection {}\n\
-        `J/L` → (allow/deny) connection forever | `up/down` → scroll alerts",
-            self.temp_rule_lifetime.get_str(),
+    /// Counters from the latest pid monitor report.
+    fn render_pid_monitor(&self, monitor: &PidMonitor, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(format!(" Process {} on {} ", monitor.pid, monitor.node))
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded);
+        let mut lines = Vec::new();
+        if let Some((at, sample)) = &monitor.latest {
+            let count =
+                |count: Option<usize>| count.map_or_else(|| String::from("-"), |n| n.to_string());
+            lines.push(Line::from(format!(
+                "{} {}",
+                sample.comm.as_deref().unwrap_or("-"),
+                sample.path.as_deref().unwrap_or_default()
+            )));
+            lines.push(Line::from(format!(
+                "CPU: {}",
+                monitor
+                    .cpu_percent
+                    .map_or_else(|| String::from("-"), |cpu| format!("{cpu:.1}%"))
+            )));
+            lines.push(Line::from(format!(
+                "Memory: {} resident, {} virtual",
+                format_bytes(sample.resident),
+                format_bytes(sample.size)
+            )));
+            lines.push(Line::from(format!(
+                "Disk: {} read, {} written",
+                format_bytes(sample.read_bytes),
+                format_bytes(sample.write_bytes)
+            )));
+            lines.push(Line::from(format!(
+                "Network: {} received, {} sent",
+                format_bytes(sample.net_read_bytes),
+                format_bytes(sample.net_write_bytes)
+            )));
+            lines.push(Line::from(format!(
+                "Descriptors: {} open, {} sockets",
+                count(sample.descriptors),
+                count(sample.sockets)
+            )));
+            let age = std::time::SystemTime::now()
+                .duration_since(*at)
+                .unwrap_or_default();
+            lines.push(Line::styled(
+                format!("Updated {}s ago", age.as_secs()),
+                Style::default().fg(Color::DarkGray),
+            ));
+        } else {
+            let status = self
+                .notifications
+                .get(monitor.task_id)
+                .map_or_else(String::new, |record| format!(" ({})", record.outcome));
+            lines.push(Line::styled(
+                format!("Waiting for the first report{status}"),
+                Style::default().fg(Color::DarkGray),
+            ));
+        }
+        if let Some(err) = &monitor.error {
+            lines.push(Line::styled(err.clone(), Style::default().fg(Color::Red)));
+        }
+        Paragraph::new(lines)
+            .wrap(Wrap { trim: false })
+            .block(block)
+            .fg(Color::Cyan)
+            .bg(Color::Black)
+            .render(area, buf);
+    }
+
+    /// The selected node's rules, next to details of the selected one.
+    fn render_rules(&self, area: Rect, buf: &mut Buffer) {
+        let [table_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(area);
+        let view = &self.rules_view;
+        let rules = self.visible_rules();
+        let selected = self.selected_rule();
+
+        let header: Vec<String> = [
+            Some(RuleSort::Name),
+            Some(RuleSort::Action),
+            Some(RuleSort::Duration),
+            Some(RuleSort::Enabled),
+            Some(RuleSort::Precedence),
+            None,
+        ]
+        .iter()
+        .map(|column| match column {
+            Some(sort) if *sort == view.sort => {
+                let arrow = if view.descending { '▼' } else { '▲' };
+                format!("{}{arrow}", sort.title())
+            }
+            Some(sort) => String::from(sort.title()),
+            None => String::from("Matches"),
+        })
+        .collect();
+        let rows: Vec<Row> = rules
+            .iter()
+            .map(|rule| {
+                let row = Row::new(vec![
+                    Cell::from(rule.name.clone()),
+                    Cell::from(rule.action.clone()),
+                    Cell::from(rule.duration.clone()),
+                    Cell::from(if rule.enabled { "✔" } else { "✖" }),
+                    Cell::from(if rule.precedence { "✔" } else { "" }),
+                    Cell::from(
+                        rule.operator
+                            .as_ref()
+                            .map_or_else(String::new, rules::operator_summary),
+                    ),
+                ]);
+                if rule.enabled {
+                    row
+                } else {
+                    row.fg(Color::DarkGray)
+                }
+            })
+            .collect();
+        // The cursor may have run past the end if rules went away.
+        let mut state = TableState::default().with_selected(
+            rules
+                .len()
+                .checked_sub(1)
+                .map(|last| std::cmp::min(view.cursor, last)),
         );
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Fill(2),
+                Constraint::Length(7),
+                Constraint::Length(13),
+                Constraint::Length(3),
+                Constraint::Length(5),
+                Constraint::Fill(3),
+            ],
+        )
+        .header(Row::new(header).bold())
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(
+            Block::bordered()
+                .title(format!(" Rules ({}) ", rules.len()))
+                .title_alignment(Alignment::Center)
+                .title_bottom(self.last_notification(&RULE_ACTIONS).map_or_else(
+                    Line::default,
+                    |record| {
+                        Line::styled(
+                            format!(
+                                " {} {}: {} ",
+                                record.action().as_str_name(),
+                                notification_summary(&record.notification),
+                                record.outcome
+                            ),
+                            Style::default().fg(outcome_color(&record.outcome)),
+                        )
+                    },
+                ))
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::Cyan)
+        .bg(Color::Black);
+        ratatui::widgets::StatefulWidget::render(table, table_area, buf, &mut state);
 
-        let controls_paragraph = Paragraph::new(controls_text)
-            .bg(Color::DarkGray)
-            .fg(Color::White)
-            .alignment(Alignment::Center);
+        let detail = selected.map_or_else(String::new, format_rule_detail);
+        Paragraph::new(detail)
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::bordered()
+                    .title(" Rule ")
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::Cyan)
+            .bg(Color::Black)
+            .render(detail_area, buf);
+    }
+
+    /// Connections the selected node has seen, newest first, with the selected one's
+    /// details over them if asked for.
+    fn render_history(&self, area: Rect, buf: &mut Buffer) {
+        let events: Vec<&pb::Event> = self
+            .selected_node
+            .iter()
+            .flat_map(|node| self.history.for_node(node))
+            .collect();
+        let rows: Vec<Row> = events
+            .iter()
+            .map(|event| {
+                let connection = event.connection.clone().unwrap_or_default();
+                let (action, rule) = event
+                    .rule
+                    .as_ref()
+                    .map_or((String::new(), String::new()), |rule| {
+                        (rule.action.clone(), rule.name.clone())
+                    });
+                let destination = if connection.dst_host.is_empty() {
+                    connection.dst_ip
+                } else {
+                    connection.dst_host
+                };
+                let row = Row::new(vec![
+                    Cell::from(event.time.clone()),
+                    Cell::from(action.clone()),
+                    Cell::from(rule),
+                    Cell::from(connection.process_path),
+                    Cell::from(destination),
+                    Cell::from(connection.dst_port.to_string()),
+                    Cell::from(connection.protocol),
+                ]);
+                if matches!(action.as_str(), "deny" | "reject") {
+                    row.fg(Color::LightRed)
+                } else {
+                    row
+                }
+            })
+            .collect();
+        let mut state = TableState::default().with_selected(
+            events
+                .len()
+                .checked_sub(1)
+                .map(|last| std::cmp::min(self.history_view.cursor, last)),
+        );
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(19),
+                Constraint::Length(6),
+                Constraint::Fill(2),
+                Constraint::Fill(3),
+                Constraint::Fill(3),
+                Constraint::Length(5),
+                Constraint::Length(5),
+            ],
+        )
+        .header(
+            Row::new(vec![
+                "Time",
+                "Action",
+                "Rule",
+                "Process",
+                "Destination",
+                "Port",
+                "Proto",
+            ])
+            .bold(),
+        )
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(
+            Block::bordered()
+                .title(format!(" History ({}) ", events.len()))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::Cyan)
+        .bg(Color::Black);
+        ratatui::widgets::StatefulWidget::render(table, area, buf, &mut state);
 
-        controls_paragraph.render(areas[3], buf);
+        if let Some(event) = self.selected_event().filter(|_| self.history_view.detail) {
+            let [popup_area] = Layout::vertical([Constraint::Percentage(80)])
+                .flex(Flex::Center)
+                .areas(area);
+            let [popup_area] = Layout::horizontal([Constraint::Percentage(70)])
+                .flex(Flex::Center)
+                .areas(popup_area);
+            Clear.render(popup_area, buf);
+            Paragraph::new(format_event_detail(event))
+                .wrap(Wrap { trim: false })
+                .block(
+                    Block::bordered()
+                        .title(" Event ")
+                        .title_alignment(Alignment::Center)
+                        .border_type(BorderType::Rounded),
+                )
+                .fg(Color::Cyan)
+                .bg(Color::Black)
+                .render(popup_area, buf);
+        }
+    }
+
+    /// Rate charts of the selected node's counters, over its hits broken down by one
+    /// dimension.
+    fn render_stats(&self, area: Rect, buf: &mut Buffer) {
+        let [charts_area, breakdown_area] =
+            Layout::vertical([Constraint::Length(10), Constraint::Min(0)]).areas(area);
+        let chart_areas =
+            Layout::horizontal([Constraint::Fill(1); Counter::ALL.len()]).split(charts_area);
+        for (counter, chart_area) in Counter::ALL.into_iter().zip(chart_areas.iter()) {
+            self.render_rate_chart(counter, *chart_area, buf);
+        }
+        self.render_breakdown(breakdown_area, buf);
+    }
+
+    /// A counter's rate over the stats view's window, as of its latest ping in the title.
+    fn render_rate_chart(&self, counter: Counter, area: Rect, buf: &mut Buffer) {
+        let rates = self.selected().map(|node| &node.rates);
+        let points = rates
+            .map(|rates| {
+                rates.points(
+                    counter,
+                    self.stats_view.window,
+                    std::time::SystemTime::now(),
+                )
+            })
+            .unwrap_or_default();
+        let title = match rates.and_then(|rates| rates.latest(counter)) {
+            Some(rate) => format!(" {} {rate:.1} ", counter.title()),
+            None => format!(" {} ", counter.title()),
+        };
+        let peak = points.iter().map(|(_, rate)| *rate).fold(0.0, f64::max);
+        let top = if peak > 0.0 { peak * 1.1 } else { 1.0 };
+        let dataset = Dataset::default()
+            .marker(symbols::Marker::Braille)
+            .graph_type(GraphType::Line)
+            .fg(Color::Cyan)
+            .data(&points);
+        Chart::new(vec![dataset])
+            .block(
+                Block::bordered()
+                    .title(title)
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .x_axis(
+                Axis::default()
+                    .bounds([-self.stats_view.window.duration().as_secs_f64(), 0.0])
+                    .labels([
+                        format!("-{}", self.stats_view.window.title()),
+                        String::from("now"),
+                    ])
+                    .fg(Color::DarkGray),
+            )
+            .y_axis(
+                Axis::default()
+                    .bounds([0.0, top])
+                    .labels([String::from("0"), format!("{top:.1}")])
+                    .fg(Color::DarkGray),
+            )
+            .fg(Color::Cyan)
+            .bg(Color::Black)
+            .render(area, buf);
+    }
+
+    /// The selected node's hits broken down by one dimension, most first, with a tab for
+    /// each dimension in the title.
+    fn render_breakdown(&self, area: Rect, buf: &mut Buffer) {
+        /// Width of the share bars, in cells.
+        const BAR_WIDTH: u16 = 20;
+        let breakdown = self.breakdown();
+        let mut tabs = vec![Span::raw(" ")];
+        for dimension in Dimension::ALL {
+            let text = format!(" {} ", dimension.title());
+            if dimension == self.stats_view.dimension {
+                tabs.push(Span::styled(
+                    text,
+                    Style::default().fg(Color::Black).bg(Color::Cyan),
+                ));
+            } else {
+                tabs.push(Span::raw(text));
+            }
+        }
+        tabs.push(Span::raw(" "));
+        let rows: Vec<Row> = breakdown
+            .entries
+            .iter()
+            .enumerate()
+            .map(|(idx, entry)| {
+                let bar =
+                    usize::try_from(entry.per_mille * u64::from(BAR_WIDTH) / 1000).unwrap_or(0);
+                Row::new(vec![
+                    Cell::from(format!("{}", idx + 1)),
+                    Cell::from(entry.label()),
+                    Cell::from(entry.hits.to_string()),
+                    Cell::from(format!(
+                        "{}.{}%",
+                        entry.per_mille / 10,
+                        entry.per_mille % 10
+                    )),
+                    Cell::from("█".repeat(bar)),
+                ])
+            })
+            .collect();
+        let mut state = TableState::default().with_selected(
+            breakdown
+                .entries
+                .len()
+                .checked_sub(1)
+                .map(|last| std::cmp::min(self.stats_view.cursor, last)),
+        );
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(3),
+                Constraint::Fill(1),
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Length(BAR_WIDTH),
+            ],
+        )
+        .header(
+            Row::new(vec![
+                "#",
+                self.stats_view.dimension.title(),
+                "Hits",
+                "Share",
+                "",
+            ])
+            .bold(),
+        )
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(
+            Block::bordered()
+                .title(Line::from(tabs))
+                .title_bottom(format!(
+                    " Top {} of {}, {} hits ",
+                    breakdown.entries.len(),
+                    breakdown.distinct,
+                    breakdown.total
+                ))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::Cyan)
+        .bg(Color::Black);
+        ratatui::widgets::StatefulWidget::render(table, area, buf, &mut state);
+    }
+
+    /// Sockets on the selected node's host, as its sockets monitor last reported them.
+    fn render_sockets(&self, area: Rect, buf: &mut Buffer) {
+        let view = &self.sockets_view;
+        let sockets = self.visible_sockets();
+        let header: Vec<String> = [
+            None,
+            Some(SocketSort::Local),
+            Some(SocketSort::Remote),
+            Some(SocketSort::State),
+            Some(SocketSort::Uid),
+            Some(SocketSort::Inode),
+            Some(SocketSort::Process),
+        ]
+        .iter()
+        .map(|column| match column {
+            Some(sort) if *sort == view.sort => {
+                let arrow = if view.descending { '▼' } else { '▲' };
+                format!("{}{arrow}", sort.title())
+            }
+            Some(sort) => String::from(sort.title()),
+            None => String::from("Proto"),
+        })
+        .collect();
+        let rows: Vec<Row> = sockets
+            .iter()
+            .map(|socket| {
+                let process = match (socket.pid, &socket.process) {
+                    (Some(pid), Some(path)) => format!("{pid} {path}"),
+                    (Some(pid), None) => pid.to_string(),
+                    (None, _) => String::new(),
+                };
+                Row::new(vec![
+                    Cell::from(socket.proto.clone()),
+                    Cell::from(socket.local_endpoint()),
+                    Cell::from(socket.remote_endpoint()),
+                    Cell::from(sockets::state_name(socket.state)),
+                    Cell::from(socket.uid.to_string()),
+                    Cell::from(socket.inode.to_string()),
+                    Cell::from(process),
+                ])
+            })
+            .collect();
+        let mut state = TableState::default().with_selected(
+            sockets
+                .len()
+                .checked_sub(1)
+                .map(|last| std::cmp::min(view.cursor, last)),
+        );
+        let filter = view.state.map_or_else(String::new, |state| {
+            format!(", {}", sockets::state_name(state))
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(7),
+                Constraint::Fill(2),
+                Constraint::Fill(2),
+                Constraint::Length(12),
+                Constraint::Length(6),
+                Constraint::Length(10),
+                Constraint::Fill(3),
+            ],
+        )
+        .header(Row::new(header).bold())
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(
+            Block::bordered()
+                .title(format!(" Sockets ({}{filter}) ", sockets.len()))
+                .title_alignment(Alignment::Center)
+                .title_bottom(self.sockets_status())
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::Cyan)
+        .bg(Color::Black);
+        ratatui::widgets::StatefulWidget::render(table, area, buf, &mut state);
+    }
+
+    /// How fresh the sockets table is, or what the daemon said instead of reporting.
+    fn sockets_status(&self) -> Line<'static> {
+        let Some(monitor) = &self.sockets_view.monitor else {
+            return Line::default();
+        };
+        if let Some(err) = &monitor.error {
+            return Line::styled(format!(" {err} "), Style::default().fg(Color::Red));
+        }
+        let text = if let Some(at) = monitor.updated_at {
+            let age = std::time::SystemTime::now()
+                .duration_since(at)
+                .unwrap_or_default();
+            format!(" Updated {}s ago ", age.as_secs())
+        } else {
+            let status = self
+                .notifications
+                .get(monitor.task_id)
+                .map_or_else(String::new, |record| format!(" ({})", record.outcome));
+            format!(" Waiting for the first report{status} ")
+        };
+        Line::styled(text, Style::default().fg(Color::DarkGray))
+    }
+
+    /// The selected node's firewall chains, with the rules of expanded chains under them,
+    /// and the full description of the selected row.
+    fn render_firewall(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .fg(Color::Cyan)
+            .bg(Color::Black);
+        let Some(firewall) = self
+            .selected()
+            .and_then(|node| node.system_firewall.as_ref())
+        else {
+            let msg = if self.selected().is_some() {
+                "No system firewall received from this daemon yet."
+            } else {
+                "No daemon selected."
+            };
+            Paragraph::new(msg)
+                .fg(Color::DarkGray)
+                .block(block.title(" System Firewall "))
+                .render(area, buf);
+            return;
+        };
+        if self.firewall_view.nft {
+            // Terminal cells don't expand tabs.
+            Paragraph::new(nft::render(firewall).replace('\t', "    "))
+                .scroll((self.firewall_view.nft_scroll, 0))
+                .block(
+                    block
+                        .title(" System Firewall (nft ruleset, read only) ")
+                        .title_bottom(self.firewall_status()),
+                )
+                .render(area, buf);
+            return;
+        }
+        let [table_area, detail_area] =
+            Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+                .areas(area);
+        let rows = self.firewall_rows();
+        let selected = self.selected_firewall_row();
+
+        let table_rows: Vec<Row> = rows
+            .iter()
+            .filter_map(|row| self.firewall_table_row(firewall, *row))
+            .collect();
+        let mut state = TableState::default().with_selected(
+            rows.len()
+                .checked_sub(1)
+                .map(|last| std::cmp::min(self.firewall_view.cursor, last)),
+        );
+        let state_text = if firewall.enabled {
+            "enabled"
+        } else {
+            "disabled"
+        };
+        let table = Table::new(
+            table_rows,
+            [
+                Constraint::Fill(1),
+                Constraint::Length(6),
+                Constraint::Length(6),
+                Constraint::Length(11),
+                Constraint::Length(8),
+                Constraint::Length(8),
+            ],
+        )
+        .header(
+            Row::new(vec![
+                "Chain", "Family", "Type", "Hook", "Priority", "Policy",
+            ])
+            .bold(),
+        )
+        .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan))
+        .block(
+            block
+                .title(format!(
+                    " System Firewall ({state_text}, v{}) ",
+                    firewall.version
+                ))
+                .title_bottom(self.firewall_status()),
+        );
+        ratatui::widgets::StatefulWidget::render(table, table_area, buf, &mut state);
+
+        let detail = selected.map_or_else(String::new, |row| format_fw_detail(firewall, row));
+        Paragraph::new(detail)
+            .wrap(Wrap { trim: false })
+            .block(
+                Block::bordered()
+                    .title(match selected {
+                        Some(FwRow::Rule(..)) => " Firewall Rule ",
+                        _ => " Chain ",
+                    })
+                    .title_alignment(Alignment::Center)
+                    .border_type(BorderType::Rounded),
+            )
+            .fg(Color::Cyan)
+            .bg(Color::Black)
+            .render(detail_area, buf);
+    }
+
+    /// What became of the last action in the firewall view, or how the last change
+    /// sent went.
+    fn firewall_status(&self) -> Line<'static> {
+        match &self.firewall_view.status {
+            Some(Ok(note)) => {
+                return Line::styled(format!(" {note} "), Style::default().fg(Color::Green));
+            }
+            Some(Err(err)) => {
+                return Line::styled(format!(" {err} "), Style::default().fg(Color::Red));
+            }
+            None => {}
+        }
+        self.last_notification(&[pb::Action::ReloadFwRules])
+            .map_or_else(Line::default, |record| {
+                Line::styled(
+                    format!(
+                        " {} #{}: {} ",
+                        record.action().as_str_name(),
+                        record.notification.id,
+                        record.outcome
+                    ),
+                    Style::default().fg(outcome_color(&record.outcome)),
+                )
+            })
+    }
+
+    /// A chain or rule as a row of the firewall table.
+    fn firewall_table_row(&self, firewall: &pb::SysFirewall, row: FwRow) -> Option<Row<'static>> {
+        match row {
+            FwRow::Chain(idx) => {
+                let chain = idx.get(firewall)?;
+                let fold = if self
+                    .firewall_view
+                    .expanded
+                    .contains(&firewall::chain_key(chain))
+                {
+                    '▾'
+                } else {
+                    '▸'
+                };
+                Some(
+                    Row::new(vec![
+                        Cell::from(format!(
+                            "{fold} {} {} ({})",
+                            chain.table,
+                            chain.name,
+                            chain.rules.len()
+                        )),
+                        Cell::from(chain.family.clone()),
+                        Cell::from(chain.r#type.clone()),
+                        Cell::from(chain.hook.clone()),
+                        Cell::from(chain.priority.clone()),
+                        Cell::from(chain.policy.clone()),
+                    ])
+                    .bold(),
+                )
+            }
+            FwRow::Rule(idx, rule) => {
+                let rule = idx.get(firewall)?.rules.get(rule)?;
+                let row = Row::new(vec![
+                    Cell::from(format!("    {}", firewall::rule_statements(rule).join(" "))),
+                    Cell::default(),
+                    Cell::default(),
+                    Cell::default(),
+                    Cell::default(),
+                    Cell::from(rule.target.clone()),
+                ]);
+                Some(if rule.enabled {
+                    row
+                } else {
+                    row.fg(Color::DarkGray)
+                })
+            }
+        }
+    }
+
+    /// Notifications sent to the selected node and how the daemon answered, newest first.
+    fn render_notification_log(&self, area: Rect, buf: &mut Buffer) {
+        let now = std::time::SystemTime::now();
+        let rows: Vec<Row> = self
+            .notification_log()
+            .skip(self.notification_log_offset)
+            .map(|record| {
+                let age_s = now
+                    .duration_since(record.sent_at)
+                    .map_or(0, |age| age.as_secs());
+                let color = outcome_color(&record.outcome);
+                Row::new(vec![
+                    Cell::from(format!("{age_s}s ago")),
+                    Cell::from(format!("#{}", record.notification.id)),
+                    Cell::from(record.action().as_str_name()),
+                    Cell::from(notification_summary(&record.notification)),
+                    Cell::from(record.outcome.to_string()).fg(color),
+                ])
+            })
+            .collect();
+
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(10),
+                Constraint::Length(6),
+                Constraint::Length(20),
+                Constraint::Fill(1),
+                Constraint::Fill(1),
+            ],
+        )
+        .header(Row::new(vec!["Sent", "ID", "Action", "Payload", "Outcome"]).bold())
+        .block(
+            Block::bordered()
+                .title(format!(
+                    " Notifications ({}) ",
+                    self.notification_log().count()
+                ))
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::Cyan)
+        .bg(Color::Black);
+        Widget::render(table, area, buf);
+    }
+
+    /// The rule being edited: its own fields, then its operators indented by nesting.
+    fn render_rule_editor(&self, editor: &RuleEditor, area: Rect, buf: &mut Buffer) {
+        let title = match &editor.editing {
+            Some(name) => format!(" Edit Rule {name} "),
+            None => String::from(" New Rule "),
+        };
+        let block = Block::bordered()
+            .title(title)
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .fg(Color::Cyan)
+            .bg(Color::Black);
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [form_area, status_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+        let rows = editor.rows();
+        let table_rows: Vec<Row> = rows
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| {
+                let field = editor.field(*row);
+                let label = match row {
+                    RuleRow::Rule(_) => field.label.clone(),
+                    RuleRow::Operator(entry, field_idx) => {
+                        let indent = "  ".repeat(editor.operators[*entry].depth + 1);
+                        let bullet = if *field_idx == 0 { "• " } else { "  " };
+                        format!("{indent}{bullet}{}", field.label)
+                    }
+                };
+                let mut value = String::from(field.display());
+                if idx == editor.cursor {
+                    match field.kind {
+                        FieldKind::Choice(_) | FieldKind::Toggle => {
+                            value = format!("◀ {value} ▶");
+                        }
+                        FieldKind::Number { .. } | FieldKind::Text => value.push('▏'),
+                    }
+                }
+                let cells = vec![Cell::from(label), Cell::from(value)];
+                // Mark where the operators start.
+                if *row == RuleRow::Operator(0, 0) {
+                    Row::new(cells).top_margin(1)
+                } else {
+                    Row::new(cells)
+                }
+            })
+            .collect();
+        let mut state = TableState::default().with_selected(Some(editor.cursor));
+        let table = Table::new(table_rows, [Constraint::Length(28), Constraint::Fill(1)])
+            .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+        ratatui::widgets::StatefulWidget::render(table, form_area, buf, &mut state);
+
+        let sent = editor.sent.and_then(|id| self.notifications.get(id));
+        let status = match (&editor.error, sent) {
+            (Some(err), _) => Line::styled(err.clone(), Style::default().fg(Color::Red)),
+            (None, Some(record)) => Line::styled(
+                format!(
+                    "{} #{}: {}",
+                    record.action().as_str_name(),
+                    record.notification.id,
+                    record.outcome
+                ),
+                Style::default().fg(outcome_color(&record.outcome)),
+            ),
+            (None, None) => Line::default(),
+        };
+        Paragraph::new(status).render(status_area, buf);
+    }
+
+    /// The firewall rule editor: the rule's fields, then one group of fields per statement.
+    fn render_fw_rule_editor(&self, editor: &FwRuleEditor, area: Rect, buf: &mut Buffer) {
+        let title = if editor.editing {
+            format!(" Edit Firewall Rule in {} ", editor.chain)
+        } else {
+            format!(" New Firewall Rule in {} ", editor.chain)
+        };
+        let block = Block::bordered()
+            .title(title)
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .fg(Color::Cyan)
+            .bg(Color::Black);
+        let inner = block.inner(area);
+        block.render(area, buf);
+        let [form_area, status_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+        let table_rows: Vec<Row> = editor
+            .rows()
+            .iter()
+            .enumerate()
+            .map(|(idx, row)| {
+                let cells = match (row, editor.field(*row)) {
+                    (FwEditorRow::Expression(entry, _), None) => {
+                        let statement = match &editor.expressions[*entry] {
+                            Expression::Kept(statement) => firewall::statement_summary(statement),
+                            Expression::Built(_) => String::new(),
+                        };
+                        vec![Cell::from("  • Kept as received"), Cell::from(statement)]
+                    }
+                    (_, None) => vec![],
+                    (row, Some(field)) => {
+                        let label = match row {
+                            FwEditorRow::Rule(_) => field.label.clone(),
+                            FwEditorRow::Expression(_, 0) => format!("  • {}", field.label),
+                            FwEditorRow::Expression(..) => format!("    {}", field.label),
+                        };
+                        let mut value = String::from(field.display());
+                        if idx == editor.cursor {
+                            match field.kind {
+                                FieldKind::Choice(_) | FieldKind::Toggle => {
+                                    value = format!("◀ {value} ▶");
+                                }
+                                FieldKind::Number { .. } | FieldKind::Text => value.push('▏'),
+                            }
+                        }
+                        vec![Cell::from(label), Cell::from(value)]
+                    }
+                };
+                // Mark where the statements start.
+                if *row == FwEditorRow::Expression(0, 0) {
+                    Row::new(cells).top_margin(1)
+                } else {
+                    Row::new(cells)
+                }
+            })
+            .collect();
+        let mut state = TableState::default().with_selected(Some(editor.cursor));
+        let table = Table::new(table_rows, [Constraint::Length(28), Constraint::Fill(1)])
+            .row_highlight_style(Style::default().fg(Color::Black).bg(Color::Cyan));
+        ratatui::widgets::StatefulWidget::render(table, form_area, buf, &mut state);
+
+        let sent = editor.sent.and_then(|id| self.notifications.get(id));
+        let status = match (&editor.error, sent) {
+            (Some(err), _) => Line::styled(err.clone(), Style::default().fg(Color::Red)),
+            (None, Some(record)) => Line::styled(
+                format!(
+                    "{} #{}: {}",
+                    record.action().as_str_name(),
+                    record.notification.id,
+                    record.outcome
+                ),
+                Style::default().fg(outcome_color(&record.outcome)),
+            ),
+            (None, None) if editor.expressions.is_empty() => Line::styled(
+                "No statements: the rule matches everything",
+                Style::default().fg(Color::Yellow),
+            ),
+            (None, None) => Line::default(),
+        };
+        Paragraph::new(status).render(status_area, buf);
+    }
+
+    /// Interception and system firewall state, and how the last switch went.
+    fn render_daemon_control(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(" Daemon Control ")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .fg(Color::Cyan)
+            .bg(Color::Black);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let Some(node) = self.selected() else {
+            Paragraph::new("No daemon selected.")
+                .fg(Color::DarkGray)
+                .render(inner, buf);
+            return;
+        };
+        let state = |label: &str, state: Option<bool>| -> Vec<Span> {
+            let (text, color) = match state {
+                Some(true) => ("on", Color::Green),
+                Some(false) => ("off", Color::Red),
+                None => ("unknown", Color::DarkGray),
+            };
+            vec![
+                Span::raw(format!("{label}: ")),
+                Span::styled(text, Style::default().fg(color).bold()),
+            ]
+        };
+        let mut states = state("Interception", node.interception);
+        states.push(Span::raw("    "));
+        states.extend(state("System firewall", node.firewall_running));
+        states.push(Span::raw("    Log level: "));
+        states.push(Span::raw(node.log_level.map_or_else(
+            || String::from("unknown"),
+            |level| settings::log_level_name(level.into()),
+        )));
+        let actions = [CONTROL_ACTIONS.as_slice(), ADMIN_ACTIONS.as_slice()].concat();
+
+        let last = self
+            .last_notification(&actions)
+            .map_or_else(Line::default, |record| {
+                Line::styled(
+                    format!(
+                        "{} #{}: {}",
+                        record.action().as_str_name(),
+                        record.notification.id,
+                        record.outcome
+                    ),
+                    Style::default().fg(outcome_color(&record.outcome)),
+                )
+            });
+        Paragraph::new(vec![
+            Line::from(states),
+            Line::styled(
+                "`i`/`I` → enable/disable interception | `f` → toggle firewall | `r` → reload firewall rules | `a` → log level, stop daemon",
+                Style::default().fg(Color::DarkGray),
+            ),
+            last,
+        ])
+        .render(inner, buf);
+    }
+
+    /// The selected node's settings form, and how the last change went.
+    fn render_settings(&self, area: Rect, buf: &mut Buffer) {
+        let block = Block::bordered()
+            .title(" Daemon Settings ")
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .fg(Color::Cyan)
+            .bg(Color::Black);
+        let inner = block.inner(area);
+        block.render(area, buf);
+
+        let Some(editor) = &self.settings else {
+            let msg = if self.selected().is_some() {
+                "No configuration received from this daemon yet."
+            } else {
+                "No daemon selected."
+            };
+            Paragraph::new(msg).fg(Color::DarkGray).render(inner, buf);
+            return;
+        };
+        let [form_area, status_area] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(inner);
+
+        let rows: Vec<Row> = editor
+            .form
+            .fields
+            .iter()
+            .enumerate()
+            .map(|(idx, field)| {
+                let selected = idx == editor.form.cursor;
+                let mut value = String::from(field.display());
+                if selected && matches!(field.kind, FieldKind::Number { .. }) {
+                    value.push('▏');
+                } else if selected {
+                    value = format!("◀ {value} ▶");
+                }
+                let marker = if field.changed() { "*" } else { "" };
+                let row = Row::new(vec![
+                    Cell::from(format!("{}{marker}", field.label)),
+                    Cell::from(value),
+                ]);
+                match (selected, field.validate()) {
+                    (true, _) => row.style(Style::default().fg(Color::Black).bg(Color::Cyan)),
+                    (false, Err(_)) => row.fg(Color::Red),
+                    (false, Ok(())) if field.changed() => row.fg(Color::Yellow),
+                    (false, Ok(())) => row,
+                }
+            })
+            .collect();
+        Widget::render(
+            Table::new(rows, [Constraint::Length(24), Constraint::Fill(1)]),
+            form_area,
+            buf,
+        );
+
+        let sent = editor.sent.and_then(|id| self.notifications.get(id));
+        let status = match (&editor.error, sent) {
+            (Some(err), _) => Line::styled(err.clone(), Style::default().fg(Color::Red)),
+            (None, Some(record)) => Line::styled(
+                format!(
+                    "{} #{}: {}",
+                    record.action().as_str_name(),
+                    record.notification.id,
+                    record.outcome
+                ),
+                Style::default().fg(outcome_color(&record.outcome)),
+            ),
+            (None, None) if editor.form.is_dirty() => Line::styled(
+                "Edited, `enter` to send to the daemon",
+                Style::default().fg(Color::Yellow),
+            ),
+            (None, None) => Line::default(),
+        };
+        Paragraph::new(status).render(status_area, buf);
+    }
+
+    /// Connection prompt details, next to the queue of prompts pending for the selected node.
+    fn render_connections(&self, area: Rect, buf: &mut Buffer) {
+        let pending = self
+            .selected()
+            .map_or(0, |node| node.pending_connections.len());
+        let title = match self.selected() {
+            Some(node) if pending > 0 => {
+                format!(" New Connections ({}/{pending}) ", node.prompt_cursor + 1)
+            }
+            _ => String::from(" New Connections "),
+        };
+        let connection_block = Block::bordered()
+            .title(title)
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded)
+            .title_style(match self.current_connection() {
+                None => Style::default(),
+                Some(_) => Style::default().bold(),
+            })
+            .style(match self.current_connection() {
+                None => Style::default().fg(Color::Cyan),
+                Some(_) => Style::default().fg(Color::Yellow),
+            });
+
+        // Only give up room for the queue when there's more than one prompt to pick from.
+        let [details_area, queue_area] = if pending > 1 {
+            Layout::horizontal([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .areas(connection_block.inner(area))
+        } else {
+            [connection_block.inner(area), Rect::default()]
+        };
+        connection_block.render(area, buf);
+
+        let connection_text = self.format_connection_panel();
+        Paragraph::new(connection_text)
+            .bg(Color::Black)
+            .render(details_area, buf);
+
+        if let Some(node) = self.selected()
+            && pending > 1
+        {
+            let now = std::time::SystemTime::now();
+            let items: Vec<ListItem> = node
+                .pending_connections
+                .iter()
+                .enumerate()
+                .map(|(idx, evt)| {
+                    let remaining_s = evt
+                        .expiry_ts
+                        .duration_since(now)
+                        .map_or(0, |remaining| remaining.as_secs());
+                    let conn = &evt.connection;
+                    let dst = if conn.dst_host.is_empty() {
+                        &conn.dst_ip
+                    } else {
+                        &conn.dst_host
+                    };
+                    let text = format!(
+                        "{remaining_s:>3}s {} → {dst}:{}",
+                        conn.process_path, conn.dst_port
+                    );
+                    if idx == node.prompt_cursor {
+                        ListItem::new(text)
+                            .style(Style::default().fg(Color::Black).bg(Color::Yellow))
+                    } else {
+                        ListItem::new(text)
+                    }
+                })
+                .collect();
+            List::new(items)
+                .block(Block::new().borders(ratatui::widgets::Borders::LEFT))
+                .bg(Color::Black)
+                .render(queue_area, buf);
+        }
+    }
+
+    /// Liveness badge for the selected node.
+    fn health_status(&self) -> Option<Line<'_>> {
+        let node = self.selected()?;
+        let now = std::time::SystemTime::now();
+        let health = node.health(now, self.stale_threshold);
+        let silence = node
+            .last_ping
+            .and_then(|ts| now.duration_since(ts).ok())
+            .map_or(0, |silence| silence.as_secs());
+        let (text, color) = match health {
+            Health::Connected => (String::from(" ● connected "), Color::Green),
+            Health::Stale => (format!(" ▲ STALE: no ping for {silence}s "), Color::Yellow),
+            Health::Disconnected if node.stream_state == StreamState::Closed => {
+                (String::from(" ✖ DISCONNECTED: stream closed "), Color::Red)
+            }
+            Health::Disconnected => (
+                format!(" ✖ DISCONNECTED: no ping for {silence}s "),
+                Color::Red,
+            ),
+        };
+        Some(Line::styled(text, Style::default().fg(color).bold()))
+    }
+
+    /// Badge for whether the gRPC server is up, kept in view on every screen.
+    fn server_status(&self) -> Line<'_> {
+        let (text, color) = match &self.server_state {
+            ServerState::Listening => (format!(" ● {} ", self.server.address()), Color::Green),
+            ServerState::Stopped => (String::from(" ○ server stopped "), Color::DarkGray),
+            ServerState::Failed(_) => (String::from(" ✖ server failed "), Color::Red),
+        };
+        Line::styled(text, Style::default().fg(color).bold())
+    }
+
+    /// One-line view tabs and node selector.
+    /// Nodes with connections awaiting disposition get a `!` and count.
+    fn top_bar(&self) -> Paragraph<'_> {
+        let mut spans = Vec::new();
+        for view in View::ALL {
+            let text = format!(" {} ", view.title());
+            if view == self.view {
+                spans.push(Span::styled(
+                    text,
+                    Style::default().fg(Color::Black).bg(Color::White),
+                ));
+            } else {
+                spans.push(Span::styled(text, Style::default().fg(Color::White)));
+            }
+        }
+        spans.push(Span::raw(" │"));
+        if self.nodes.is_empty() {
+            spans.push(Span::styled(
+                " Waiting for a daemon to connect... ",
+                Style::default().fg(Color::DarkGray),
+            ));
+        } else {
+            spans.push(Span::raw(" Nodes: "));
+        }
+        for node in self.nodes.values() {
+            let pending = node.pending_connections.len();
+            let marker = if pending > 0 {
+                format!(" !{pending}")
+            } else {
+                String::new()
+            };
+            let text = format!(" {}{marker} ", node.label());
+            let health_color = match node.last_health {
+                Health::Connected => Color::Cyan,
+                Health::Stale => Color::Yellow,
+                Health::Disconnected => Color::Red,
+            };
+            if self.selected_node.as_ref() == Some(&node.id) {
+                spans.push(Span::styled(
+                    text,
+                    Style::default().fg(Color::Black).bg(health_color),
+                ));
+            } else if pending > 0 {
+                spans.push(Span::styled(
+                    text,
+                    Style::default().fg(Color::Yellow).bold(),
+                ));
+            } else {
+                spans.push(Span::styled(text, Style::default().fg(health_color)));
+            }
+        }
+        Paragraph::new(Line::from(spans))
+            .fg(Color::Cyan)
+            .bg(Color::Black)
     }
-}
 
-impl App {
     fn format_stats_panel(&self) -> String {
-        match &self.current_stats {
-            Some(stats) => {
+        let stats = self.format_stats();
+        match self.selected().and_then(|node| node.config.as_ref()) {
+            Some(config) => {
+                let unset = String::from("-");
+                format!(
+                    "{stats}\n\
+                        default: {} {} | proc monitor: {} | log level: {} | intercept unknown: {}",
+                    config.DefaultAction.as_ref().unwrap_or(&unset),
+                    config.DefaultDuration.as_ref().unwrap_or(&unset),
+                    config.ProcMonitorMethod.as_ref().unwrap_or(&unset),
+                    config
+                        .LogLevel
+                        .map_or(unset.clone(), |level| level.to_string()),
+                    config
+                        .InterceptUnknown
+                        .map_or(unset.clone(), |intercept| intercept.to_string()),
+                )
+            }
+            None => stats,
+        }
+    }
+
+    fn format_stats(&self) -> String {
+        match self.selected() {
+            Some(Node {
+                rx_pings,
+                stats: Some(stats),
+                ..
+            }) => {
                 format!(
                     "\
                         rx pings: {} | daemon version: {} | rules: {}\n\
                         uptime: {} | dns_responses: {} | connections: {}\n\
                         ignored: {} | accepted: {} | dropped: {}\n\
                         rule_hits: {} | rule_misses: {}",
-                    self.rx_pings,
+                    rx_pings,
                     stats.daemon_version,
                     stats.rules,
                     stats.uptime,
@@ -133,12 +1518,12 @@ impl App {
                     stats.rule_misses,
                 )
             }
-            None => String::default(), // Consider a more useful message in the future?
+            _ => String::default(), // Consider a more useful message in the future?
         }
     }
 
     fn format_connection_panel(&self) -> String {
-        match &self.current_connection {
+        match self.current_connection() {
             None => String::default(),
             Some(info) => {
                 // Don't just leave field blank if not populated.
@@ -171,3 +1556,266 @@ impl App {
         }
     }
 }
+
+/// Everything about a rule, with its operator tree last.
+/// Everything an event says about a connection, and the rule it matched.
+fn format_event_detail(event: &pb::Event) -> String {
+    let connection = event.connection.clone().unwrap_or_default();
+    let or_dash = |text: &str| {
+        if text.is_empty() {
+            String::from("-")
+        } else {
+            String::from(text)
+        }
+    };
+    let mut lines = vec![
+        format!("time        {}", event.time),
+        format!(
+            "src         {} / {}",
+            connection.src_ip, connection.src_port
+        ),
+        format!(
+            "dst         {} / {}",
+            connection.dst_ip, connection.dst_port
+        ),
+        format!("dst host    {}", or_dash(&connection.dst_host)),
+        format!("proto       {}", connection.protocol),
+        format!("uid         {}", connection.user_id),
+        format!("pid         {}", connection.process_id),
+        format!("ppath       {}", connection.process_path),
+        format!("cwd         {}", or_dash(&connection.process_cwd)),
+        format!(
+            "args        {}",
+            or_dash(&connection.process_args.join(" "))
+        ),
+        String::new(),
+    ];
+    match &event.rule {
+        Some(rule) => lines.push(format_rule_detail(rule)),
+        None => lines.push(String::from("(no rule)")),
+    }
+    lines.join("\n")
+}
+
+fn format_rule_detail(rule: &pb::Rule) -> String {
+    let description = if rule.description.is_empty() {
+        "-"
+    } else {
+        &rule.description
+    };
+    let mut lines = vec![
+        format!("name        {}", rule.name),
+        format!("description {description}"),
+        format!("action      {}", rule.action),
+        format!("duration    {}", rule.duration),
+        format!("enabled     {}", rule.enabled),
+        format!("precedence  {}", rule.precedence),
+        format!("nolog       {}", rule.nolog),
+        format!("created     {}", rule.created),
+        String::new(),
+    ];
+    match &rule.operator {
+        Some(operator) => lines.extend(rules::operator_tree(operator)),
+        None => lines.push(String::from("(no operator)")),
+    }
+    lines.join("\n")
+}
+
+/// Everything about a firewall chain or rule, for the detail pane.
+fn format_fw_detail(firewall: &pb::SysFirewall, row: FwRow) -> String {
+    let Some(chain) = row.chain().get(firewall) else {
+        return String::new();
+    };
+    let location = format!("{} {} {}", chain.family, chain.table, chain.name);
+    let or_dash = |value: &str| {
+        if value.is_empty() {
+            String::from("-")
+        } else {
+            String::from(value)
+        }
+    };
+    match row {
+        FwRow::Chain(_) => [
+            format!("chain       {location}"),
+            format!("type        {}", or_dash(&chain.r#type)),
+            format!("hook        {}", or_dash(&chain.hook)),
+            format!("priority    {}", or_dash(&chain.priority)),
+            format!("policy      {}", or_dash(&chain.policy)),
+            format!("rules       {}", chain.rules.len()),
+        ]
+        .join("\n"),
+        FwRow::Rule(_, idx) => {
+            let Some(rule) = chain.rules.get(idx) else {
+                return String::new();
+            };
+            let mut lines = vec![
+                format!("chain       {location}"),
+                format!("description {}", or_dash(&rule.description)),
+                format!("uuid        {}", or_dash(&rule.uuid)),
+                format!("position    {}", rule.position),
+                format!("enabled     {}", rule.enabled),
+                format!("target      {}", firewall::rule_target(rule)),
+            ];
+            if !rule.parameters.is_empty() {
+                lines.push(format!("parameters  {}", rule.parameters));
+            }
+            lines.push(String::new());
+            let statements = firewall::rule_statements(rule);
+            if statements.is_empty() {
+                lines.push(String::from("(matches everything)"));
+            }
+            lines.extend(statements.iter().map(|statement| format!("• {statement}")));
+            lines.join("\n")
+        }
+    }
+}
+
+/// A yes/no question, or one answered by typing its challenge, boxed in the middle of `area`.
+fn render_confirmation(confirmation: &Confirmation, area: Rect, buf: &mut Buffer) {
+    let prompt = confirmation.prompt.as_str();
+    let answer = match &confirmation.challenge {
+        Some(challenge) => Line::from(vec![
+            Span::raw(format!("Type '{challenge}' to confirm: ")),
+            Span::raw(format!("{}_", confirmation.typed)).bold(),
+        ]),
+        None => Line::from("y / n").bold(),
+    };
+    let width = u16::try_from(prompt.chars().count())
+        .unwrap_or(u16::MAX)
+        .saturating_add(4)
+        .min(area.width);
+    let [popup_area] = Layout::vertical([Constraint::Length(5)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [popup_area] = Layout::horizontal([Constraint::Length(width)])
+        .flex(Flex::Center)
+        .areas(popup_area);
+    Clear.render(popup_area, buf);
+    Paragraph::new(vec![Line::from(prompt), Line::default(), answer])
+        .alignment(Alignment::Center)
+        .block(
+            Block::bordered()
+                .title(" Confirm ")
+                .title_alignment(Alignment::Center)
+                .border_type(BorderType::Rounded),
+        )
+        .fg(Color::Yellow)
+        .bg(Color::Black)
+        .render(popup_area, buf);
+}
+
+/// The admin menu, boxed in the middle of `area`.
+fn render_admin_menu(menu: &AdminMenu, area: Rect, buf: &mut Buffer) {
+    let [popup_area] = Layout::vertical([Constraint::Length(4)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [popup_area] = Layout::horizontal([Constraint::Length(40)])
+        .flex(Flex::Center)
+        .areas(popup_area);
+    let entry = |item: admin::Item, text: String| {
+        if menu.item == item {
+            Line::styled(
+                format!("▶ {text}"),
+                Style::default().fg(Color::Black).bg(Color::Cyan),
+            )
+        } else {
+            Line::from(format!("  {text}"))
+        }
+    };
+    Clear.render(popup_area, buf);
+    Paragraph::new(vec![
+        entry(
+            admin::Item::LogLevel,
+            format!("{}: ◀ {} ▶", menu.log_level.label, menu.log_level.display()),
+        ),
+        entry(admin::Item::Stop, String::from("Stop daemon…")),
+    ])
+    .block(
+        Block::bordered()
+            .title(format!(" Daemon Admin: {} ", menu.node))
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded),
+    )
+    .fg(Color::Cyan)
+    .bg(Color::Black)
+    .render(popup_area, buf);
+}
+
+/// The firewall export prompt, boxed in the middle of `area`.
+fn render_fw_export(export: &firewall::Export, area: Rect, buf: &mut Buffer) {
+    let [popup_area] = Layout::vertical([Constraint::Length(5)])
+        .flex(Flex::Center)
+        .areas(area);
+    let [popup_area] = Layout::horizontal([Constraint::Percentage(60)])
+        .flex(Flex::Center)
+        .areas(popup_area);
+    let question = if export.overwrite {
+        Line::from("The file exists. Replace it? y / n").bold()
+    } else {
+        Line::default()
+    };
+    Clear.render(popup_area, buf);
+    Paragraph::new(vec![
+        Line::from(vec![
+            Span::raw(format!("{}: ", export.path.label)),
+            Span::raw(format!("{}_", export.path.value)).bold(),
+        ]),
+        Line::default(),
+        question,
+    ])
+    .block(
+        Block::bordered()
+            .title(format!(" Export Firewall: {} ", export.node))
+            .title_alignment(Alignment::Center)
+            .border_type(BorderType::Rounded),
+    )
+    .fg(Color::Cyan)
+    .bg(Color::Black)
+    .render(popup_area, buf);
+}
+
+/// A byte count in the largest unit that keeps it above one, or a dash if unknown.
+fn format_bytes(bytes: Option<u64>) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+    let Some(bytes) = bytes else {
+        return String::from("-");
+    };
+    let mut unit = 0;
+    let mut scaled = bytes;
+    while scaled >= 1024 && unit < UNITS.len() - 1 {
+        scaled /= 1024;
+        unit += 1;
+    }
+    if unit == 0 {
+        return format!("{bytes} B");
+    }
+    // One decimal place, worked out in integers.
+    let tenths = bytes * 10 / 1024u64.pow(u32::try_from(unit).unwrap_or_default()) % 10;
+    format!("{scaled}.{tenths} {}", UNITS[unit])
+}
+
+/// Color for a notification outcome: pending, good or bad.
+fn outcome_color(outcome: &Outcome) -> Color {
+    match outcome {
+        Outcome::Pending => Color::Yellow,
+        Outcome::Ok(_) => Color::Green,
+        _ => Color::Red,
+    }
+}
+
+/// Short description of what a notification carries, for the log view.
+fn notification_summary(notification: &pb::Notification) -> String {
+    if !notification.rules.is_empty() {
+        let names: Vec<&str> = notification
+            .rules
+            .iter()
+            .map(|rule| rule.name.as_str())
+            .collect();
+        return names.join(", ");
+    }
+    if let Some(fw) = &notification.sys_firewall {
+        let chains: usize = fw.system_rules.iter().map(|c| c.chains.len()).sum();
+        return format!("firewall, {chains} chains");
+    }
+    notification.data.clone()
+}